@@ -12,12 +12,12 @@ fn main() {
     for i in 0..3 {
         println!("Executing run {}", i);
         let mut run = TrackingRun::new();
-        run.log_param("i", &format!("{}", i));
+        run.log_param("i", i);
         run.log_param("constant", "42");
         let mut rng = WyRand::new_seed(i);
         for s in 0..10 {
             let int: f64 = rng.generate::<u16>().into();
-            let max: f64 = std::u16::MAX.into();
+            let max: f64 = u16::MAX.into();
             let value = int / max;
             run.log_metric("rand", value, s);
         }
@@ -1,8 +1,11 @@
+pub mod artifact;
 pub mod client;
 pub mod error;
 pub mod experiment;
 pub mod id;
 pub mod metric;
+pub mod pagination;
+pub mod params;
 pub mod run;
 pub mod search;
 
@@ -11,6 +14,26 @@ pub mod limits {
     pub const BATCH_METRICS: usize = 1000;
     pub const BATCH_PARAMS: usize = 100;
     pub const BATCH_TAGS: usize = 100;
+    /// The longest param value accepted by all MLflow versions.
+    pub const PARAM_VALUE_LENGTH: usize = 500;
+}
+
+/// Run tags with a special meaning to MLflow.
+pub mod tags {
+    /// Markdown description of the run, shown in the UI.
+    pub const NOTE: &str = "mlflow.note.content";
+    /// The id of the run this run is nested in.
+    pub const PARENT_RUN_ID: &str = "mlflow.parentRunId";
+    pub const RUN_NAME: &str = "mlflow.runName";
+    pub const USER: &str = "mlflow.user";
+    pub const SOURCE_NAME: &str = "mlflow.source.name";
+    /// One of `NOTEBOOK`, `JOB`, `PROJECT`, `LOCAL` or `UNKNOWN`.
+    pub const SOURCE_TYPE: &str = "mlflow.source.type";
+    pub const GIT_COMMIT: &str = "mlflow.source.git.commit";
+    pub const GIT_BRANCH: &str = "mlflow.source.git.branch";
+    pub const GIT_REPO_URL: &str = "mlflow.source.git.repoURL";
+    /// A JSON list of the artifacts with a special view in the UI, like tables.
+    pub const LOGGED_ARTIFACTS: &str = "mlflow.loggedArtifacts";
 }
 
 // serialize i64 as str
@@ -63,3 +86,45 @@ mod opt_str_int {
         s.serialize(serializer)
     }
 }
+// serialize NaN and infinite f64 as the strings used by protobuf JSON, since JSON has no numbers for them
+pub(crate) mod non_finite {
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Float {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<f64, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Float::deserialize(deserializer)? {
+            Float::Number(value) => Ok(value),
+            Float::Text(text) => match text.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                other => other.parse().map_err(de::Error::custom),
+            },
+        }
+    }
+
+    pub fn serialize<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if value.is_nan() {
+            serializer.serialize_str("NaN")
+        } else if value.is_infinite() && *value > 0.0 {
+            serializer.serialize_str("Infinity")
+        } else if value.is_infinite() {
+            serializer.serialize_str("-Infinity")
+        } else {
+            serializer.serialize_f64(*value)
+        }
+    }
+}
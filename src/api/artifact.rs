@@ -0,0 +1,165 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::api::error::StorageError;
+
+/// An entry of a directory of artifacts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileInfo {
+    /// The path relative to the `artifact_uri` of the run, separated by `/`.
+    pub path: String,
+    pub is_dir: bool,
+    /// The size in bytes, which is missing for directories.
+    #[serde(default)]
+    pub file_size: Option<u64>,
+}
+
+/// Where the artifacts of a run are stored, as given by its `artifact_uri`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArtifactLocation {
+    /// A directory on the local file system, given by a `file:` uri or a plain path.
+    Local(PathBuf),
+    /// The artifact proxy of a tracking server, given by a `mlflow-artifacts:` uri.
+    Proxied {
+        /// The `host:port` of the server, if it differs from the tracking server.
+        authority: Option<String>,
+        path: String,
+    },
+}
+
+impl ArtifactLocation {
+    pub fn parse(artifact_uri: &str) -> Result<Self, StorageError> {
+        if let Some(rest) = artifact_uri.strip_prefix("mlflow-artifacts:") {
+            let (authority, path) = match rest.strip_prefix("//") {
+                Some(rest) => {
+                    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+                    (Some(authority.to_string()), path)
+                }
+                None => (None, rest),
+            };
+            Ok(ArtifactLocation::Proxied {
+                authority: authority.filter(|authority| !authority.is_empty()),
+                path: path.trim_matches('/').to_string(),
+            })
+        } else if let Some(path) = artifact_uri.strip_prefix("file://") {
+            Ok(ArtifactLocation::Local(PathBuf::from(path)))
+        } else if let Some(path) = artifact_uri.strip_prefix("file:") {
+            Ok(ArtifactLocation::Local(PathBuf::from(path)))
+        } else if !artifact_uri.contains("://") {
+            Ok(ArtifactLocation::Local(PathBuf::from(artifact_uri)))
+        } else {
+            bail!("the artifact location {} is not supported", artifact_uri)
+        }
+    }
+}
+
+/// Ensures the artifact path stays inside the artifact directory of the run.
+pub fn validate_path(path: &str) -> Result<(), StorageError> {
+    let valid = !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if !valid {
+        bail!(
+            "the artifact path {:?} must be relative and not contain `..`",
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Stores an artifact in a local artifact directory.
+pub fn write_local(root: &Path, path: &str, contents: &[u8]) -> Result<(), StorageError> {
+    validate_path(path)?;
+    let target = root.join(path);
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(&target, contents)
+        .with_context(|| format!("failed to write {}", target.display()))?;
+    Ok(())
+}
+
+/// Reads an artifact from a local artifact directory.
+pub fn read_local(root: &Path, path: &str) -> Result<Vec<u8>, StorageError> {
+    validate_path(path)?;
+    let source = root.join(path);
+    std::fs::read(&source).with_context(|| format!("failed to read {}", source.display()))
+}
+
+/// Lists the directory `path` of a local artifact directory, where `""` is the root.
+///
+/// A missing directory is empty, like on a tracking server.
+pub fn list_local(root: &Path, path: &str) -> Result<Vec<FileInfo>, StorageError> {
+    let path = path.trim_matches('/');
+    if !path.is_empty() {
+        validate_path(path)?;
+    }
+    let dir = root.join(path);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to list {}", dir.display()))
+        }
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("failed to list {}", dir.display()))?;
+        let metadata = entry.metadata()?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        files.push(FileInfo {
+            path: join(path, &name),
+            is_dir: metadata.is_dir(),
+            file_size: Some(metadata.len()).filter(|_| !metadata.is_dir()),
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Joins artifact paths, where `""` is the root.
+pub(crate) fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else if name.is_empty() {
+        dir.to_string()
+    } else {
+        format!("{}/{}", dir.trim_end_matches('/'), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArtifactLocation;
+
+    #[test]
+    fn parse_artifact_uris() {
+        assert_eq!(
+            ArtifactLocation::parse("mlflow-artifacts:/0/abc/artifacts").unwrap(),
+            ArtifactLocation::Proxied {
+                authority: None,
+                path: "0/abc/artifacts".to_string()
+            }
+        );
+        assert_eq!(
+            ArtifactLocation::parse("mlflow-artifacts://host:5000/0/abc/artifacts").unwrap(),
+            ArtifactLocation::Proxied {
+                authority: Some("host:5000".to_string()),
+                path: "0/abc/artifacts".to_string()
+            }
+        );
+        assert_eq!(
+            ArtifactLocation::parse("file:///tmp/mlruns/0/abc/artifacts").unwrap(),
+            ArtifactLocation::Local("/tmp/mlruns/0/abc/artifacts".into())
+        );
+        assert_eq!(
+            ArtifactLocation::parse("./mlruns/0/abc/artifacts").unwrap(),
+            ArtifactLocation::Local("./mlruns/0/abc/artifacts".into())
+        );
+        assert!(ArtifactLocation::parse("s3://bucket/path").is_err());
+    }
+}
@@ -1,7 +1,7 @@
-use crate::api::{error::*, experiment::*, id::*, run::*, search::*};
+use crate::api::{artifact::*, error::*, experiment::*, id::*, run::*, search::*};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ViewType {
     #[serde(rename = "ACTIVE_ONLY")]
     Active,
@@ -30,12 +30,56 @@ pub trait Client {
     fn create_run(&mut self, experiment: &ExperimentId, start_time: i64, tags: &[RunTag]) -> Result<Run, StorageError>;
     fn delete_run(&mut self, id: &RunId) -> Result<(), DeleteError>;
     fn get_run(&mut self, id: &RunId) -> Result<Run, GetError>;
-    fn update_run(&mut self, id: &RunId, status: RunStatus, end_time: i64) -> Result<RunInfo, UpdateError>;
-    fn search_runs(&mut self, experiment_ids: &[&ExperimentId], filter: &str, run_view_type: ViewType, max_results: i32, order_by: Option<&str>, page_token: Option<&str>) -> Result<Search, StorageError>;
-    fn list_run_infos(&mut self, experiment: &ExperimentId, run_view_type: ViewType, max_results: i32, order_by: Option<&str>, page_token: Option<&str>) -> Result<RunList, StorageError>;
-    fn get_metric_history(&mut self, run: &RunId, metric: &str) -> Result<Vec<Metric>, GetError>;
+    fn update_run(&mut self, id: &RunId, status: RunStatus, end_time: Option<i64>) -> Result<RunInfo, UpdateError>;
+    fn search_runs(&mut self, request: &SearchRunsRequest) -> Result<Search, StorageError>;
+    fn list_run_infos(&mut self, request: &ListRunsRequest) -> Result<RunList, StorageError>;
+    fn get_metric_history(&mut self, run: &RunId, metric: &str) -> Result<Vec<Metric<'static>>, GetError>;
 
     fn log_param(&mut self, run: &RunId, key: &str, value: &str) -> Result<(), StorageError>;
     fn log_metric(&mut self, run: &RunId, key: &str, value: f64, timestamp: i64, step: i64) -> Result<(), StorageError>;
     fn log_batch(&mut self, run: &RunId, metrics: &[Metric], params: &[Param], tags: &[RunTag]) -> Result<(), BatchError>;
+
+    /// Restores a deleted experiment.
+    ///
+    /// The default implementation returns an error, as restoring is not supported.
+    fn restore_experiment(&mut self, id: &ExperimentId) -> Result<(), UpdateError> {
+        Err(anyhow::anyhow!("this client cannot restore the experiment {}", id.as_ref()).into())
+    }
+
+    /// Restores a deleted run.
+    ///
+    /// The default implementation returns an error, as restoring is not supported.
+    fn restore_run(&mut self, id: &RunId) -> Result<(), UpdateError> {
+        Err(anyhow::anyhow!("this client cannot restore the run {}", id.as_ref()).into())
+    }
+
+    /// Stores `contents` as the artifact at `path` below the `artifact_uri` of the run.
+    ///
+    /// The default implementation only supports local artifact locations.
+    fn upload_artifact(&mut self, run: &RunInfo, path: &str, contents: &[u8]) -> Result<(), StorageError> {
+        match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => write_local(&root, path, contents),
+            ArtifactLocation::Proxied { .. } => Err(anyhow::anyhow!("this client does not support the mlflow-artifacts proxy")),
+        }
+    }
+
+    /// Lists the artifacts directly inside the directory `path` of the run, where `""` is the root.
+    ///
+    /// The default implementation only supports local artifact locations.
+    fn list_artifacts(&mut self, run: &RunInfo, path: &str) -> Result<Vec<FileInfo>, StorageError> {
+        match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => list_local(&root, path),
+            ArtifactLocation::Proxied { .. } => Err(anyhow::anyhow!("this client does not support the mlflow-artifacts proxy")),
+        }
+    }
+
+    /// Reads the artifact at `path` below the `artifact_uri` of the run.
+    ///
+    /// The default implementation only supports local artifact locations.
+    fn download_artifact(&mut self, run: &RunInfo, path: &str) -> Result<Vec<u8>, StorageError> {
+        match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => read_local(&root, path),
+            ArtifactLocation::Proxied { .. } => Err(anyhow::anyhow!("this client does not support the mlflow-artifacts proxy")),
+        }
+    }
 }
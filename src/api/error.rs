@@ -1,5 +1,10 @@
 use thiserror::Error;
 
+use crate::api::{
+    limits,
+    run::{Metric, Param, RunTag},
+};
+
 pub type StorageError = anyhow::Error;
 
 #[derive(Error, Debug)]
@@ -28,9 +33,47 @@ pub enum BatchError {
     ToManyParams(usize),
     #[error("only up to 100 tags can be logged at once, found {0}")]
     ToManyTags(usize),
+    #[error("the param {key} was logged with the conflicting values {first:?} and {second:?}")]
+    ConflictingParam {
+        key: String,
+        first: String,
+        second: String,
+    },
     #[error("an error ocurred in the storage backend: {0:?}")]
     Storage(#[from] StorageError),
 }
 
+impl BatchError {
+    /// Checks that the batch respects the [`limits`] and contains no conflicting params.
+    pub(crate) fn check(metrics: &[Metric], params: &[Param], tags: &[RunTag]) -> Result<(), Self> {
+        if metrics.len() > limits::BATCH_METRICS {
+            return Err(BatchError::ToManyMetrics(metrics.len()));
+        }
+        if params.len() > limits::BATCH_PARAMS {
+            return Err(BatchError::ToManyParams(params.len()));
+        }
+        if tags.len() > limits::BATCH_TAGS {
+            return Err(BatchError::ToManyTags(tags.len()));
+        }
+        let total_len = metrics.len() + params.len() + tags.len();
+        if total_len > limits::BATCH_TOTAL {
+            return Err(BatchError::ToManyItems(total_len));
+        }
+        for (i, param) in params.iter().enumerate() {
+            let conflict = params[..i]
+                .iter()
+                .find(|other| other.key == param.key && other.value != param.value);
+            if let Some(other) = conflict {
+                return Err(BatchError::ConflictingParam {
+                    key: param.key.clone(),
+                    first: other.value.clone(),
+                    second: param.value.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
 pub type DeleteError = GetError;
 pub type UpdateError = GetError;
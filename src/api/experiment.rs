@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::ExperimentId;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Experiment {
     pub experiment_id: ExperimentId,
     pub name: String,
@@ -13,7 +13,7 @@ pub struct Experiment {
     pub tags: Option<Vec<ExperimentTag>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExperimentTag {
     pub key: String,
     pub value: String,
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 // EXPERIMENTS
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct ExperimentId(String);
 
@@ -26,7 +26,7 @@ impl From<&str> for ExperimentId {
 
 // RUNS
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct RunId(String);
 
@@ -0,0 +1,329 @@
+use crate::{
+    api::{
+        client::{Client, ViewType},
+        error::{GetError, StorageError},
+        experiment::Experiment,
+        run::{Metric, Run, RunInfo},
+        search::{ListRunsRequest, PageToken, SearchRunsRequest},
+        tags,
+    },
+    ExperimentId, RunId,
+};
+
+/// Iterators over [`Client`] listings which follow page tokens on their own.
+///
+/// This is implemented for every [`Client`], including `dyn Client`.
+pub trait Paginate: Client {
+    /// Starts a run search over the given experiments.
+    fn runs(&mut self, experiment_ids: &[&ExperimentId]) -> RunSearch<'_, Self>;
+
+    /// Starts a search for the runs nested in `parent`.
+    #[doc(alias = "get_child_runs")]
+    fn child_runs(&mut self, parent: &RunInfo) -> RunSearch<'_, Self>;
+
+    /// Starts a run search with the given parameters.
+    ///
+    /// The `max_results` of the request are used as page size.
+    fn search(&mut self, request: SearchRunsRequest) -> RunSearch<'_, Self>;
+
+    /// Iterates over all experiments of the given view type.
+    ///
+    /// The REST API does not page this listing, so it is fetched with the first call to `next`.
+    fn experiments(&mut self, view_type: ViewType) -> Pages<'_, Self, Experiment>;
+
+    /// Iterates over all values logged for the metric `key` of the given run.
+    ///
+    /// The REST API does not page the history, so it is fetched with the first call to `next`.
+    fn metric_history<'a>(
+        &'a mut self,
+        run: &'a RunId,
+        key: &'a str,
+    ) -> Pages<'a, Self, Metric<'static>, GetError>;
+}
+
+impl<C: Client + ?Sized> Paginate for C {
+    fn runs(&mut self, experiment_ids: &[&ExperimentId]) -> RunSearch<'_, Self> {
+        self.search(SearchRunsRequest::new(experiment_ids.iter().copied()))
+    }
+
+    fn child_runs(&mut self, parent: &RunInfo) -> RunSearch<'_, Self> {
+        let filter = format!(
+            "tags.`{}` = '{}'",
+            tags::PARENT_RUN_ID,
+            parent.run_id.as_ref()
+        );
+        self.search(SearchRunsRequest::new(std::iter::once(&parent.experiment_id)).filter(filter))
+    }
+
+    fn search(&mut self, request: SearchRunsRequest) -> RunSearch<'_, Self> {
+        RunSearch {
+            client: self,
+            request,
+            limit: None,
+        }
+    }
+
+    fn experiments(&mut self, view_type: ViewType) -> Pages<'_, Self, Experiment> {
+        Pages::new(
+            self,
+            i32::MAX,
+            None,
+            Box::new(move |client, _, _| {
+                let experiments = client.list_experiments(view_type)?;
+                Ok((experiments, PageToken::default()))
+            }),
+        )
+    }
+
+    fn metric_history<'a>(
+        &'a mut self,
+        run: &'a RunId,
+        key: &'a str,
+    ) -> Pages<'a, Self, Metric<'static>, GetError> {
+        Pages::new(
+            self,
+            i32::MAX,
+            None,
+            Box::new(move |client, _, _| {
+                let metrics = client.get_metric_history(run, key)?;
+                Ok((metrics, PageToken::default()))
+            }),
+        )
+    }
+}
+
+/// A lazily executed run search.
+///
+/// Created by [`Paginate::runs`]. Configure it using the builder methods
+/// and turn it into an iterator with [`iter`][RunSearch::iter] or [`infos`][RunSearch::infos].
+pub struct RunSearch<'a, C: ?Sized> {
+    client: &'a mut C,
+    request: SearchRunsRequest,
+    limit: Option<usize>,
+}
+
+impl<'a, C: Client + ?Sized> RunSearch<'a, C> {
+    /// Only yield runs matching the given MLflow search filter.
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.request = self.request.filter(filter);
+        self
+    }
+
+    /// Defaults to [`ViewType::Active`].
+    pub fn view_type(mut self, view_type: ViewType) -> Self {
+        self.request = self.request.view_type(view_type);
+        self
+    }
+
+    /// Adds a column to order by, e.g. `metrics.loss ASC`.
+    pub fn order_by(mut self, order_by: impl Into<String>) -> Self {
+        self.request = self.request.order_by(order_by);
+        self
+    }
+
+    /// The amount of runs requested at once, defaults to [`DEFAULT_MAX_RESULTS`].
+    ///
+    /// Sizes below 1 are raised to 1.
+    ///
+    /// [`DEFAULT_MAX_RESULTS`]: crate::api::search::DEFAULT_MAX_RESULTS
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.request = self.request.max_results(page_size.max(1));
+        self
+    }
+
+    /// The maximum amount of runs yielded in total.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Iterates over the matching runs including their data.
+    pub fn iter(self) -> Pages<'a, C, Run> {
+        let RunSearch {
+            client,
+            mut request,
+            limit,
+        } = self;
+        let page_token = request.page_token.take();
+        let mut pages = Pages::new(
+            client,
+            request.max_results,
+            limit,
+            Box::new(move |client, max_results, page_token| {
+                request.max_results = max_results;
+                request.page_token = page_token.cloned();
+                let search = client.search_runs(&request)?;
+                Ok((search.runs, search.next_page_token))
+            }),
+        );
+        pages.page_token = page_token;
+        pages
+    }
+
+    /// Iterates over the infos of the matching runs.
+    ///
+    /// Searches of exactly one experiment without a filter list only the infos,
+    /// other searches take the infos from the full runs.
+    pub fn infos(self) -> Pages<'a, C, RunInfo> {
+        let RunSearch {
+            client,
+            mut request,
+            limit,
+        } = self;
+        if request.experiment_ids.len() != 1 || !request.filter.is_empty() {
+            let page_token = request.page_token.take();
+            let mut pages = Pages::new(
+                client,
+                request.max_results,
+                limit,
+                Box::new(move |client, max_results, page_token| {
+                    request.max_results = max_results;
+                    request.page_token = page_token.cloned();
+                    let search = client.search_runs(&request)?;
+                    let infos = search.runs.into_iter().map(|run| run.info).collect();
+                    Ok((infos, search.next_page_token))
+                }),
+            );
+            pages.page_token = page_token;
+            return pages;
+        }
+        let mut list = ListRunsRequest {
+            experiment_id: request.experiment_ids[0].clone(),
+            run_view_type: request.run_view_type,
+            max_results: request.max_results,
+            order_by: request.order_by,
+            page_token: None,
+        };
+        let mut pages = Pages::new(
+            client,
+            list.max_results,
+            limit,
+            Box::new(move |client, max_results, page_token| {
+                list.max_results = max_results;
+                list.page_token = page_token.cloned();
+                let runs = client.list_run_infos(&list)?;
+                Ok((runs.runs, runs.page_token))
+            }),
+        );
+        pages.page_token = request.page_token;
+        pages
+    }
+}
+
+type FetchPage<'a, C, T, E> =
+    Box<dyn FnMut(&mut C, i32, Option<&PageToken>) -> Result<(Vec<T>, PageToken), E> + 'a>;
+
+/// An iterator which requests the next page once the current one is exhausted.
+///
+/// It ends as soon as the server returns an empty page token or the limit is reached.
+/// After yielding an error, the iterator ends as well.
+pub struct Pages<'a, C: ?Sized, T, E = StorageError> {
+    client: &'a mut C,
+    fetch: FetchPage<'a, C, T, E>,
+    page: std::vec::IntoIter<T>,
+    page_size: i32,
+    page_token: Option<PageToken>,
+    remaining: Option<usize>,
+    exhausted: bool,
+}
+
+impl<'a, C: ?Sized, T, E> Pages<'a, C, T, E> {
+    fn new(
+        client: &'a mut C,
+        page_size: i32,
+        limit: Option<usize>,
+        fetch: FetchPage<'a, C, T, E>,
+    ) -> Self {
+        Pages {
+            client,
+            fetch,
+            page: Vec::new().into_iter(),
+            page_size: page_size.max(1),
+            page_token: None,
+            remaining: limit,
+            exhausted: false,
+        }
+    }
+}
+
+impl<C: ?Sized, T, E> Iterator for Pages<'_, C, T, E> {
+    type Item = Result<T, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        loop {
+            if let Some(item) = self.page.next() {
+                if let Some(remaining) = &mut self.remaining {
+                    *remaining -= 1;
+                }
+                return Some(Ok(item));
+            }
+            if self.exhausted {
+                return None;
+            }
+            let max_results = match self.remaining {
+                Some(remaining) if remaining < self.page_size as usize => remaining as i32,
+                _ => self.page_size,
+            };
+            match (self.fetch)(self.client, max_results, self.page_token.as_ref()) {
+                Ok((items, next_page_token)) => {
+                    self.page = items.into_iter();
+                    if next_page_token.as_ref().is_empty() {
+                        self.exhausted = true;
+                    } else {
+                        self.page_token = Some(next_page_token);
+                    }
+                }
+                Err(error) => {
+                    self.exhausted = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PageToken, Pages, Paginate};
+    use crate::{backend::memory::Memory, Client};
+
+    #[test]
+    fn infos_of_several_experiments_or_filtered_runs() {
+        let mut memory = Memory::new();
+        let first = memory.create_experiment("first").unwrap();
+        let second = memory.create_experiment("second").unwrap();
+        for experiment in [&first, &second] {
+            let run = memory.create_run(experiment, 0, &[]).unwrap().info.run_id;
+            memory.log_param(&run, "lr", "0.1").unwrap();
+        }
+        memory.create_run(&first, 0, &[]).unwrap();
+
+        let infos = memory.runs(&[&first, &second]).page_size(0).infos();
+        assert_eq!(infos.collect::<Result<Vec<_>, _>>().unwrap().len(), 3);
+        let infos = memory.runs(&[&first]).filter("params.lr = '0.1'").infos();
+        assert_eq!(infos.collect::<Result<Vec<_>, _>>().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn follows_page_tokens_until_limit() {
+        let mut requests = Vec::new();
+        let mut client = ();
+        let pages: Pages<'_, (), usize, ()> = Pages::new(
+            &mut client,
+            3,
+            Some(7),
+            Box::new(|_, max_results, token| {
+                let start = token.map_or(0, |t| t.as_ref().parse().unwrap());
+                requests.push(max_results);
+                let items = (start..start + max_results as usize).collect();
+                Ok((items, PageToken::from(format!("{}", start + 3))))
+            }),
+        );
+        let items = pages.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(items, (0..7).collect::<Vec<_>>());
+        assert_eq!(requests, vec![3, 3, 1]);
+    }
+}
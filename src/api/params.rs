@@ -0,0 +1,326 @@
+//! Conversion between serializable configs and flat [`Param`]s.
+//!
+//! Nested values are flattened into dotted keys, e.g. `optimizer.lr` or `layers.0.width`.
+//! Scalars are rendered like in JSON, but without quotes around strings.
+//! Empty arrays and objects are kept as `[]` and `{}`, so they can be restored.
+
+use std::collections::BTreeMap;
+
+use serde::{
+    de::{
+        self,
+        value::{Error as DeError, MapAccessDeserializer, MapDeserializer, SeqDeserializer},
+        DeserializeOwned, IntoDeserializer, Visitor,
+    },
+    ser::Error as _,
+    Serialize,
+};
+use serde_json::Value;
+
+use crate::api::{limits, run::Param};
+
+/// Flattens the value into params.
+///
+/// Values longer than [`limits::PARAM_VALUE_LENGTH`] are truncated and suffixed with a hash
+/// of the full value, so different values stay distinguishable.
+pub fn flatten(value: &impl Serialize) -> Result<Vec<Param>, serde_json::Error> {
+    let mut params = Vec::new();
+    match serde_json::to_value(value)? {
+        value @ Value::Object(_) | value @ Value::Array(_) => {
+            flatten_into(&mut params, String::new(), value)
+        }
+        _ => {
+            return Err(serde_json::Error::custom(
+                "only structs, maps and sequences can be flattened into params",
+            ))
+        }
+    }
+    Ok(params)
+}
+
+fn flatten_into(params: &mut Vec<Param>, key: String, value: Value) {
+    let join = |child: &str| {
+        if key.is_empty() {
+            child.to_string()
+        } else {
+            format!("{}.{}", key, child)
+        }
+    };
+    let value = match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (child, value) in map {
+                flatten_into(params, join(&child), value);
+            }
+            return;
+        }
+        Value::Array(array) if !array.is_empty() => {
+            for (index, value) in array.into_iter().enumerate() {
+                flatten_into(params, join(&index.to_string()), value);
+            }
+            return;
+        }
+        Value::String(string) => string,
+        other => other.to_string(),
+    };
+    params.push(Param {
+        key,
+        value: shorten(value),
+    });
+}
+
+/// Truncates values longer than [`limits::PARAM_VALUE_LENGTH`] like [`flatten`] does.
+pub fn shorten(value: String) -> String {
+    if value.len() <= limits::PARAM_VALUE_LENGTH {
+        return value;
+    }
+    let suffix = format!("...{:016x}", fnv1a(value.as_bytes()));
+    let mut end = limits::PARAM_VALUE_LENGTH - suffix.len();
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &value[..end], suffix)
+}
+
+/// A hash which is stable across platforms and Rust versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Rebuilds a value from params created by [`flatten`].
+///
+/// Params which are not part of `T` are ignored, unless `T` denies unknown fields.
+/// Values which were shortened can not be restored.
+pub fn unflatten<T: DeserializeOwned>(params: &[Param]) -> Result<T, DeError> {
+    let mut root = Node::Map(BTreeMap::new());
+    for param in params {
+        let mut node = &mut root;
+        for segment in param.key.split('.') {
+            node = match node {
+                Node::Map(map) => map
+                    .entry(segment.to_string())
+                    .or_insert_with(|| Node::Map(BTreeMap::new())),
+                Node::Leaf(_) => {
+                    return Err(de::Error::custom(format!(
+                        "the param {} is nested in another param",
+                        param.key
+                    )))
+                }
+            };
+        }
+        *node = Node::Leaf(param.value.clone());
+    }
+    T::deserialize(root)
+}
+
+/// A tree of params, whose leaves are parsed into the type requested by the deserialized value.
+enum Node {
+    Leaf(String),
+    Map(BTreeMap<String, Node>),
+}
+
+impl Node {
+    fn is_sequence(map: &BTreeMap<String, Node>) -> bool {
+        !map.is_empty() && (0..map.len()).all(|index| map.contains_key(&index.to_string()))
+    }
+
+    fn into_sequence(map: BTreeMap<String, Node>) -> Vec<Node> {
+        let mut items = map
+            .into_iter()
+            .map(|(index, node)| (index.parse::<usize>().unwrap(), node))
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(index, _)| *index);
+        items.into_iter().map(|(_, node)| node).collect()
+    }
+
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T, DeError> {
+        match self {
+            Node::Leaf(value) => value.parse().map_err(|_| {
+                de::Error::custom(format!("expected {}, found {:?}", expected, value))
+            }),
+            Node::Map(_) => Err(de::Error::custom(format!(
+                "expected {}, found nested params",
+                expected
+            ))),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for Node {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                visitor.$visit(self.parse(stringify!($visit))?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Node {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self {
+            Node::Leaf(value) => match value.as_str() {
+                "null" => visitor.visit_unit(),
+                "true" => visitor.visit_bool(true),
+                "false" => visitor.visit_bool(false),
+                "[]" => visitor.visit_seq(SeqDeserializer::new(Vec::<Node>::new().into_iter())),
+                "{}" => visitor.visit_map(MapDeserializer::new(
+                    BTreeMap::<String, Node>::new().into_iter(),
+                )),
+                _ => {
+                    if let Ok(int) = value.parse::<i64>() {
+                        visitor.visit_i64(int)
+                    } else if let Ok(int) = value.parse::<u64>() {
+                        visitor.visit_u64(int)
+                    } else if let Ok(float) = value.parse::<f64>() {
+                        visitor.visit_f64(float)
+                    } else {
+                        visitor.visit_string(value)
+                    }
+                }
+            },
+            Node::Map(map) if Node::is_sequence(&map) => {
+                visitor.visit_seq(SeqDeserializer::new(Node::into_sequence(map).into_iter()))
+            }
+            Node::Map(map) => visitor.visit_map(MapDeserializer::new(map.into_iter())),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self {
+            Node::Leaf(value) => visitor.visit_string(value),
+            map => map.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match &self {
+            Node::Leaf(value) if value == "null" => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        match self {
+            Node::Leaf(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Node::Map(map) if map.len() == 1 => visitor.visit_enum(MapAccessDeserializer::new(
+                MapDeserializer::new(map.into_iter()),
+            )),
+            Node::Map(_) => Err(de::Error::custom(
+                "expected an enum variant, found multiple nested params",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::{flatten, unflatten};
+    use crate::api::limits;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        seed: Option<u64>,
+        optimizer: Optimizer,
+        layers: Vec<Layer>,
+        tags: Vec<String>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Optimizer {
+        Sgd,
+        Adam { lr: f64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Layer {
+        width: u32,
+        activation: String,
+    }
+
+    #[test]
+    fn flatten_roundtrip() {
+        let config = Config {
+            name: "42".to_string(),
+            seed: None,
+            optimizer: Optimizer::Adam { lr: 0.001 },
+            layers: vec![
+                Layer {
+                    width: 128,
+                    activation: "relu".to_string(),
+                },
+                Layer {
+                    width: 10,
+                    activation: "softmax".to_string(),
+                },
+            ],
+            tags: vec![],
+        };
+        let params = flatten(&config).unwrap();
+        let keys = params.iter().map(|p| p.key.as_str()).collect::<Vec<_>>();
+        assert!(keys.contains(&"optimizer.Adam.lr"));
+        assert!(keys.contains(&"layers.1.width"));
+        assert_eq!(unflatten::<Config>(&params).unwrap(), config);
+    }
+
+    #[test]
+    fn flatten_shortens_long_values() {
+        let prefix = "x".repeat(1000);
+        let long = vec![format!("{}a", prefix), format!("{}b", prefix)];
+        let params = flatten(&long).unwrap();
+        assert!(params
+            .iter()
+            .all(|p| p.value.len() <= limits::PARAM_VALUE_LENGTH));
+        assert_ne!(params[0].value, params[1].value);
+    }
+}
@@ -1,40 +1,60 @@
 use std::borrow::Cow;
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    api::{opt_str_int, str_int},
+    api::{opt_str_int, params, str_int},
     ExperimentId, RunId,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metric<'a> {
     pub key: Cow<'a, str>,
+    #[serde(with = "crate::api::non_finite")]
     pub value: f64,
     pub timestamp: i64,
     pub step: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Metric<'_> {
+    pub fn into_owned(self) -> Metric<'static> {
+        Metric {
+            key: Cow::Owned(self.key.into_owned()),
+            value: self.value,
+            timestamp: self.timestamp,
+            step: self.step,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Param {
     pub key: String,
     pub value: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Run {
     pub info: RunInfo,
     pub data: RunData,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+impl Run {
+    /// Rebuilds a config which was logged using
+    /// [`TrackingRun::log_params_from`][crate::tracking::TrackingRun::log_params_from].
+    pub fn params_into<T: DeserializeOwned>(&self) -> Result<T, serde::de::value::Error> {
+        params::unflatten(self.data.params.as_deref().unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RunData {
     pub metrics: Option<Vec<Metric<'static>>>,
     pub params: Option<Vec<Param>>,
     pub tags: Option<Vec<RunTag>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RunInfo {
     pub run_id: RunId,
     #[deprecated = "This field will be removed in a future FLflow version"]
@@ -61,7 +81,7 @@ pub enum RunStatus {
     Killed,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct RunTag {
     pub key: String,
     pub value: String,
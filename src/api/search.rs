@@ -1,7 +1,19 @@
-use crate::api::run::{Run, RunInfo};
+use crate::{
+    api::{
+        client::ViewType,
+        run::{Run, RunInfo},
+    },
+    ExperimentId,
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// The amount of results requested unless configured otherwise.
+pub const DEFAULT_MAX_RESULTS: i32 = 1000;
+
+/// Identifies the next page of a listing.
+///
+/// An empty token signals that there are no more pages.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct PageToken(String);
 impl AsRef<str> for PageToken {
@@ -22,7 +34,9 @@ impl From<&str> for PageToken {
 
 #[derive(Deserialize)]
 pub struct Search {
+    #[serde(default)]
     pub runs: Vec<Run>,
+    #[serde(default)]
     pub next_page_token: PageToken,
 }
 
@@ -30,3 +44,101 @@ pub struct RunList {
     pub runs: Vec<RunInfo>,
     pub page_token: PageToken,
 }
+
+/// Parameters of [`Client::search_runs`][crate::Client::search_runs].
+///
+/// Defaults to active runs and up to [`DEFAULT_MAX_RESULTS`] results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchRunsRequest {
+    pub experiment_ids: Vec<ExperimentId>,
+    pub filter: String,
+    pub run_view_type: ViewType,
+    pub max_results: i32,
+    pub order_by: Vec<String>,
+    pub page_token: Option<PageToken>,
+}
+
+impl SearchRunsRequest {
+    pub fn new<'a>(experiment_ids: impl IntoIterator<Item = &'a ExperimentId>) -> Self {
+        SearchRunsRequest {
+            experiment_ids: experiment_ids.into_iter().cloned().collect(),
+            filter: String::new(),
+            run_view_type: ViewType::Active,
+            max_results: DEFAULT_MAX_RESULTS,
+            order_by: Vec::new(),
+            page_token: None,
+        }
+    }
+
+    /// Only return runs matching the given MLflow search filter.
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = filter.into();
+        self
+    }
+
+    pub fn view_type(mut self, view_type: ViewType) -> Self {
+        self.run_view_type = view_type;
+        self
+    }
+
+    pub fn max_results(mut self, max_results: i32) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    /// Adds a column to order by, e.g. `metrics.loss ASC`.
+    pub fn order_by(mut self, order_by: impl Into<String>) -> Self {
+        self.order_by.push(order_by.into());
+        self
+    }
+
+    pub fn page_token(mut self, page_token: impl Into<Option<PageToken>>) -> Self {
+        self.page_token = page_token.into();
+        self
+    }
+}
+
+/// Parameters of [`Client::list_run_infos`][crate::Client::list_run_infos].
+///
+/// Defaults to active runs and up to [`DEFAULT_MAX_RESULTS`] results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListRunsRequest {
+    pub experiment_id: ExperimentId,
+    pub run_view_type: ViewType,
+    pub max_results: i32,
+    pub order_by: Vec<String>,
+    pub page_token: Option<PageToken>,
+}
+
+impl ListRunsRequest {
+    pub fn new(experiment_id: &ExperimentId) -> Self {
+        ListRunsRequest {
+            experiment_id: experiment_id.clone(),
+            run_view_type: ViewType::Active,
+            max_results: DEFAULT_MAX_RESULTS,
+            order_by: Vec::new(),
+            page_token: None,
+        }
+    }
+
+    pub fn view_type(mut self, view_type: ViewType) -> Self {
+        self.run_view_type = view_type;
+        self
+    }
+
+    pub fn max_results(mut self, max_results: i32) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    /// Adds a column to order by, e.g. `attributes.start_time DESC`.
+    pub fn order_by(mut self, order_by: impl Into<String>) -> Self {
+        self.order_by.push(order_by.into());
+        self
+    }
+
+    pub fn page_token(mut self, page_token: impl Into<Option<PageToken>>) -> Self {
+        self.page_token = page_token.into();
+        self
+    }
+}
@@ -0,0 +1,627 @@
+//! Exports experiments to self-contained archives and imports them into any [`Client`],
+//! e.g. to keep the provenance of models after their experiments were deleted from the server.
+//!
+//! Archives use the layout of the `mlflow-export-import` tool:
+//! an `experiment.json` manifest and a directory for each run,
+//! with a `run.json` and the `artifacts` of the run.
+//! With the `tarball` feature, archives can also be written to and read from `.tar.gz` files.
+//!
+//! ```no_run
+//! use mlflow::{archive, backend::{memory::Memory, rest::Server}};
+//!
+//! let mut server = Server::from_tracking_uri("http://localhost:5000")?;
+//! archive::export_experiment(&mut server, &"1".into(), "exports/experiment-1")?;
+//! let mut memory = Memory::new();
+//! let experiment = archive::import_experiment(&mut memory, "exports/experiment-1", None)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        artifact::{validate_path, write_local},
+        client::{Client, ViewType},
+        error::{CreateError, StorageError},
+        run::{Metric, Param, RunInfo, RunStatus, RunTag},
+        tags,
+    },
+    migrate::{self, list_files},
+    tracking::batch::log_batches,
+    ExperimentId, Paginate, RunId,
+};
+
+/// The version of `mlflow-export-import` whose layout is written.
+///
+/// Archives of any `1.x` version can be imported.
+pub const FORMAT_VERSION: &str = "1.2.0";
+
+const MANIFEST: &str = "experiment.json";
+const RUN: &str = "run.json";
+const ARTIFACTS: &str = "artifacts";
+
+#[derive(Serialize, Deserialize)]
+struct System {
+    package_version: String,
+    #[serde(default)]
+    script: String,
+    #[serde(default)]
+    export_time: i64,
+}
+
+impl System {
+    fn new() -> Self {
+        System {
+            package_version: FORMAT_VERSION.to_string(),
+            script: format!("mlflow-rs {}", env!("CARGO_PKG_VERSION")),
+            export_time: crate::timestamp() / 1000,
+        }
+    }
+
+    fn check(&self, path: &Path) -> Result<(), StorageError> {
+        if !self.package_version.starts_with("1.") {
+            bail!(
+                "{} has the unsupported version {}",
+                path.display(),
+                self.package_version
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExperimentFile {
+    system: System,
+    #[serde(default)]
+    info: ExportInfo,
+    mlflow: ExperimentData,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ExportInfo {
+    num_total_runs: usize,
+    num_ok_runs: usize,
+    num_failed_runs: usize,
+    failed_runs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExperimentData {
+    experiment: ExperimentJson,
+    runs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExperimentJson {
+    experiment_id: String,
+    name: String,
+    #[serde(default)]
+    artifact_location: String,
+    #[serde(default)]
+    lifecycle_stage: String,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
+    creation_time: Option<i64>,
+    #[serde(default)]
+    last_update_time: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RunFile {
+    system: System,
+    mlflow: RunJson,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RunJson {
+    info: RunInfoJson,
+    #[serde(default)]
+    params: BTreeMap<String, String>,
+    #[serde(default)]
+    metrics: BTreeMap<String, Vec<MetricJson>>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RunInfoJson {
+    run_id: String,
+    #[serde(default)]
+    run_uuid: String,
+    experiment_id: String,
+    #[serde(default)]
+    user_id: String,
+    status: RunStatus,
+    start_time: i64,
+    #[serde(default)]
+    end_time: Option<i64>,
+    #[serde(default)]
+    artifact_uri: String,
+    #[serde(default)]
+    lifecycle_stage: String,
+    #[serde(default)]
+    run_name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MetricJson {
+    #[serde(with = "crate::api::non_finite")]
+    value: f64,
+    timestamp: i64,
+    step: i64,
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<(), StorageError> {
+    let json = serde_json::to_vec_pretty(value)?;
+    fs::write(path, json).with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, StorageError> {
+    let json = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_slice(&json).with_context(|| format!("{} is not valid", path.display()))
+}
+
+/// Writes the experiment with all of its runs, including deleted ones, to the directory `dir`.
+///
+/// Runs contain their params, tags, full metric histories and artifacts.
+/// The experiment may be deleted itself.
+pub fn export_experiment(
+    client: &mut dyn Client,
+    experiment: &ExperimentId,
+    dir: impl AsRef<Path>,
+) -> Result<(), StorageError> {
+    let dir = dir.as_ref();
+    let experiment = client.get_experiment(experiment)?;
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let runs = client.runs(&[&experiment.experiment_id]);
+    let runs = runs.view_type(ViewType::All).iter();
+    let runs = runs.collect::<Result<Vec<_>, _>>()?;
+    let mut ids = Vec::with_capacity(runs.len());
+    for run in runs {
+        let id = run.info.run_id.as_ref().to_string();
+        let run_dir = dir.join(&id);
+        let mut metrics = BTreeMap::new();
+        for metric in run.data.metrics.iter().flatten() {
+            let history = client.get_metric_history(&run.info.run_id, &metric.key)?;
+            let history = history.into_iter().map(|metric| MetricJson {
+                value: metric.value,
+                timestamp: metric.timestamp,
+                step: metric.step,
+            });
+            metrics.insert(metric.key.to_string(), history.collect());
+        }
+        let params = run.data.params.into_iter().flatten();
+        let tags = run.data.tags.into_iter().flatten();
+        let tags = tags
+            .map(|tag| (tag.key, tag.value))
+            .collect::<BTreeMap<_, _>>();
+        #[allow(deprecated)]
+        let info = RunInfoJson {
+            run_id: id.clone(),
+            run_uuid: id.clone(),
+            experiment_id: run.info.experiment_id.as_ref().to_string(),
+            user_id: run.info.user_id.clone(),
+            status: run.info.status,
+            start_time: run.info.start_time,
+            end_time: run.info.end_time,
+            artifact_uri: run.info.artifact_uri.clone(),
+            lifecycle_stage: run.info.lifecycle_stage.clone(),
+            run_name: tags.get(tags::RUN_NAME).cloned().unwrap_or_default(),
+        };
+        let file = RunFile {
+            system: System::new(),
+            mlflow: RunJson {
+                info,
+                params: params.map(|param| (param.key, param.value)).collect(),
+                metrics,
+                tags,
+            },
+        };
+        fs::create_dir_all(&run_dir)
+            .with_context(|| format!("failed to create {}", run_dir.display()))?;
+        write_json(&run_dir.join(RUN), &file)?;
+
+        let mut artifacts = Vec::new();
+        list_files(client, &run.info, "", &mut artifacts)?;
+        for path in artifacts {
+            let contents = client.download_artifact(&run.info, &path)?;
+            write_local(&run_dir.join(ARTIFACTS), &path, &contents)?;
+        }
+        ids.push(id);
+    }
+
+    let tags = experiment.tags.into_iter().flatten();
+    let manifest = ExperimentFile {
+        system: System::new(),
+        info: ExportInfo {
+            num_total_runs: ids.len(),
+            num_ok_runs: ids.len(),
+            ..ExportInfo::default()
+        },
+        mlflow: ExperimentData {
+            experiment: ExperimentJson {
+                experiment_id: experiment.experiment_id.as_ref().to_string(),
+                name: experiment.name,
+                artifact_location: experiment.artifact_location,
+                lifecycle_stage: experiment.lifecycle_stage,
+                tags: tags.map(|tag| (tag.key, tag.value)).collect(),
+                creation_time: experiment.creation_time,
+                last_update_time: experiment.last_update_time,
+            },
+            runs: ids,
+        },
+    };
+    // The manifest is written last, so incomplete exports are not mistaken for archives.
+    write_json(&dir.join(MANIFEST), &manifest)
+}
+
+/// Creates the experiment in the archive at `dir` with all of its runs and returns its id.
+///
+/// The experiment keeps its name unless `name` is given, and is reused if it already exists.
+/// Runs keep their start and end times, status and lifecycle stage, and are tagged with
+/// their original ids like copies made by a [`Migration`][crate::migrate::Migration].
+/// Importing an archive again skips the runs which were already imported completely.
+/// Experiment tags are not imported, because they are not part of the [`Client`] api.
+pub fn import_experiment(
+    client: &mut dyn Client,
+    dir: impl AsRef<Path>,
+    name: Option<&str>,
+) -> Result<ExperimentId, StorageError> {
+    let dir = dir.as_ref();
+    let manifest_path = dir.join(MANIFEST);
+    let manifest: ExperimentFile = read_json(&manifest_path)?;
+    manifest.system.check(&manifest_path)?;
+    let source = manifest.mlflow.experiment;
+    let name = name.unwrap_or(&source.name);
+    let (experiment, existed) = match client.create_experiment(name) {
+        Ok(id) => (id, false),
+        Err(CreateError::AlreadyExists(_)) => {
+            (client.get_experiment_by_name(name)?.experiment_id, true)
+        }
+        Err(error) => return Err(error.into()),
+    };
+    let copies = if existed {
+        imported_runs(client, &experiment)?
+    } else {
+        HashMap::new()
+    };
+
+    let mut runs = Vec::with_capacity(manifest.mlflow.runs.len());
+    for id in &manifest.mlflow.runs {
+        let path = dir.join(id).join(RUN);
+        let run: RunFile = read_json(&path)?;
+        run.system.check(&path)?;
+        runs.push((dir.join(id), run.mlflow));
+    }
+    runs.sort_by_key(|(_, run)| run.info.start_time);
+
+    // Parents are imported before their children, so the parent ids can be mapped.
+    let mut imported = HashMap::new();
+    let mut pending = runs
+        .iter()
+        .map(|(_, run)| run.info.run_id.clone())
+        .collect::<HashSet<_>>();
+    while !runs.is_empty() {
+        let ready = runs.iter().position(|(_, run)| {
+            let parent = run.tags.get(tags::PARENT_RUN_ID);
+            parent.is_none_or(|parent| !pending.contains(parent))
+        });
+        let (run_dir, run) = runs.remove(ready.unwrap_or(0));
+        let source_id = run.info.run_id.clone();
+        pending.remove(&source_id);
+        match copies.get(&source_id) {
+            Some((id, true)) => {
+                imported.insert(source_id, id.clone());
+                continue;
+            }
+            // The copy of an interrupted import is replaced.
+            Some((id, false)) => client.delete_run(id)?,
+            None => {}
+        }
+        let id = import_run(
+            client,
+            &experiment,
+            &source.experiment_id,
+            &run_dir,
+            run,
+            &imported,
+        )
+        .with_context(|| format!("failed to import the run in {}", run_dir.display()))?;
+        imported.insert(source_id, id);
+    }
+    Ok(experiment)
+}
+
+/// The runs of the experiment which were imported before, by their original ids,
+/// with whether they were imported completely.
+fn imported_runs(
+    client: &mut dyn Client,
+    experiment: &ExperimentId,
+) -> Result<HashMap<String, (RunId, bool)>, StorageError> {
+    let mut copies = HashMap::new();
+    for run in client.runs(&[experiment]).view_type(ViewType::All).iter() {
+        let run = run?;
+        let tags = run.data.tags.unwrap_or_default();
+        let tag = |key: &str| tags.iter().find(|tag| tag.key == key);
+        let source = match tag(migrate::SOURCE_RUN_ID) {
+            Some(source) => source.value.clone(),
+            None => continue,
+        };
+        let complete = tag(migrate::COMPLETE).is_some();
+        // Incomplete copies are deleted once they are replaced.
+        let replaced = !complete && run.info.lifecycle_stage == "deleted";
+        let known = copies.get(&source).is_some_and(|(_, complete)| *complete);
+        if !replaced && !known {
+            copies.insert(source, (run.info.run_id, complete));
+        }
+    }
+    Ok(copies)
+}
+
+fn import_run(
+    client: &mut dyn Client,
+    experiment: &ExperimentId,
+    source_experiment: &str,
+    dir: &Path,
+    run: RunJson,
+    imported: &HashMap<String, RunId>,
+) -> Result<RunId, StorageError> {
+    let mut tags = run
+        .tags
+        .into_iter()
+        .map(|(key, value)| RunTag { key, value })
+        .collect::<Vec<_>>();
+    for tag in &mut tags {
+        if tag.key == tags::PARENT_RUN_ID {
+            if let Some(parent) = imported.get(&tag.value) {
+                tag.value = parent.as_ref().to_string();
+            }
+        }
+    }
+    let source_tags = [
+        (migrate::SOURCE_RUN_ID, run.info.run_id.as_str()),
+        (migrate::SOURCE_EXPERIMENT_ID, source_experiment),
+    ];
+    tags.retain(|tag| !source_tags.iter().any(|(key, _)| *key == tag.key));
+    let mut initial = source_tags
+        .iter()
+        .map(|(key, value)| RunTag {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+        .collect::<Vec<_>>();
+    if !run.info.run_name.is_empty() && !tags.iter().any(|tag| tag.key == tags::RUN_NAME) {
+        initial.push(RunTag {
+            key: tags::RUN_NAME.to_string(),
+            value: run.info.run_name.clone(),
+        });
+    }
+    let info = client
+        .create_run(experiment, run.info.start_time, &initial)?
+        .info;
+
+    let params = run
+        .params
+        .into_iter()
+        .map(|(key, value)| Param { key, value })
+        .collect::<Vec<_>>();
+    let mut metrics = Vec::new();
+    for (key, history) in run.metrics {
+        metrics.extend(history.into_iter().map(|metric| Metric {
+            key: key.clone().into(),
+            value: metric.value,
+            timestamp: metric.timestamp,
+            step: metric.step,
+        }));
+    }
+    let (_, result) = log_batches(client, &info.run_id, &metrics, &params, &tags);
+    result?;
+    upload_dir(client, &info, &dir.join(ARTIFACTS), "")?;
+    client.update_run(&info.run_id, run.info.status, run.info.end_time)?;
+    let complete = RunTag {
+        key: migrate::COMPLETE.to_string(),
+        value: "true".to_string(),
+    };
+    client.log_batch(&info.run_id, &[], &[], &[complete])?;
+    if run.info.lifecycle_stage == "deleted" {
+        client.delete_run(&info.run_id)?;
+    }
+    Ok(info.run_id)
+}
+
+/// Uploads all files below `dir` as artifacts below `prefix`.
+fn upload_dir(
+    client: &mut dyn Client,
+    run: &RunInfo,
+    dir: &Path,
+    prefix: &str,
+) -> Result<(), StorageError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to list {}", dir.display()))
+        }
+    };
+    let mut paths = entries
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<PathBuf>, std::io::Error>>()?;
+    paths.sort();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let artifact = crate::api::artifact::join(prefix, &name);
+        validate_path(&artifact)?;
+        if path.is_dir() {
+            upload_dir(client, run, &path, &artifact)?;
+        } else {
+            let contents =
+                fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+            client.upload_artifact(run, &artifact, &contents)?;
+        }
+    }
+    Ok(())
+}
+
+/// A directory in the system temp directory which is removed when dropped.
+#[cfg(feature = "tarball")]
+struct TempDir(PathBuf);
+
+#[cfg(feature = "tarball")]
+impl TempDir {
+    fn new() -> Result<Self, StorageError> {
+        let name = format!(
+            "mlflow-archive-{}-{}",
+            std::process::id(),
+            crate::timestamp()
+        );
+        let dir = std::env::temp_dir().join(name);
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        Ok(TempDir(dir))
+    }
+}
+
+#[cfg(feature = "tarball")]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Like [`export_experiment`], but writes a gzipped tarball to `file`.
+#[cfg(feature = "tarball")]
+pub fn export_tarball(
+    client: &mut dyn Client,
+    experiment: &ExperimentId,
+    file: impl AsRef<Path>,
+) -> Result<(), StorageError> {
+    let file = file.as_ref();
+    let temp = TempDir::new()?;
+    export_experiment(client, experiment, &temp.0)?;
+    let output =
+        fs::File::create(file).with_context(|| format!("failed to create {}", file.display()))?;
+    let encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.append_dir_all(".", &temp.0)?;
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// Like [`import_experiment`], but reads a gzipped tarball written by [`export_tarball`].
+#[cfg(feature = "tarball")]
+pub fn import_tarball(
+    client: &mut dyn Client,
+    file: impl AsRef<Path>,
+    name: Option<&str>,
+) -> Result<ExperimentId, StorageError> {
+    let file = file.as_ref();
+    let input =
+        fs::File::open(file).with_context(|| format!("failed to open {}", file.display()))?;
+    let temp = TempDir::new()?;
+    tar::Archive::new(flate2::read::GzDecoder::new(input))
+        .unpack(&temp.0)
+        .with_context(|| format!("failed to unpack {}", file.display()))?;
+    import_experiment(client, &temp.0, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_experiment, import_experiment};
+    use crate::{
+        api::{
+            run::{RunStatus, RunTag},
+            tags,
+        },
+        backend::memory::Memory,
+        Client, Paginate,
+    };
+
+    #[test]
+    fn export_and_import_an_experiment() {
+        let mut source = Memory::new();
+        let experiment = source.create_experiment("exp").unwrap();
+        let name = RunTag {
+            key: tags::RUN_NAME.to_string(),
+            value: "first".to_string(),
+        };
+        let run = source.create_run(&experiment, 10, &[name]).unwrap().info;
+        source.log_metric(&run.run_id, "loss", 0.5, 11, 0).unwrap();
+        source.log_metric(&run.run_id, "loss", 0.25, 12, 1).unwrap();
+        source.log_param(&run.run_id, "lr", "0.1").unwrap();
+        source
+            .upload_artifact(&run, "model/weights.bin", b"123")
+            .unwrap();
+        source
+            .update_run(&run.run_id, RunStatus::Finished, Some(13))
+            .unwrap();
+        source.delete_experiment(&experiment).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("mlflow-export-{}", std::process::id()));
+        export_experiment(&mut source, &experiment, &dir).unwrap();
+        let manifest = std::fs::read_to_string(dir.join("experiment.json")).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["mlflow"]["experiment"]["name"], "exp");
+        let run_json = dir.join(run.run_id.as_ref()).join("run.json");
+        let run_json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(run_json).unwrap()).unwrap();
+        assert_eq!(run_json["mlflow"]["metrics"]["loss"][1]["value"], 0.25);
+
+        let mut target = Memory::new();
+        let imported = import_experiment(&mut target, &dir, Some("restored")).unwrap();
+        let again = import_experiment(&mut target, &dir, Some("restored")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(again, imported);
+        let runs = target.runs(&[&imported]).iter();
+        let runs = runs.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(runs.len(), 1);
+        let copy = &runs[0].info;
+        assert_eq!((copy.start_time, copy.end_time), (10, Some(13)));
+        assert_eq!(copy.status, RunStatus::Finished);
+        assert_eq!(
+            target
+                .get_metric_history(&copy.run_id, "loss")
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            target.artifact(&copy.run_id, "model/weights.bin"),
+            Some(&b"123"[..])
+        );
+    }
+
+    #[cfg(feature = "tarball")]
+    #[test]
+    fn round_trip_through_a_tarball() {
+        let mut source = Memory::new();
+        let experiment = source.create_experiment("exp").unwrap();
+        let run = source.create_run(&experiment, 1, &[]).unwrap().info;
+        source.upload_artifact(&run, "notes.txt", b"abc").unwrap();
+
+        let file =
+            std::env::temp_dir().join(format!("mlflow-export-{}.tar.gz", std::process::id()));
+        super::export_tarball(&mut source, &experiment, &file).unwrap();
+        let mut target = Memory::new();
+        let imported = super::import_tarball(&mut target, &file, None).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(target.get_experiment(&imported).unwrap().name, "exp");
+        let runs = target
+            .runs(&[&imported])
+            .iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            target.artifact(&runs[0].info.run_id, "notes.txt"),
+            Some(&b"abc"[..])
+        );
+    }
+}
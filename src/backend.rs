@@ -1 +1,8 @@
+pub mod cache;
+pub mod cassette;
+pub mod file;
+mod filter;
+pub mod memory;
+pub mod mirror;
 pub mod rest;
+pub mod spool;
@@ -0,0 +1,467 @@
+//! A [`Client`] decorator which caches reads, e.g. for dashboards which fetch the same runs repeatedly.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        artifact::FileInfo,
+        client::{Client, ViewType},
+        error::{BatchError, CreateError, DeleteError, GetError, StorageError, UpdateError},
+        experiment::Experiment,
+        run::{Metric, Param, Run, RunInfo, RunStatus, RunTag},
+        search::{ListRunsRequest, RunList, Search, SearchRunsRequest},
+    },
+    ExperimentId, RunId,
+};
+
+/// Caches experiments, runs and metric histories read from the inner client.
+///
+/// Entries expire after the [ttl][Cache::ttl], except for finished runs and the metric histories
+/// of runs which were seen finished, because their data does not change anymore.
+/// When the cache holds more than [capacity][Cache::capacity] entries,
+/// the least recently used ones are evicted.
+///
+/// Writes through the cache invalidate the entries of the run or experiment they change.
+/// Writes by other clients are only noticed once the entries expire,
+/// so finished runs which are changed elsewhere can be stale.
+/// Searches and listings are never cached.
+pub struct Cache {
+    inner: Box<dyn Client + Send>,
+    ttl: Duration,
+    capacity: usize,
+    path: Option<PathBuf>,
+    entries: HashMap<Key, Entry>,
+    uses: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum Key {
+    Experiment(ExperimentId),
+    ExperimentByName(String),
+    Run(RunId),
+    History(RunId, String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    value: Value,
+    /// When the entry was stored, as MLflow timestamp.
+    stored: i64,
+    /// Whether the entry never expires.
+    permanent: bool,
+    /// The experiment of the run the entry belongs to, if it is known.
+    #[serde(default)]
+    experiment: Option<ExperimentId>,
+    #[serde(skip)]
+    used: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Value {
+    Experiment(Experiment),
+    Run(Run),
+    History(Vec<Metric<'static>>),
+}
+
+fn is_finished(info: &RunInfo) -> bool {
+    let terminal = matches!(
+        info.status,
+        RunStatus::Finished | RunStatus::Failed | RunStatus::Killed
+    );
+    terminal && info.end_time.is_some()
+}
+
+impl Cache {
+    /// Defaults to a ttl of one minute and a capacity of 10000 entries.
+    pub fn new(inner: impl Client + Send + 'static) -> Self {
+        Cache {
+            inner: Box::new(inner),
+            ttl: Duration::from_secs(60),
+            capacity: 10_000,
+            path: None,
+            entries: HashMap::new(),
+            uses: 0,
+        }
+    }
+
+    /// How long entries of active runs, experiments and histories are kept.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The maximum number of cached entries.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Keeps the cache in the JSON file at `path`, which is read now if it exists
+    /// and written by [`save`][Cache::save].
+    pub fn persist(mut self, path: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        let path = path.into();
+        if path.exists() {
+            let json = fs::read(&path)
+                .with_context(|| format!("failed to read the cache {}", path.display()))?;
+            let entries: Vec<(Key, Entry)> = serde_json::from_slice(&json)
+                .with_context(|| format!("the cache {} is not valid", path.display()))?;
+            self.entries.extend(entries);
+        }
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Writes the entries which have not expired to the file given to [`persist`][Cache::persist].
+    pub fn save(&self) -> Result<(), StorageError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let now = crate::timestamp();
+        let entries = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| self.is_fresh(entry, now));
+        let json = serde_json::to_vec(&entries.collect::<Vec<_>>())?;
+        write_atomic(path, &json)
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// The number of cached entries, including expired ones which were not removed yet.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn is_fresh(&self, entry: &Entry, now: i64) -> bool {
+        entry.permanent || now - entry.stored < self.ttl.as_millis() as i64
+    }
+
+    fn get(&mut self, key: &Key) -> Option<Value> {
+        let now = crate::timestamp();
+        let fresh = self.is_fresh(self.entries.get(key)?, now);
+        if !fresh {
+            self.entries.remove(key);
+            return None;
+        }
+        self.uses += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.used = self.uses;
+        Some(entry.value.clone())
+    }
+
+    fn insert(
+        &mut self,
+        key: Key,
+        value: Value,
+        permanent: bool,
+        experiment: Option<ExperimentId>,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.used);
+            let oldest = oldest.map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+        self.uses += 1;
+        let entry = Entry {
+            value,
+            stored: crate::timestamp(),
+            permanent,
+            experiment,
+            used: self.uses,
+        };
+        self.entries.insert(key, entry);
+    }
+
+    /// Removes the run and its metric histories.
+    fn invalidate_run(&mut self, id: &RunId) {
+        self.entries.retain(|key, _| match key {
+            Key::Run(run) | Key::History(run, _) => run != id,
+            _ => true,
+        });
+    }
+
+    /// Removes the experiment, including lookups by name which found it, and its runs.
+    fn invalidate_experiment(&mut self, id: &ExperimentId) {
+        let runs = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.experiment.as_ref() == Some(id))
+            .filter_map(|(key, _)| match key {
+                Key::Run(run) => Some(run.clone()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        self.entries.retain(|key, entry| match (key, &entry.value) {
+            (Key::Experiment(experiment), _) => experiment != id,
+            (Key::ExperimentByName(_), Value::Experiment(experiment)) => {
+                experiment.experiment_id != *id
+            }
+            (Key::Run(run), _) | (Key::History(run, _), _) => {
+                entry.experiment.as_ref() != Some(id) && !runs.contains(run)
+            }
+            _ => true,
+        });
+    }
+}
+
+fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), StorageError> {
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, contents).with_context(|| format!("failed to write {}", temp.display()))?;
+    fs::rename(&temp, path).with_context(|| format!("failed to write {}", path.display()))
+}
+
+impl Client for Cache {
+    fn create_experiment(&mut self, name: &str) -> Result<ExperimentId, CreateError> {
+        self.entries
+            .remove(&Key::ExperimentByName(name.to_string()));
+        self.inner.create_experiment(name)
+    }
+
+    fn list_experiments(&mut self, view_type: ViewType) -> Result<Vec<Experiment>, StorageError> {
+        self.inner.list_experiments(view_type)
+    }
+
+    fn get_experiment(&mut self, id: &ExperimentId) -> Result<Experiment, GetError> {
+        let key = Key::Experiment(id.clone());
+        if let Some(Value::Experiment(experiment)) = self.get(&key) {
+            return Ok(experiment);
+        }
+        let experiment = self.inner.get_experiment(id)?;
+        self.insert(key, Value::Experiment(experiment.clone()), false, None);
+        Ok(experiment)
+    }
+
+    fn get_experiment_by_name(&mut self, name: &str) -> Result<Experiment, GetError> {
+        let key = Key::ExperimentByName(name.to_string());
+        if let Some(Value::Experiment(experiment)) = self.get(&key) {
+            return Ok(experiment);
+        }
+        let experiment = self.inner.get_experiment_by_name(name)?;
+        self.insert(key, Value::Experiment(experiment.clone()), false, None);
+        Ok(experiment)
+    }
+
+    fn delete_experiment(&mut self, id: &ExperimentId) -> Result<(), DeleteError> {
+        self.invalidate_experiment(id);
+        self.inner.delete_experiment(id)
+    }
+
+    fn restore_experiment(&mut self, id: &ExperimentId) -> Result<(), UpdateError> {
+        self.invalidate_experiment(id);
+        self.inner.restore_experiment(id)
+    }
+
+    fn update_experiment(
+        &mut self,
+        id: &ExperimentId,
+        new_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        self.invalidate_experiment(id);
+        if let Some(name) = new_name {
+            self.entries
+                .remove(&Key::ExperimentByName(name.to_string()));
+        }
+        self.inner.update_experiment(id, new_name)
+    }
+
+    fn create_run(
+        &mut self,
+        experiment: &ExperimentId,
+        start_time: i64,
+        tags: &[RunTag],
+    ) -> Result<Run, StorageError> {
+        self.inner.create_run(experiment, start_time, tags)
+    }
+
+    fn delete_run(&mut self, id: &RunId) -> Result<(), DeleteError> {
+        self.invalidate_run(id);
+        self.inner.delete_run(id)
+    }
+
+    fn restore_run(&mut self, id: &RunId) -> Result<(), UpdateError> {
+        self.invalidate_run(id);
+        self.inner.restore_run(id)
+    }
+
+    fn get_run(&mut self, id: &RunId) -> Result<Run, GetError> {
+        let key = Key::Run(id.clone());
+        if let Some(Value::Run(run)) = self.get(&key) {
+            return Ok(run);
+        }
+        let run = self.inner.get_run(id)?;
+        let finished = is_finished(&run.info);
+        let experiment = Some(run.info.experiment_id.clone());
+        self.insert(key, Value::Run(run.clone()), finished, experiment);
+        Ok(run)
+    }
+
+    fn update_run(
+        &mut self,
+        id: &RunId,
+        status: RunStatus,
+        end_time: Option<i64>,
+    ) -> Result<RunInfo, UpdateError> {
+        self.invalidate_run(id);
+        self.inner.update_run(id, status, end_time)
+    }
+
+    fn search_runs(&mut self, request: &SearchRunsRequest) -> Result<Search, StorageError> {
+        self.inner.search_runs(request)
+    }
+
+    fn list_run_infos(&mut self, request: &ListRunsRequest) -> Result<RunList, StorageError> {
+        self.inner.list_run_infos(request)
+    }
+
+    fn get_metric_history(
+        &mut self,
+        run: &RunId,
+        metric: &str,
+    ) -> Result<Vec<Metric<'static>>, GetError> {
+        let key = Key::History(run.clone(), metric.to_string());
+        if let Some(Value::History(history)) = self.get(&key) {
+            return Ok(history);
+        }
+        let history = self.inner.get_metric_history(run, metric)?;
+        let (finished, experiment) = match self.entries.get(&Key::Run(run.clone())) {
+            Some(Entry {
+                value: Value::Run(run),
+                ..
+            }) => (is_finished(&run.info), Some(run.info.experiment_id.clone())),
+            _ => (false, None),
+        };
+        self.insert(key, Value::History(history.clone()), finished, experiment);
+        Ok(history)
+    }
+
+    fn log_param(&mut self, run: &RunId, key: &str, value: &str) -> Result<(), StorageError> {
+        self.invalidate_run(run);
+        self.inner.log_param(run, key, value)
+    }
+
+    fn log_metric(
+        &mut self,
+        run: &RunId,
+        key: &str,
+        value: f64,
+        timestamp: i64,
+        step: i64,
+    ) -> Result<(), StorageError> {
+        self.invalidate_run(run);
+        self.inner.log_metric(run, key, value, timestamp, step)
+    }
+
+    fn log_batch(
+        &mut self,
+        run: &RunId,
+        metrics: &[Metric],
+        params: &[Param],
+        tags: &[RunTag],
+    ) -> Result<(), BatchError> {
+        self.invalidate_run(run);
+        self.inner.log_batch(run, metrics, params, tags)
+    }
+
+    fn upload_artifact(
+        &mut self,
+        run: &RunInfo,
+        path: &str,
+        contents: &[u8],
+    ) -> Result<(), StorageError> {
+        self.inner.upload_artifact(run, path, contents)
+    }
+
+    fn list_artifacts(&mut self, run: &RunInfo, path: &str) -> Result<Vec<FileInfo>, StorageError> {
+        self.inner.list_artifacts(run, path)
+    }
+
+    fn download_artifact(&mut self, run: &RunInfo, path: &str) -> Result<Vec<u8>, StorageError> {
+        self.inner.download_artifact(run, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Cache;
+    use crate::{api::run::RunStatus, testing::MockServer, Client};
+
+    #[test]
+    fn cache_finished_runs_and_invalidate_on_writes() {
+        let server = MockServer::start();
+        let path = std::env::temp_dir().join(format!("mlflow-cache-{}.json", std::process::id()));
+        let mut cache = Cache::new(server.client())
+            .ttl(Duration::from_secs(0))
+            .persist(&path)
+            .unwrap();
+        let run = cache.create_run(&"0".into(), 0, &[]).unwrap().info.run_id;
+        cache.log_metric(&run, "loss", 0.5, 1, 0).unwrap();
+        let count = |endpoint: &str| {
+            let requests = server.requests();
+            requests
+                .iter()
+                .filter(|r| r.path.ends_with(endpoint))
+                .count()
+        };
+
+        // Active runs expire right away with a ttl of zero.
+        cache.get_run(&run).unwrap();
+        cache.get_run(&run).unwrap();
+        assert_eq!(count("runs/get"), 2);
+
+        cache
+            .update_run(&run, RunStatus::Finished, Some(2))
+            .unwrap();
+        assert_eq!(
+            cache.get_run(&run).unwrap().info.status,
+            RunStatus::Finished
+        );
+        cache.get_run(&run).unwrap();
+        cache.get_metric_history(&run, "loss").unwrap();
+        cache.get_metric_history(&run, "loss").unwrap();
+        assert_eq!((count("runs/get"), count("metrics/get-history")), (3, 1));
+
+        cache.log_param(&run, "lr", "0.1").unwrap();
+        assert_eq!(cache.len(), 0);
+        cache.get_run(&run).unwrap();
+        cache.save().unwrap();
+        drop(cache);
+
+        server.clear_requests();
+        let mut cache = Cache::new(server.client()).persist(&path).unwrap();
+        let restored = cache.get_run(&run).unwrap();
+        assert_eq!(restored.data.params.unwrap()[0].value, "0.1");
+        assert_eq!(count("runs/get"), 0);
+        cache.get_metric_history(&run, "loss").unwrap();
+
+        // Deleting the experiment can change the lifecycle stage of its finished runs.
+        cache.delete_experiment(&"0".into()).unwrap();
+        assert!(cache.is_empty());
+        cache.get_run(&run).unwrap();
+        assert_eq!(count("runs/get"), 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
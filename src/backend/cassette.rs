@@ -0,0 +1,319 @@
+//! Recording and replaying the requests of a [`Server`][super::rest::Server].
+//!
+//! A cassette is a JSON file with the requests sent to the server and the responses it returned.
+//! Use [`Server::recording`][super::rest::Server::recording] to record one against a real server,
+//! and [`Server::replaying`][super::rest::Server::replaying] to serve the recorded responses
+//! without a server, e.g. in tests on CI.
+//! Requests are matched by their method, endpoint and body, in the order they were recorded.
+//! JSON bodies are compared by value, so they may be reformatted, and the wall-clock fields
+//! [`CLOCK_FIELDS`] are ignored, so a replayed run may start at another time than the recorded one.
+//!
+//! A recording is written when the last clone of the server is dropped,
+//! or explicitly with [`Server::save_cassette`][super::rest::Server::save_cassette].
+
+use std::{
+    fs,
+    io::Read,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::api::error::StorageError;
+
+/// The fields of JSON bodies which hold the current time and are ignored when matching requests.
+pub const CLOCK_FIELDS: &[&str] = &["start_time", "end_time", "timestamp"];
+
+/// A request and the response the server returned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    /// The endpoint relative to the api url, e.g. `2.0/mlflow/runs/create`.
+    pub path: String,
+    /// The query string or body of the request. Binary bodies are only recorded by their size.
+    pub request: String,
+    pub status: u16,
+    /// The body of the response, where binary bodies are not preserved.
+    pub response: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Cassette {
+    interactions: Vec<Interaction>,
+    #[serde(skip)]
+    path: PathBuf,
+    /// Which interactions were already replayed.
+    #[serde(skip)]
+    used: Vec<bool>,
+    /// Whether interactions were recorded since the cassette was last saved.
+    #[serde(skip)]
+    unsaved: bool,
+}
+
+impl Cassette {
+    pub fn load(path: PathBuf) -> Result<Self, StorageError> {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read the cassette {}", path.display()))?;
+        let mut cassette: Cassette = serde_json::from_str(&content)
+            .with_context(|| format!("the cassette {} is not valid", path.display()))?;
+        cassette.used = vec![false; cassette.interactions.len()];
+        cassette.path = path;
+        Ok(cassette)
+    }
+
+    fn save(&mut self) -> Result<(), StorageError> {
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(&self.path, content)
+            .with_context(|| format!("failed to write the cassette {}", self.path.display()))?;
+        self.unsaved = false;
+        Ok(())
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        if self.unsaved {
+            // Errors can not be reported here, `Server::save_cassette` reports them.
+            let _ = self.save();
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Body<'a> {
+    Query(&'a str),
+    Text(&'a str),
+    Bytes(&'a [u8]),
+}
+
+impl Body<'_> {
+    fn recorded(&self) -> String {
+        match self {
+            Body::Query(query) => query.to_string(),
+            Body::Text(text) => text.to_string(),
+            Body::Bytes(bytes) => format!("<{} bytes>", bytes.len()),
+        }
+    }
+}
+
+/// The status and body of a response.
+pub(crate) struct Exchange {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Exchange {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Transport {
+    Http,
+    Record(Arc<Mutex<Cassette>>),
+    Replay(Arc<Mutex<Cassette>>),
+}
+
+impl Transport {
+    pub fn record(path: PathBuf) -> Self {
+        Transport::Record(Arc::new(Mutex::new(Cassette {
+            interactions: Vec::new(),
+            path,
+            used: Vec::new(),
+            // The cassette is overwritten even if nothing is recorded.
+            unsaved: true,
+        })))
+    }
+
+    pub fn replay(path: PathBuf) -> Result<Self, StorageError> {
+        Ok(Transport::Replay(Arc::new(Mutex::new(Cassette::load(
+            path,
+        )?))))
+    }
+
+    /// Writes the recorded interactions, does nothing unless recording.
+    pub fn save(&self) -> Result<(), StorageError> {
+        match self {
+            Transport::Record(cassette) => cassette.lock().unwrap().save(),
+            Transport::Http | Transport::Replay(_) => Ok(()),
+        }
+    }
+
+    pub fn send(
+        &self,
+        method: &str,
+        api_url: &str,
+        path: &str,
+        body: Body,
+    ) -> Result<Exchange, StorageError> {
+        match self {
+            Transport::Http => send_http(method, &format!("{}/{}", api_url, path), body),
+            Transport::Record(cassette) => {
+                let exchange = send_http(method, &format!("{}/{}", api_url, path), body)?;
+                let mut cassette = cassette.lock().unwrap();
+                cassette.interactions.push(Interaction {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    request: body.recorded(),
+                    status: exchange.status,
+                    response: exchange.text(),
+                });
+                cassette.unsaved = true;
+                Ok(exchange)
+            }
+            Transport::Replay(cassette) => {
+                let mut cassette = cassette.lock().unwrap();
+                let request = body.recorded();
+                let Cassette {
+                    interactions, used, ..
+                } = &mut *cassette;
+                let (interaction, used) = interactions
+                    .iter()
+                    .zip(used.iter_mut())
+                    .find(|(interaction, used)| {
+                        !**used
+                            && interaction.method == method
+                            && interaction.path == path
+                            && same_request(&interaction.request, &request)
+                    })
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "the cassette has no response for {} {} {}",
+                            method,
+                            path,
+                            request
+                        )
+                    })?;
+                *used = true;
+                Ok(Exchange {
+                    status: interaction.status,
+                    body: interaction.response.clone().into_bytes(),
+                })
+            }
+        }
+    }
+}
+
+/// Compares JSON bodies by value, so recorded bodies can be formatted for readability.
+///
+/// The [`CLOCK_FIELDS`] are removed before comparing.
+fn same_request(recorded: &str, request: &str) -> bool {
+    let parse = |body| {
+        let mut value = serde_json::from_str::<serde_json::Value>(body).ok()?;
+        remove_clock_fields(&mut value);
+        Some(value)
+    };
+    match (parse(recorded), parse(request)) {
+        (Some(recorded), Some(request)) => recorded == request,
+        _ => recorded == request,
+    }
+}
+
+fn remove_clock_fields(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            object.retain(|key, _| !CLOCK_FIELDS.contains(&key.as_str()));
+            object.values_mut().for_each(remove_clock_fields);
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(remove_clock_fields),
+        _ => {}
+    }
+}
+
+fn send_http(method: &str, url: &str, body: Body) -> Result<Exchange, StorageError> {
+    let mut request = ureq::request(method, url);
+    let response = match body {
+        Body::Query(query) => request.query_str(query).call(),
+        Body::Text(text) => request.send_string(text),
+        Body::Bytes(bytes) => request.send_bytes(bytes),
+    };
+    if let Some(error) = response.synthetic_error() {
+        return Err(anyhow!("{}", error)).with_context(|| format!("{} {} failed", method, url));
+    }
+    let status = response.status();
+    let mut body = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut body)
+        .with_context(|| format!("failed to read the response of {} {}", method, url))?;
+    Ok(Exchange { status, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{api::run::Metric, backend::rest::Server, testing::MockServer, Client};
+
+    #[test]
+    fn replay_matches_requests() {
+        let path =
+            std::env::temp_dir().join(format!("mlflow-cassette-{}.json", std::process::id()));
+        let cassette = serde_json::json!({
+            "interactions": [
+                {
+                    "method": "POST",
+                    "path": "2.0/mlflow/experiments/create",
+                    "request": r#"{ "name": "exp", "artifact_location": null }"#,
+                    "status": 200,
+                    "response": r#"{"experiment_id":"7"}"#
+                },
+                {
+                    "method": "POST",
+                    "path": "2.0/mlflow/experiments/create",
+                    "request": r#"{"artifact_location":null,"name":"exp"}"#,
+                    "status": 400,
+                    "response": r#"{"error_code":"RESOURCE_ALREADY_EXISTS","message":"exists"}"#
+                }
+            ]
+        });
+        std::fs::write(&path, cassette.to_string()).unwrap();
+
+        let mut server = Server::replaying(&path).unwrap();
+        assert_eq!(server.create_experiment("exp").unwrap().as_ref(), "7");
+        assert!(server.create_experiment("exp").is_err());
+        let error = server.create_experiment("other").unwrap_err();
+        assert!(format!("{:?}", error).contains("no response"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recordings_are_saved_once_and_replayed_at_another_time() {
+        let path = std::env::temp_dir().join(format!(
+            "mlflow-cassette-record-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let mock = MockServer::start();
+        let metric = |timestamp| Metric {
+            key: "loss".into(),
+            value: 0.5,
+            timestamp,
+            step: 0,
+        };
+
+        let mut server = mock.client().recording(&path);
+        let experiment = server.create_experiment("exp").unwrap();
+        let run = server.create_run(&experiment, 1000, &[]).unwrap();
+        let id = run.info.run_id;
+        server.log_batch(&id, &[metric(1000)], &[], &[]).unwrap();
+        assert!(!path.exists());
+        server.save_cassette().unwrap();
+        assert!(path.exists());
+        server
+            .update_run(&id, crate::api::run::RunStatus::Finished, Some(2000))
+            .unwrap();
+        drop(server);
+
+        let mut server = Server::replaying(&path).unwrap();
+        let experiment = server.create_experiment("exp").unwrap();
+        let run = server.create_run(&experiment, 5000, &[]).unwrap();
+        assert_eq!(run.info.run_id, id);
+        server.log_batch(&id, &[metric(5000)], &[], &[]).unwrap();
+        server
+            .update_run(&id, crate::api::run::RunStatus::Finished, Some(6000))
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,815 @@
+//! A [`Client`] which reads and writes the `mlruns` directory layout of the MLflow file store.
+
+use std::{
+    collections::hash_map::RandomState,
+    fs,
+    hash::{BuildHasher, Hasher},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+use super::filter;
+use crate::{
+    api::{
+        artifact::validate_path,
+        client::{Client, ViewType},
+        error::{BatchError, CreateError, DeleteError, GetError, StorageError, UpdateError},
+        experiment::{Experiment, ExperimentTag},
+        run::{Metric, Param, Run, RunData, RunInfo, RunStatus, RunTag},
+        search::{ListRunsRequest, RunList, Search, SearchRunsRequest},
+        tags,
+    },
+    ExperimentId, RunId,
+};
+
+const META: &str = "meta.yaml";
+const TRASH: &str = ".trash";
+
+/// Stores experiments and runs like `mlflow` does without a tracking server,
+/// e.g. in the `mlruns` directory of a project.
+///
+/// Every experiment is a directory with a `meta.yaml`, which contains a directory for each run.
+/// Runs store each param, tag and metric in a file named after its key.
+/// Deleted experiments are moved to `.trash`, deleted runs are only marked as deleted.
+/// Artifacts are stored in the `artifacts` directory of each run,
+/// unless the store was opened [with another artifact root][FileStore::with_artifact_root].
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    root: PathBuf,
+    /// The uri below which new experiments store their artifacts, or `None` for the experiment directory.
+    artifact_root: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExperimentMeta {
+    artifact_location: String,
+    #[serde(default)]
+    creation_time: Option<i64>,
+    #[serde(deserialize_with = "string_or_number")]
+    experiment_id: String,
+    #[serde(default)]
+    last_update_time: Option<i64>,
+    lifecycle_stage: String,
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RunMeta {
+    artifact_uri: String,
+    #[serde(default)]
+    end_time: Option<i64>,
+    #[serde(default)]
+    entry_point_name: String,
+    #[serde(deserialize_with = "string_or_number")]
+    experiment_id: String,
+    lifecycle_stage: String,
+    run_id: String,
+    #[serde(default)]
+    run_name: String,
+    run_uuid: String,
+    #[serde(default)]
+    source_name: String,
+    #[serde(default = "local_source")]
+    source_type: i32,
+    #[serde(default)]
+    source_version: String,
+    #[serde(default)]
+    start_time: Option<i64>,
+    status: i32,
+    #[serde(default)]
+    tags: Vec<serde_yaml::Value>,
+    #[serde(default)]
+    user_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deleted_time: Option<i64>,
+}
+
+/// The `LOCAL` source type.
+fn local_source() -> i32 {
+    4
+}
+
+/// Older versions of MLflow wrote experiment ids as numbers.
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::String(text) => Ok(text),
+        serde_yaml::Value::Number(number) => Ok(number.to_string()),
+        other => Err(de::Error::custom(format!("invalid id {:?}", other))),
+    }
+}
+
+fn status_code(status: RunStatus) -> i32 {
+    match status {
+        RunStatus::Running => 1,
+        RunStatus::Scheduled => 2,
+        RunStatus::Finished => 3,
+        RunStatus::Failed => 4,
+        RunStatus::Killed => 5,
+    }
+}
+
+fn status_from_code(code: i32) -> Result<RunStatus, StorageError> {
+    Ok(match code {
+        1 => RunStatus::Running,
+        2 => RunStatus::Scheduled,
+        3 => RunStatus::Finished,
+        4 => RunStatus::Failed,
+        5 => RunStatus::Killed,
+        _ => bail!("invalid run status {}", code),
+    })
+}
+
+impl RunMeta {
+    #[allow(deprecated)]
+    fn info(&self) -> Result<RunInfo, StorageError> {
+        Ok(RunInfo {
+            run_id: RunId::from(self.run_id.as_str()),
+            run_uuid: self.run_uuid.clone(),
+            experiment_id: ExperimentId::from(self.experiment_id.as_str()),
+            user_id: self.user_id.clone(),
+            status: status_from_code(self.status)?,
+            start_time: self.start_time.unwrap_or_default(),
+            end_time: self.end_time,
+            artifact_uri: self.artifact_uri.clone(),
+            lifecycle_stage: self.lifecycle_stage.clone(),
+        })
+    }
+}
+
+fn read_yaml<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, StorageError> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_yaml::from_str(&content).with_context(|| format!("{} is not valid", path.display()))
+}
+
+fn write_yaml(path: &Path, value: &impl Serialize) -> Result<(), StorageError> {
+    let content = serde_yaml::to_string(value)?;
+    fs::write(path, content).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Reads all files below `dir` by their path relative to it, which are the keys of params,
+/// tags or metrics. Keys may contain `/`, which are stored as directories.
+fn read_entries(dir: &Path) -> Result<Vec<(String, String)>, StorageError> {
+    fn visit(
+        dir: &Path,
+        prefix: &str,
+        entries: &mut Vec<(String, String)>,
+    ) -> Result<(), StorageError> {
+        let listing = match fs::read_dir(dir) {
+            Ok(listing) => listing,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to list {}", dir.display()))
+            }
+        };
+        for entry in listing {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let key = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            if entry.file_type()?.is_dir() {
+                visit(&entry.path(), &key, entries)?;
+            } else {
+                let path = entry.path();
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                entries.push((key, content));
+            }
+        }
+        Ok(())
+    }
+    let mut entries = Vec::new();
+    visit(dir, "", &mut entries)?;
+    entries.sort();
+    Ok(entries)
+}
+
+/// Writes the value to the file of the key, replacing it.
+fn write_entry(dir: &Path, key: &str, value: &str) -> Result<(), StorageError> {
+    let path = entry_path(dir, key)?;
+    fs::write(&path, value).with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn entry_path(dir: &Path, key: &str) -> Result<PathBuf, StorageError> {
+    validate_path(key).with_context(|| format!("invalid key {}", key))?;
+    let path = dir.join(key);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    Ok(path)
+}
+
+/// Parses a line `timestamp value step` of a metric file, where old files have no step.
+fn parse_metric(key: &str, line: &str) -> Result<Metric<'static>, StorageError> {
+    let invalid = || anyhow!("invalid value {:?} of the metric {}", line, key);
+    let mut parts = line.split_whitespace();
+    let timestamp = parts
+        .next()
+        .ok_or_else(invalid)?
+        .parse()
+        .map_err(|_| invalid())?;
+    let value = parts
+        .next()
+        .ok_or_else(invalid)?
+        .parse()
+        .map_err(|_| invalid())?;
+    let step = match parts.next() {
+        Some(step) => step.parse().map_err(|_| invalid())?,
+        None => 0,
+    };
+    Ok(Metric {
+        key: key.to_string().into(),
+        value,
+        timestamp,
+        step,
+    })
+}
+
+/// A random id with 32 hex digits, like the uuids used by MLflow.
+fn random_id() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let half = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.write_u32(std::process::id());
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(), half())
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+impl FileStore {
+    /// Uses the directory at `root`, which is created with a `Default` experiment if necessary.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        Self::create(root.into(), None)
+    }
+
+    /// Like [`open`][FileStore::open], but new experiments store their artifacts below
+    /// `artifact_root`, e.g. `mlflow-artifacts:/` when a tracking server proxies them.
+    pub fn with_artifact_root(
+        root: impl Into<PathBuf>,
+        artifact_root: impl Into<String>,
+    ) -> Result<Self, StorageError> {
+        Self::create(root.into(), Some(artifact_root.into()))
+    }
+
+    fn create(root: PathBuf, artifact_root: Option<String>) -> Result<Self, StorageError> {
+        fs::create_dir_all(&root)
+            .with_context(|| format!("failed to create {}", root.display()))?;
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("failed to resolve {}", root.display()))?;
+        let store = FileStore {
+            root,
+            artifact_root,
+        };
+        if !store.root.join("0").join(META).exists() && !store.root.join(TRASH).join("0").exists() {
+            store.write_experiment("0", "Default")?;
+        }
+        Ok(store)
+    }
+
+    fn write_experiment(&self, id: &str, name: &str) -> Result<(), StorageError> {
+        let dir = self.root.join(id);
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let now = crate::timestamp();
+        let artifact_location = match &self.artifact_root {
+            Some(root) => format!("{}/{}", root.trim_end_matches('/'), id),
+            None => file_uri(&dir),
+        };
+        let meta = ExperimentMeta {
+            artifact_location,
+            creation_time: Some(now),
+            experiment_id: id.to_string(),
+            last_update_time: Some(now),
+            lifecycle_stage: "active".to_string(),
+            name: name.to_string(),
+        };
+        write_yaml(&dir.join(META), &meta)
+    }
+
+    /// The directories of all experiments, active ones first.
+    fn experiment_dirs(&self, view_type: ViewType) -> Result<Vec<PathBuf>, StorageError> {
+        let mut parents = Vec::new();
+        if view_type != ViewType::Deleted {
+            parents.push(self.root.clone());
+        }
+        if view_type != ViewType::Active {
+            parents.push(self.root.join(TRASH));
+        }
+        let mut dirs = Vec::new();
+        for parent in parents {
+            let listing = match fs::read_dir(&parent) {
+                Ok(listing) => listing,
+                Err(error) if error.kind() == ErrorKind::NotFound => continue,
+                Err(error) => {
+                    return Err(error)
+                        .with_context(|| format!("failed to list {}", parent.display()))
+                }
+            };
+            let mut found = listing
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.join(META).is_file())
+                .collect::<Vec<_>>();
+            found.sort();
+            dirs.extend(found);
+        }
+        Ok(dirs)
+    }
+
+    fn experiment_dir(&self, id: &ExperimentId) -> Result<PathBuf, GetError> {
+        let id = id.as_ref();
+        if validate_path(id).is_err() || id.contains('/') {
+            return Err(GetError::DoesNotExist(id.to_string()));
+        }
+        [self.root.join(id), self.root.join(TRASH).join(id)]
+            .iter()
+            .find(|dir| dir.join(META).is_file())
+            .cloned()
+            .ok_or_else(|| GetError::DoesNotExist(id.to_string()))
+    }
+
+    fn read_experiment(&self, dir: &Path) -> Result<Experiment, StorageError> {
+        let meta: ExperimentMeta = read_yaml(&dir.join(META))?;
+        let tags = read_entries(&dir.join("tags"))?;
+        let tags = tags
+            .into_iter()
+            .map(|(key, value)| ExperimentTag { key, value });
+        Ok(Experiment {
+            experiment_id: meta.experiment_id.into(),
+            name: meta.name,
+            artifact_location: meta.artifact_location,
+            lifecycle_stage: meta.lifecycle_stage,
+            last_update_time: meta.last_update_time,
+            creation_time: meta.creation_time,
+            tags: Some(tags.collect()),
+        })
+    }
+
+    fn update_experiment_meta(
+        &self,
+        dir: &Path,
+        update: impl FnOnce(&mut ExperimentMeta),
+    ) -> Result<(), StorageError> {
+        let path = dir.join(META);
+        let mut meta: ExperimentMeta = read_yaml(&path)?;
+        update(&mut meta);
+        meta.last_update_time = Some(crate::timestamp());
+        write_yaml(&path, &meta)
+    }
+
+    fn run_dir(&self, id: &RunId) -> Result<PathBuf, GetError> {
+        let missing = || GetError::DoesNotExist(id.as_ref().to_string());
+        if validate_path(id.as_ref()).is_err() || id.as_ref().contains('/') {
+            return Err(missing());
+        }
+        for experiment in self.experiment_dirs(ViewType::All)? {
+            let dir = experiment.join(id.as_ref());
+            if dir.join(META).is_file() {
+                return Ok(dir);
+            }
+        }
+        Err(missing())
+    }
+
+    fn update_run_meta(
+        &self,
+        dir: &Path,
+        update: impl FnOnce(&mut RunMeta),
+    ) -> Result<RunInfo, StorageError> {
+        let path = dir.join(META);
+        let mut meta: RunMeta = read_yaml(&path)?;
+        update(&mut meta);
+        write_yaml(&path, &meta)?;
+        meta.info()
+    }
+
+    fn read_run(&self, dir: &Path) -> Result<Run, StorageError> {
+        let meta: RunMeta = read_yaml(&dir.join(META))?;
+        let params = read_entries(&dir.join("params"))?;
+        let tags = read_entries(&dir.join("tags"))?;
+        let mut metrics = Vec::new();
+        for (key, content) in read_entries(&dir.join("metrics"))? {
+            let history = content.lines().filter(|line| !line.trim().is_empty());
+            let history = history.map(|line| parse_metric(&key, line));
+            let latest = history
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .max_by(|a, b| {
+                    (a.step, a.timestamp)
+                        .cmp(&(b.step, b.timestamp))
+                        .then(std::cmp::Ordering::Less)
+                });
+            metrics.extend(latest);
+        }
+        Ok(Run {
+            info: meta.info()?,
+            data: RunData {
+                metrics: Some(metrics),
+                params: Some(
+                    params
+                        .into_iter()
+                        .map(|(key, value)| Param { key, value })
+                        .collect(),
+                ),
+                tags: Some(
+                    tags.into_iter()
+                        .map(|(key, value)| RunTag { key, value })
+                        .collect(),
+                ),
+            },
+        })
+    }
+
+    fn runs(&self, experiments: &[ExperimentId]) -> Result<Vec<Run>, StorageError> {
+        let mut runs = Vec::new();
+        for experiment in experiments {
+            let dir = match self.experiment_dir(experiment) {
+                Ok(dir) => dir,
+                Err(GetError::DoesNotExist(_)) => continue,
+                Err(error) => return Err(error.into()),
+            };
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.join(META).is_file() {
+                    runs.push(self.read_run(&path)?);
+                }
+            }
+        }
+        Ok(runs)
+    }
+}
+
+impl Client for FileStore {
+    fn create_experiment(&mut self, name: &str) -> Result<ExperimentId, CreateError> {
+        if name.is_empty() {
+            return Err(anyhow!("the experiment name must not be empty").into());
+        }
+        let mut next = 0;
+        for dir in self.experiment_dirs(ViewType::All)? {
+            let experiment = self.read_experiment(&dir)?;
+            if experiment.name == name {
+                return Err(CreateError::AlreadyExists(name.to_string()));
+            }
+            if let Ok(id) = experiment.experiment_id.as_ref().parse::<u64>() {
+                next = next.max(id + 1);
+            }
+        }
+        let id = next.to_string();
+        self.write_experiment(&id, name)?;
+        Ok(id.into())
+    }
+
+    fn list_experiments(&mut self, view_type: ViewType) -> Result<Vec<Experiment>, StorageError> {
+        let dirs = self.experiment_dirs(view_type)?;
+        dirs.iter().map(|dir| self.read_experiment(dir)).collect()
+    }
+
+    fn get_experiment(&mut self, id: &ExperimentId) -> Result<Experiment, GetError> {
+        let dir = self.experiment_dir(id)?;
+        Ok(self.read_experiment(&dir)?)
+    }
+
+    fn get_experiment_by_name(&mut self, name: &str) -> Result<Experiment, GetError> {
+        let experiments = self.list_experiments(ViewType::All)?;
+        let experiment = experiments.into_iter().find(|e| e.name == name);
+        experiment.ok_or_else(|| GetError::DoesNotExist(name.to_string()))
+    }
+
+    fn delete_experiment(&mut self, id: &ExperimentId) -> Result<(), DeleteError> {
+        let dir = self.experiment_dir(id)?;
+        self.update_experiment_meta(&dir, |meta| meta.lifecycle_stage = "deleted".to_string())?;
+        let trash = self.root.join(TRASH);
+        if !dir.starts_with(&trash) {
+            fs::create_dir_all(&trash)
+                .with_context(|| format!("failed to create {}", trash.display()))?;
+            fs::rename(&dir, trash.join(id.as_ref()))
+                .with_context(|| format!("failed to move {} to the trash", dir.display()))?;
+        }
+        Ok(())
+    }
+
+    fn restore_experiment(&mut self, id: &ExperimentId) -> Result<(), UpdateError> {
+        let dir = self.experiment_dir(id)?;
+        self.update_experiment_meta(&dir, |meta| meta.lifecycle_stage = "active".to_string())?;
+        if dir.starts_with(self.root.join(TRASH)) {
+            fs::rename(&dir, self.root.join(id.as_ref()))
+                .with_context(|| format!("failed to restore {}", dir.display()))?;
+        }
+        Ok(())
+    }
+
+    fn update_experiment(
+        &mut self,
+        id: &ExperimentId,
+        new_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        if let Some(name) = new_name {
+            match self.get_experiment_by_name(name) {
+                Ok(other) if other.experiment_id != *id => {
+                    return Err(CreateError::AlreadyExists(name.to_string()).into())
+                }
+                Ok(_) | Err(GetError::DoesNotExist(_)) => {}
+                Err(error) => return Err(error.into()),
+            }
+            let dir = self.experiment_dir(id)?;
+            self.update_experiment_meta(&dir, |meta| meta.name = name.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn create_run(
+        &mut self,
+        experiment: &ExperimentId,
+        start_time: i64,
+        tags: &[RunTag],
+    ) -> Result<Run, StorageError> {
+        let experiment_dir = self.experiment_dir(experiment)?;
+        let meta: ExperimentMeta = read_yaml(&experiment_dir.join(META))?;
+        if meta.lifecycle_stage != "active" {
+            bail!("the experiment {} is deleted", experiment.as_ref());
+        }
+        let id = random_id();
+        let dir = experiment_dir.join(&id);
+        for sub in &["metrics", "params", "tags", "artifacts"] {
+            fs::create_dir_all(dir.join(sub))
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let tag = |key| {
+            tags.iter()
+                .find(|tag| tag.key == key)
+                .map(|tag| tag.value.clone())
+        };
+        let meta = RunMeta {
+            artifact_uri: format!(
+                "{}/{}/artifacts",
+                meta.artifact_location.trim_end_matches('/'),
+                id
+            ),
+            end_time: None,
+            entry_point_name: String::new(),
+            experiment_id: experiment.as_ref().to_string(),
+            lifecycle_stage: "active".to_string(),
+            run_id: id.clone(),
+            run_name: tag(tags::RUN_NAME).unwrap_or_default(),
+            run_uuid: id.clone(),
+            source_name: String::new(),
+            source_type: local_source(),
+            source_version: String::new(),
+            start_time: Some(start_time),
+            status: status_code(RunStatus::Running),
+            tags: Vec::new(),
+            user_id: tag(tags::USER).unwrap_or_default(),
+            deleted_time: None,
+        };
+        write_yaml(&dir.join(META), &meta)?;
+        for tag in tags {
+            write_entry(&dir.join("tags"), &tag.key, &tag.value)?;
+        }
+        self.read_run(&dir)
+    }
+
+    fn delete_run(&mut self, id: &RunId) -> Result<(), DeleteError> {
+        let dir = self.run_dir(id)?;
+        self.update_run_meta(&dir, |meta| {
+            meta.lifecycle_stage = "deleted".to_string();
+            meta.deleted_time = Some(crate::timestamp());
+        })?;
+        Ok(())
+    }
+
+    fn restore_run(&mut self, id: &RunId) -> Result<(), UpdateError> {
+        let dir = self.run_dir(id)?;
+        self.update_run_meta(&dir, |meta| {
+            meta.lifecycle_stage = "active".to_string();
+            meta.deleted_time = None;
+        })?;
+        Ok(())
+    }
+
+    fn get_run(&mut self, id: &RunId) -> Result<Run, GetError> {
+        let dir = self.run_dir(id)?;
+        Ok(self.read_run(&dir)?)
+    }
+
+    fn update_run(
+        &mut self,
+        id: &RunId,
+        status: RunStatus,
+        end_time: Option<i64>,
+    ) -> Result<RunInfo, UpdateError> {
+        let dir = self.run_dir(id)?;
+        let info = self.update_run_meta(&dir, |meta| {
+            meta.status = status_code(status);
+            meta.end_time = end_time.or(meta.end_time);
+        })?;
+        Ok(info)
+    }
+
+    fn search_runs(&mut self, request: &SearchRunsRequest) -> Result<Search, StorageError> {
+        let runs = self.runs(&request.experiment_ids)?;
+        filter::search(runs.into_iter(), request)
+    }
+
+    fn list_run_infos(&mut self, request: &ListRunsRequest) -> Result<RunList, StorageError> {
+        let mut search = SearchRunsRequest::new(std::slice::from_ref(&request.experiment_id))
+            .view_type(request.run_view_type)
+            .max_results(request.max_results)
+            .page_token(request.page_token.clone());
+        search.order_by = request.order_by.clone();
+        let search = self.search_runs(&search)?;
+        Ok(RunList {
+            runs: search.runs.into_iter().map(|run| run.info).collect(),
+            page_token: search.next_page_token,
+        })
+    }
+
+    fn get_metric_history(
+        &mut self,
+        run: &RunId,
+        metric: &str,
+    ) -> Result<Vec<Metric<'static>>, GetError> {
+        let dir = self.run_dir(run)?.join("metrics");
+        validate_path(metric).map_err(|_| GetError::DoesNotExist(metric.to_string()))?;
+        let content = match fs::read_to_string(dir.join(metric)) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(anyhow!(error).into()),
+        };
+        let lines = content.lines().filter(|line| !line.trim().is_empty());
+        let history = lines.map(|line| parse_metric(metric, line));
+        Ok(history.collect::<Result<_, _>>()?)
+    }
+
+    fn log_param(&mut self, run: &RunId, key: &str, value: &str) -> Result<(), StorageError> {
+        let param = Param {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        Ok(self.log_batch(run, &[], &[param], &[])?)
+    }
+
+    fn log_metric(
+        &mut self,
+        run: &RunId,
+        key: &str,
+        value: f64,
+        timestamp: i64,
+        step: i64,
+    ) -> Result<(), StorageError> {
+        let metric = Metric {
+            key: key.into(),
+            value,
+            timestamp,
+            step,
+        };
+        Ok(self.log_batch(run, &[metric], &[], &[])?)
+    }
+
+    fn log_batch(
+        &mut self,
+        run: &RunId,
+        metrics: &[Metric],
+        params: &[Param],
+        tags: &[RunTag],
+    ) -> Result<(), BatchError> {
+        BatchError::check(metrics, params, tags)?;
+        let dir = self.run_dir(run).map_err(|error| anyhow!(error))?;
+        let meta: RunMeta = read_yaml(&dir.join(META))?;
+        if meta.lifecycle_stage != "active" {
+            return Err(anyhow!("the run {} is deleted", run.as_ref()).into());
+        }
+        for param in params {
+            let path = entry_path(&dir.join("params"), &param.key)?;
+            match fs::read_to_string(&path) {
+                Ok(logged) if logged != param.value => {
+                    return Err(BatchError::ConflictingParam {
+                        key: param.key.clone(),
+                        first: logged,
+                        second: param.value.clone(),
+                    })
+                }
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::NotFound => {
+                    write_entry(&dir.join("params"), &param.key, &param.value)?
+                }
+                Err(error) => return Err(anyhow!(error).into()),
+            }
+        }
+        for tag in tags {
+            write_entry(&dir.join("tags"), &tag.key, &tag.value)?;
+            if tag.key == tags::RUN_NAME {
+                self.update_run_meta(&dir, |meta| meta.run_name = tag.value.clone())?;
+            }
+        }
+        for metric in metrics {
+            use std::io::Write;
+            let path = entry_path(&dir.join("metrics"), &metric.key)?;
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("failed to open {}", path.display()))?;
+            writeln!(
+                file,
+                "{} {} {}",
+                metric.timestamp, metric.value, metric.step
+            )
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FileStore;
+    use crate::{
+        api::{client::ViewType, run::RunStatus},
+        Client,
+    };
+
+    #[test]
+    fn read_what_mlflow_wrote() {
+        let root = std::env::temp_dir().join(format!("mlflow-file-store-{}", std::process::id()));
+        let run = root.join("1").join("abc");
+        std::fs::create_dir_all(run.join("metrics").join("train")).unwrap();
+        std::fs::create_dir_all(run.join("params")).unwrap();
+        std::fs::write(
+            root.join("1").join("meta.yaml"),
+            "artifact_location: file:///tmp/mlruns/1\nexperiment_id: 1\nlifecycle_stage: active\nname: exp\n",
+        )
+        .unwrap();
+        std::fs::write(
+            run.join("meta.yaml"),
+            "artifact_uri: file:///tmp/mlruns/1/abc/artifacts\nend_time: 20\nexperiment_id: '1'\nlifecycle_stage: active\nrun_id: abc\nrun_uuid: abc\nstart_time: 10\nstatus: 3\nuser_id: me\n",
+        )
+        .unwrap();
+        std::fs::write(
+            run.join("metrics").join("train").join("loss"),
+            "1 0.5 0\n2 0.25 1\n",
+        )
+        .unwrap();
+        std::fs::write(run.join("params").join("lr"), "0.1").unwrap();
+
+        let mut store = FileStore::open(&root).unwrap();
+        let experiments = store.list_experiments(ViewType::Active).unwrap();
+        assert_eq!(
+            experiments
+                .iter()
+                .map(|e| e.name.as_str())
+                .collect::<Vec<_>>(),
+            ["Default", "exp"]
+        );
+        let fetched = store.get_run(&"abc".into()).unwrap();
+        assert_eq!(fetched.info.status, RunStatus::Finished);
+        assert_eq!(fetched.data.metrics.unwrap()[0].value, 0.25);
+        assert_eq!(
+            store
+                .get_metric_history(&"abc".into(), "train/loss")
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let experiment = store.create_experiment("new").unwrap();
+        assert_eq!(experiment.as_ref(), "2");
+        let created = store.create_run(&experiment, 5, &[]).unwrap().info.run_id;
+        store.log_metric(&created, "acc", 0.5, 6, 0).unwrap();
+        store.log_param(&created, "lr", "0.1").unwrap();
+        assert!(store.log_param(&created, "lr", "0.2").is_err());
+        store
+            .update_run(&created, RunStatus::Failed, Some(7))
+            .unwrap();
+        let reread = store.get_run(&created).unwrap();
+        assert_eq!(reread.info.end_time, Some(7));
+        store.delete_experiment(&experiment).unwrap();
+        assert!(root
+            .join(".trash")
+            .join("2")
+            .join(created.as_ref())
+            .is_dir());
+
+        let mut store = FileStore::with_artifact_root(&root, "mlflow-artifacts:/").unwrap();
+        let proxied = store.create_experiment("proxied").unwrap();
+        let run = store.create_run(&proxied, 8, &[]).unwrap();
+        assert_eq!(
+            run.info.artifact_uri,
+            format!("mlflow-artifacts:/3/{}/artifacts", run.info.run_id.as_ref())
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,422 @@
+//! A subset of the MLflow search syntax, used by the backends which search runs themselves.
+//!
+//! Filters are conjunctions of comparisons like ``metrics.loss < 0.5 AND tags.`mlflow.user` = 'me'``.
+//! Supported operators are `=`, `!=`, `<`, `<=`, `>`, `>=`, `LIKE` and `ILIKE`.
+
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, bail};
+
+use crate::api::{
+    client::ViewType,
+    error::StorageError,
+    run::Run,
+    search::{Search, SearchRunsRequest, DEFAULT_MAX_RESULTS},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entity {
+    Metric,
+    Param,
+    Tag,
+    Attribute,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+impl Value {
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Number(a), Value::Text(b)) => {
+                b.parse::<f64>().ok()?.partial_cmp(a)?.reverse().into()
+            }
+            (Value::Text(a), Value::Number(b)) => a.parse::<f64>().ok()?.partial_cmp(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    entity: Entity,
+    key: String,
+}
+
+impl Field {
+    fn parse(identifier: &str) -> Result<Self, StorageError> {
+        // Like in MLflow, identifiers without an entity are attributes, e.g. `start_time`.
+        let (entity, key) = identifier
+            .split_once('.')
+            .unwrap_or(("attribute", identifier));
+        let entity = match entity.to_ascii_lowercase().as_str() {
+            "metric" | "metrics" => Entity::Metric,
+            "param" | "params" | "parameter" | "parameters" => Entity::Param,
+            "tag" | "tags" => Entity::Tag,
+            "attribute" | "attributes" | "attr" | "run" => Entity::Attribute,
+            other => bail!("invalid entity {} in {}", other, identifier),
+        };
+        let key = unquote(key.trim());
+        Ok(Field { entity, key })
+    }
+
+    fn value(&self, run: &Run) -> Option<Value> {
+        let data = &run.data;
+        let info = &run.info;
+        let text = |value: &str| Some(Value::Text(value.to_string()));
+        match self.entity {
+            Entity::Metric => data
+                .metrics
+                .iter()
+                .flatten()
+                .find(|metric| metric.key == self.key)
+                .map(|metric| Value::Number(metric.value)),
+            Entity::Param => data
+                .params
+                .iter()
+                .flatten()
+                .find(|param| param.key == self.key)
+                .and_then(|param| text(&param.value)),
+            Entity::Tag => data
+                .tags
+                .iter()
+                .flatten()
+                .find(|tag| tag.key == self.key)
+                .and_then(|tag| text(&tag.value)),
+            Entity::Attribute => match self.key.as_str() {
+                "run_id" => text(info.run_id.as_ref()),
+                "status" => serde_json::to_value(info.status)
+                    .ok()
+                    .and_then(|status| status.as_str().and_then(text)),
+                "start_time" => Some(Value::Number(info.start_time as f64)),
+                "end_time" => info.end_time.map(|time| Value::Number(time as f64)),
+                "artifact_uri" => text(&info.artifact_uri),
+                "run_name" => data
+                    .tags
+                    .iter()
+                    .flatten()
+                    .find(|tag| tag.key == crate::api::tags::RUN_NAME)
+                    .and_then(|tag| text(&tag.value)),
+                _ => None,
+            },
+        }
+    }
+}
+
+fn unquote(text: &str) -> String {
+    let quoted = ['`', '"', '\'']
+        .iter()
+        .any(|&quote| text.len() >= 2 && text.starts_with(quote) && text.ends_with(quote));
+    if quoted {
+        text[1..text.len() - 1].to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Like,
+    ILike,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Comparison {
+    field: Field,
+    operator: Operator,
+    value: Value,
+}
+
+impl Comparison {
+    fn matches(&self, run: &Run) -> bool {
+        let actual = match self.field.value(run) {
+            Some(actual) => actual,
+            None => return false,
+        };
+        match self.operator {
+            Operator::Like | Operator::ILike => {
+                let (actual, pattern) = match (&actual, &self.value) {
+                    (Value::Text(actual), Value::Text(pattern)) => (actual, pattern),
+                    _ => return false,
+                };
+                if self.operator == Operator::ILike {
+                    like(&actual.to_lowercase(), &pattern.to_lowercase())
+                } else {
+                    like(actual, pattern)
+                }
+            }
+            operator => {
+                let ordering = match actual.compare(&self.value) {
+                    Some(ordering) => ordering,
+                    None => return false,
+                };
+                match operator {
+                    Operator::Equal => ordering == Ordering::Equal,
+                    Operator::NotEqual => ordering != Ordering::Equal,
+                    Operator::Less => ordering == Ordering::Less,
+                    Operator::LessOrEqual => ordering != Ordering::Greater,
+                    Operator::Greater => ordering == Ordering::Greater,
+                    Operator::GreaterOrEqual => ordering != Ordering::Less,
+                    Operator::Like | Operator::ILike => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+/// Matches a SQL `LIKE` pattern, where `%` matches any text and `_` any single character.
+fn like(text: &str, pattern: &str) -> bool {
+    let text = text.chars().collect::<Vec<_>>();
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    // matches[j] is whether the text so far matches the first j characters of the pattern.
+    let mut matches = vec![false; pattern.len() + 1];
+    matches[0] = true;
+    for j in 1..=pattern.len() {
+        matches[j] = matches[j - 1] && pattern[j - 1] == '%';
+    }
+    for c in text {
+        let mut next = vec![false; pattern.len() + 1];
+        for j in 1..=pattern.len() {
+            next[j] = match pattern[j - 1] {
+                '%' => next[j - 1] || matches[j],
+                '_' => matches[j - 1],
+                p => matches[j - 1] && p == c,
+            };
+        }
+        matches = next;
+    }
+    matches[pattern.len()]
+}
+
+/// A parsed filter, which matches all runs if it is empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Filter(Vec<Comparison>);
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Self, StorageError> {
+        let mut comparisons = Vec::new();
+        let tokens = tokenize(filter)?;
+        for clause in tokens.split(|token| token.eq_ignore_ascii_case("and")) {
+            match clause {
+                [] if tokens.is_empty() => {}
+                [field, operator, value] => comparisons.push(Comparison {
+                    field: Field::parse(field)?,
+                    operator: match operator.to_ascii_uppercase().as_str() {
+                        "=" => Operator::Equal,
+                        "!=" => Operator::NotEqual,
+                        "<" => Operator::Less,
+                        "<=" => Operator::LessOrEqual,
+                        ">" => Operator::Greater,
+                        ">=" => Operator::GreaterOrEqual,
+                        "LIKE" => Operator::Like,
+                        "ILIKE" => Operator::ILike,
+                        other => bail!("unsupported operator {} in filter {:?}", other, filter),
+                    },
+                    value: if value.starts_with('\'') || value.starts_with('"') {
+                        Value::Text(unquote(value))
+                    } else {
+                        Value::Number(value.parse().map_err(|_| {
+                            anyhow!("invalid value {} in filter {:?}", value, filter)
+                        })?)
+                    },
+                }),
+                _ => bail!("unsupported filter {:?}", filter),
+            }
+        }
+        Ok(Filter(comparisons))
+    }
+
+    pub fn matches(&self, run: &Run) -> bool {
+        self.0.iter().all(|comparison| comparison.matches(run))
+    }
+}
+
+/// Splits the filter into identifiers, operators and values, keeping quoted parts together.
+fn tokenize(filter: &str) -> Result<Vec<String>, StorageError> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if "=!<>".contains(c) {
+            let mut operator = String::new();
+            while let Some(&c) = chars.peek().filter(|c| "=!<>".contains(**c)) {
+                operator.push(c);
+                chars.next();
+            }
+            tokens.push(operator);
+        } else {
+            let mut token = String::new();
+            let mut quote = None;
+            while let Some(&c) = chars.peek() {
+                match quote {
+                    Some(q) if c == q => quote = None,
+                    Some(_) => {}
+                    None if c == '\'' || c == '"' || c == '`' => quote = Some(c),
+                    None if c.is_whitespace() || "=!<>".contains(c) => break,
+                    None => {}
+                }
+                token.push(c);
+                chars.next();
+            }
+            if quote.is_some() {
+                bail!("unterminated quote in filter {:?}", filter);
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+/// An `order_by` clause like `metrics.loss ASC`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Order {
+    field: Field,
+    ascending: bool,
+}
+
+impl Order {
+    pub fn parse(order_by: &str) -> Result<Self, StorageError> {
+        let order_by = order_by.trim();
+        let (field, direction) = match order_by.rsplit_once(char::is_whitespace) {
+            Some((field, direction))
+                if direction.eq_ignore_ascii_case("asc")
+                    || direction.eq_ignore_ascii_case("desc") =>
+            {
+                (field.trim(), direction)
+            }
+            _ => (order_by, "asc"),
+        };
+        Ok(Order {
+            field: Field::parse(field)?,
+            ascending: direction.eq_ignore_ascii_case("asc"),
+        })
+    }
+
+    /// Orders by the start time, the newest runs first.
+    pub fn default_order() -> Self {
+        Order {
+            field: Field {
+                entity: Entity::Attribute,
+                key: "start_time".to_string(),
+            },
+            ascending: false,
+        }
+    }
+
+    /// Compares the runs, where runs without a value are always last.
+    pub fn compare(&self, a: &Run, b: &Run) -> Ordering {
+        match (self.field.value(a), self.field.value(b)) {
+            (Some(a), Some(b)) => {
+                let ordering = a.compare(&b).unwrap_or(Ordering::Equal);
+                if self.ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// Answers the search with the matching runs, using the offset of the page as its token.
+pub(crate) fn search(
+    runs: impl Iterator<Item = Run>,
+    request: &SearchRunsRequest,
+) -> Result<Search, StorageError> {
+    let filter = Filter::parse(&request.filter)?;
+    let mut orders = request
+        .order_by
+        .iter()
+        .map(|order_by| Order::parse(order_by))
+        .collect::<Result<Vec<_>, _>>()?;
+    orders.push(Order::default_order());
+
+    let mut runs = runs
+        .filter(|run| {
+            let active = run.info.lifecycle_stage == "active";
+            request.experiment_ids.contains(&run.info.experiment_id)
+                && match request.run_view_type {
+                    ViewType::Active => active,
+                    ViewType::Deleted => !active,
+                    ViewType::All => true,
+                }
+                && filter.matches(run)
+        })
+        .collect::<Vec<_>>();
+    runs.sort_by(|a, b| {
+        let mut ordering = orders.iter().map(|order| order.compare(a, b));
+        let ordering = ordering.find(|ordering| *ordering != Ordering::Equal);
+        ordering.unwrap_or_else(|| a.info.run_id.as_ref().cmp(b.info.run_id.as_ref()))
+    });
+
+    let offset = match request.page_token.as_ref().map(AsRef::as_ref) {
+        None | Some("") => 0,
+        Some(token) => token
+            .parse::<usize>()
+            .map_err(|_| anyhow!("invalid page token {}", token))?,
+    };
+    let max_results = match request.max_results {
+        max_results if max_results > 0 => max_results as usize,
+        _ => DEFAULT_MAX_RESULTS as usize,
+    };
+    let end = runs.len().min(offset.saturating_add(max_results));
+    let next_page_token = if end < runs.len() {
+        end.to_string().into()
+    } else {
+        Default::default()
+    };
+    let runs = runs.drain(offset.min(end)..end).collect();
+    Ok(Search {
+        runs,
+        next_page_token,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{like, tokenize, Filter};
+
+    #[test]
+    fn parse_filters() {
+        let tokens = tokenize("tags.`mlflow.parentRunId` = 'a b' and metrics.loss<=0.5").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                "tags.`mlflow.parentRunId`",
+                "=",
+                "'a b'",
+                "and",
+                "metrics.loss",
+                "<=",
+                "0.5"
+            ]
+        );
+        assert!(Filter::parse("").unwrap().0.is_empty());
+        assert_eq!(
+            Filter::parse("params.lr = '0.1' AND metrics.loss > 1")
+                .unwrap()
+                .0
+                .len(),
+            2
+        );
+        assert!(Filter::parse("params.lr ~ 1").is_err());
+        assert_eq!(Filter::parse("status = 'FINISHED'").unwrap().0.len(), 1);
+        assert!(like("train_loss", "%loss"));
+        assert!(like("loss", "l_s%"));
+        assert!(!like("accuracy", "%loss%"));
+    }
+}
@@ -0,0 +1,532 @@
+//! A [`Client`] which keeps everything in memory, e.g. for tests or short-lived servers.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{anyhow, bail, Context};
+use serde::{Deserialize, Serialize};
+
+use super::filter;
+use crate::{
+    api::{
+        artifact::{self, ArtifactLocation, FileInfo},
+        client::{Client, ViewType},
+        error::{BatchError, CreateError, DeleteError, GetError, StorageError, UpdateError},
+        experiment::Experiment,
+        run::{Metric, Param, Run, RunData, RunInfo, RunStatus, RunTag},
+        search::{ListRunsRequest, RunList, Search, SearchRunsRequest},
+    },
+    ExperimentId, RunId,
+};
+
+const ACTIVE: &str = "active";
+const DELETED: &str = "deleted";
+
+/// Stores experiments, runs and artifacts in memory and behaves like a tracking server.
+///
+/// Like on a server, there is a `Default` experiment with the id `0`,
+/// deleted experiments and runs are only marked as deleted,
+/// and the artifacts of runs are stored using the `mlflow-artifacts:` proxy.
+/// Searches support filters joined by `AND`, e.g. `metrics.loss < 0.5 AND params.lr = '0.1'`,
+/// with the operators `=`, `!=`, `<`, `<=`, `>`, `>=`, `LIKE` and `ILIKE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Memory {
+    experiments: Vec<Experiment>,
+    runs: Vec<StoredRun>,
+    /// The artifacts by their path below `mlflow-artifacts:/`.
+    #[serde(skip)]
+    artifacts: BTreeMap<String, Vec<u8>>,
+    created_runs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredRun {
+    info: RunInfo,
+    params: Vec<Param>,
+    tags: Vec<RunTag>,
+    /// The full history of all metrics, in the order they were logged.
+    metrics: Vec<Metric<'static>>,
+}
+
+impl StoredRun {
+    /// The run with the latest value of each metric, i.e. the one with the highest step and timestamp.
+    fn to_run(&self) -> Run {
+        let mut latest: Vec<Metric<'static>> = Vec::new();
+        for metric in &self.metrics {
+            match latest.iter_mut().find(|other| other.key == metric.key) {
+                Some(other) => {
+                    if (metric.step, metric.timestamp) >= (other.step, other.timestamp) {
+                        *other = metric.clone();
+                    }
+                }
+                None => latest.push(metric.clone()),
+            }
+        }
+        Run {
+            info: self.info.clone(),
+            data: RunData {
+                metrics: Some(latest),
+                params: Some(self.params.clone()),
+                tags: Some(self.tags.clone()),
+            },
+        }
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Memory {
+            experiments: vec![new_experiment("0".into(), "Default")],
+            runs: Vec::new(),
+            artifacts: BTreeMap::new(),
+            created_runs: 0,
+        }
+    }
+}
+
+fn new_experiment(id: ExperimentId, name: &str) -> Experiment {
+    let now = crate::timestamp();
+    Experiment {
+        artifact_location: format!("mlflow-artifacts:/{}", id.as_ref()),
+        experiment_id: id,
+        name: name.to_string(),
+        lifecycle_stage: ACTIVE.to_string(),
+        last_update_time: Some(now),
+        creation_time: Some(now),
+        tags: None,
+    }
+}
+
+/// Mixes the bits of `n`, so run ids look random but are reproducible.
+fn mix(n: u64) -> u64 {
+    let mut z = n.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn in_view(lifecycle_stage: &str, view_type: ViewType) -> bool {
+    match view_type {
+        ViewType::Active => lifecycle_stage == ACTIVE,
+        ViewType::Deleted => lifecycle_stage == DELETED,
+        ViewType::All => true,
+    }
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Memory::default()
+    }
+
+    /// Restores the experiments and runs which were [saved][Memory::save] to the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("{} is not valid", path.display()))
+    }
+
+    /// Writes the experiments and runs to a JSON file, but not the artifacts.
+    ///
+    /// The file is replaced atomically, so it stays valid if the process stops while saving.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), StorageError> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string(self)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))
+    }
+
+    /// The contents of an artifact uploaded to the run, e.g. by a [`TrackingRun`][crate::tracking::TrackingRun].
+    pub fn artifact(&self, run: &RunId, path: &str) -> Option<&[u8]> {
+        let root = self.artifact_root(run)?;
+        let artifact = self.artifacts.get(&format!("{}/{}", root, path))?;
+        Some(artifact)
+    }
+
+    /// The paths of all artifacts uploaded to the run.
+    pub fn artifact_paths(&self, run: &RunId) -> Vec<&str> {
+        let root = match self.artifact_root(run) {
+            Some(root) => format!("{}/", root),
+            None => return Vec::new(),
+        };
+        let paths = self.artifacts.keys();
+        paths.filter_map(|path| path.strip_prefix(&root)).collect()
+    }
+
+    fn artifact_root(&self, run: &RunId) -> Option<String> {
+        let info = &self.runs.iter().find(|r| r.info.run_id == *run)?.info;
+        match ArtifactLocation::parse(&info.artifact_uri).ok()? {
+            ArtifactLocation::Proxied { path, .. } => Some(path),
+            ArtifactLocation::Local(_) => None,
+        }
+    }
+
+    fn experiment(&mut self, id: &ExperimentId) -> Result<&mut Experiment, GetError> {
+        self.experiments
+            .iter_mut()
+            .find(|experiment| experiment.experiment_id == *id)
+            .ok_or_else(|| GetError::DoesNotExist(id.as_ref().to_string()))
+    }
+
+    fn run(&mut self, id: &RunId) -> Result<&mut StoredRun, GetError> {
+        self.runs
+            .iter_mut()
+            .find(|run| run.info.run_id == *id)
+            .ok_or_else(|| GetError::DoesNotExist(id.as_ref().to_string()))
+    }
+
+    /// The run, if it exists and was not deleted, as required for logging to it.
+    fn active_run(&mut self, id: &RunId) -> Result<&mut StoredRun, StorageError> {
+        let run = self.run(id)?;
+        if run.info.lifecycle_stage != ACTIVE {
+            bail!("the run {} is deleted", id.as_ref());
+        }
+        Ok(run)
+    }
+}
+
+impl Client for Memory {
+    fn create_experiment(&mut self, name: &str) -> Result<ExperimentId, CreateError> {
+        if name.is_empty() {
+            return Err(anyhow!("the experiment name must not be empty").into());
+        }
+        if self.experiments.iter().any(|e| e.name == name) {
+            return Err(CreateError::AlreadyExists(name.to_string()));
+        }
+        let next = self
+            .experiments
+            .iter()
+            .filter_map(|e| e.experiment_id.as_ref().parse::<u64>().ok());
+        let id = ExperimentId::from((next.max().unwrap_or(0) + 1).to_string());
+        self.experiments.push(new_experiment(id.clone(), name));
+        Ok(id)
+    }
+
+    fn list_experiments(&mut self, view_type: ViewType) -> Result<Vec<Experiment>, StorageError> {
+        let experiments = self.experiments.iter();
+        let experiments = experiments.filter(|e| in_view(&e.lifecycle_stage, view_type));
+        Ok(experiments.cloned().collect())
+    }
+
+    fn get_experiment(&mut self, id: &ExperimentId) -> Result<Experiment, GetError> {
+        self.experiment(id).map(|experiment| experiment.clone())
+    }
+
+    fn get_experiment_by_name(&mut self, name: &str) -> Result<Experiment, GetError> {
+        let experiment = self.experiments.iter().find(|e| e.name == name);
+        experiment
+            .cloned()
+            .ok_or_else(|| GetError::DoesNotExist(name.to_string()))
+    }
+
+    fn delete_experiment(&mut self, id: &ExperimentId) -> Result<(), DeleteError> {
+        let experiment = self.experiment(id)?;
+        experiment.lifecycle_stage = DELETED.to_string();
+        experiment.last_update_time = Some(crate::timestamp());
+        Ok(())
+    }
+
+    fn restore_experiment(&mut self, id: &ExperimentId) -> Result<(), UpdateError> {
+        let experiment = self.experiment(id)?;
+        experiment.lifecycle_stage = ACTIVE.to_string();
+        experiment.last_update_time = Some(crate::timestamp());
+        Ok(())
+    }
+
+    fn update_experiment(
+        &mut self,
+        id: &ExperimentId,
+        new_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        if let Some(name) = new_name {
+            let taken = self
+                .experiments
+                .iter()
+                .any(|e| e.name == name && e.experiment_id != *id);
+            if taken {
+                return Err(CreateError::AlreadyExists(name.to_string()).into());
+            }
+            let experiment = self.experiment(id)?;
+            experiment.name = name.to_string();
+            experiment.last_update_time = Some(crate::timestamp());
+        }
+        Ok(())
+    }
+
+    fn create_run(
+        &mut self,
+        experiment: &ExperimentId,
+        start_time: i64,
+        tags: &[RunTag],
+    ) -> Result<Run, StorageError> {
+        let artifact_location = {
+            let experiment = self.experiment(experiment)?;
+            if experiment.lifecycle_stage != ACTIVE {
+                bail!(
+                    "the experiment {} is deleted",
+                    experiment.experiment_id.as_ref()
+                );
+            }
+            experiment.artifact_location.clone()
+        };
+        self.created_runs += 1;
+        let id = format!(
+            "{:016x}{:016x}",
+            mix(self.created_runs),
+            mix(!self.created_runs)
+        );
+        let user = tags.iter().find(|tag| tag.key == crate::api::tags::USER);
+        #[allow(deprecated)]
+        let info = RunInfo {
+            run_id: RunId::from(id.as_str()),
+            run_uuid: id.clone(),
+            experiment_id: experiment.clone(),
+            user_id: user.map(|tag| tag.value.clone()).unwrap_or_default(),
+            status: RunStatus::Running,
+            start_time,
+            end_time: None,
+            artifact_uri: format!("{}/{}/artifacts", artifact_location, id),
+            lifecycle_stage: ACTIVE.to_string(),
+        };
+        let run = StoredRun {
+            info,
+            params: Vec::new(),
+            tags: tags.to_vec(),
+            metrics: Vec::new(),
+        };
+        self.runs.push(run);
+        Ok(self.runs.last().unwrap().to_run())
+    }
+
+    fn delete_run(&mut self, id: &RunId) -> Result<(), DeleteError> {
+        self.run(id)?.info.lifecycle_stage = DELETED.to_string();
+        Ok(())
+    }
+
+    fn restore_run(&mut self, id: &RunId) -> Result<(), UpdateError> {
+        self.run(id)?.info.lifecycle_stage = ACTIVE.to_string();
+        Ok(())
+    }
+
+    fn get_run(&mut self, id: &RunId) -> Result<Run, GetError> {
+        self.run(id).map(|run| run.to_run())
+    }
+
+    fn update_run(
+        &mut self,
+        id: &RunId,
+        status: RunStatus,
+        end_time: Option<i64>,
+    ) -> Result<RunInfo, UpdateError> {
+        let info = &mut self.run(id)?.info;
+        info.status = status;
+        info.end_time = end_time.or(info.end_time);
+        Ok(info.clone())
+    }
+
+    fn search_runs(&mut self, request: &SearchRunsRequest) -> Result<Search, StorageError> {
+        filter::search(self.runs.iter().map(StoredRun::to_run), request)
+    }
+
+    fn list_run_infos(&mut self, request: &ListRunsRequest) -> Result<RunList, StorageError> {
+        let mut search = SearchRunsRequest::new(std::slice::from_ref(&request.experiment_id))
+            .view_type(request.run_view_type)
+            .max_results(request.max_results)
+            .page_token(request.page_token.clone());
+        search.order_by = request.order_by.clone();
+        let search = self.search_runs(&search)?;
+        Ok(RunList {
+            runs: search.runs.into_iter().map(|run| run.info).collect(),
+            page_token: search.next_page_token,
+        })
+    }
+
+    fn get_metric_history(
+        &mut self,
+        run: &RunId,
+        metric: &str,
+    ) -> Result<Vec<Metric<'static>>, GetError> {
+        let metrics = self.run(run)?.metrics.iter();
+        Ok(metrics.filter(|m| m.key == metric).cloned().collect())
+    }
+
+    fn log_param(&mut self, run: &RunId, key: &str, value: &str) -> Result<(), StorageError> {
+        let param = Param {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        Ok(self.log_batch(run, &[], &[param], &[])?)
+    }
+
+    fn log_metric(
+        &mut self,
+        run: &RunId,
+        key: &str,
+        value: f64,
+        timestamp: i64,
+        step: i64,
+    ) -> Result<(), StorageError> {
+        let metric = Metric {
+            key: key.into(),
+            value,
+            timestamp,
+            step,
+        };
+        Ok(self.log_batch(run, &[metric], &[], &[])?)
+    }
+
+    fn log_batch(
+        &mut self,
+        run: &RunId,
+        metrics: &[Metric],
+        params: &[Param],
+        tags: &[RunTag],
+    ) -> Result<(), BatchError> {
+        BatchError::check(metrics, params, tags)?;
+        let run = self.active_run(run)?;
+        for param in params {
+            let logged = run.params.iter().find(|p| p.key == param.key);
+            match logged {
+                Some(logged) if logged.value != param.value => {
+                    return Err(BatchError::ConflictingParam {
+                        key: param.key.clone(),
+                        first: logged.value.clone(),
+                        second: param.value.clone(),
+                    })
+                }
+                Some(_) => {}
+                None => run.params.push(param.clone()),
+            }
+        }
+        for tag in tags {
+            match run.tags.iter_mut().find(|t| t.key == tag.key) {
+                Some(logged) => logged.value = tag.value.clone(),
+                None => run.tags.push(tag.clone()),
+            }
+        }
+        let metrics = metrics.iter().cloned().map(Metric::into_owned);
+        run.metrics.extend(metrics);
+        Ok(())
+    }
+
+    fn upload_artifact(
+        &mut self,
+        run: &RunInfo,
+        path: &str,
+        contents: &[u8],
+    ) -> Result<(), StorageError> {
+        match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => artifact::write_local(&root, path, contents),
+            ArtifactLocation::Proxied { path: root, .. } => {
+                artifact::validate_path(path)?;
+                let key = format!("{}/{}", root, path);
+                self.artifacts.insert(key, contents.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    fn list_artifacts(&mut self, run: &RunInfo, path: &str) -> Result<Vec<FileInfo>, StorageError> {
+        let root = match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => return artifact::list_local(&root, path),
+            ArtifactLocation::Proxied { path: root, .. } => root,
+        };
+        let path = path.trim_matches('/');
+        let prefix = format!("{}/", artifact::join(&root, path));
+        let mut files: Vec<FileInfo> = Vec::new();
+        for (key, contents) in self.artifacts.range(prefix.clone()..) {
+            let name = match key.strip_prefix(&prefix) {
+                Some(name) => name,
+                None => break,
+            };
+            let file = match name.split_once('/') {
+                Some((dir, _)) => FileInfo {
+                    path: artifact::join(path, dir),
+                    is_dir: true,
+                    file_size: None,
+                },
+                None => FileInfo {
+                    path: artifact::join(path, name),
+                    is_dir: false,
+                    file_size: Some(contents.len() as u64),
+                },
+            };
+            if files.last() != Some(&file) {
+                files.push(file);
+            }
+        }
+        Ok(files)
+    }
+
+    fn download_artifact(&mut self, run: &RunInfo, path: &str) -> Result<Vec<u8>, StorageError> {
+        let root = match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => return artifact::read_local(&root, path),
+            ArtifactLocation::Proxied { path: root, .. } => root,
+        };
+        let artifact = self.artifacts.get(&format!("{}/{}", root, path));
+        let artifact = artifact.ok_or_else(|| GetError::DoesNotExist(path.to_string()))?;
+        Ok(artifact.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Memory;
+    use crate::{
+        api::{error::BatchError, run::Param, search::SearchRunsRequest},
+        Client, ExperimentId,
+    };
+
+    #[test]
+    fn search_filters_orders_and_pages() {
+        let mut memory = Memory::new();
+        let experiment = memory.create_experiment("exp").unwrap();
+        assert_eq!(experiment, ExperimentId::from("1"));
+        for (i, loss) in [0.3, 0.1, 0.2].iter().enumerate() {
+            let run = memory.create_run(&experiment, i as i64, &[]).unwrap();
+            let id = &run.info.run_id;
+            memory.log_metric(id, "loss", 1.0, 0, 0).unwrap();
+            memory.log_metric(id, "loss", *loss, 0, 1).unwrap();
+            memory.log_param(id, "index", &i.to_string()).unwrap();
+        }
+
+        let request = SearchRunsRequest::new(std::slice::from_ref(&experiment))
+            .filter("metrics.loss < 0.25")
+            .order_by("metrics.loss ASC")
+            .max_results(1);
+        let first = memory.search_runs(&request).unwrap();
+        let index = |search: &crate::api::search::Search| {
+            let params = search.runs[0].data.params.clone().unwrap();
+            params[0].value.clone()
+        };
+        assert_eq!(index(&first), "1");
+        let request = request.page_token(first.next_page_token);
+        let second = memory.search_runs(&request).unwrap();
+        assert_eq!(index(&second), "2");
+        assert!(second.next_page_token.as_ref().is_empty());
+
+        let run = first.runs[0].info.run_id.clone();
+        assert!(memory.log_param(&run, "index", "1").is_ok());
+        let conflict = memory.log_batch(
+            &run,
+            &[],
+            &[Param {
+                key: "index".into(),
+                value: "2".into(),
+            }],
+            &[],
+        );
+        assert!(matches!(conflict, Err(BatchError::ConflictingParam { .. })));
+        assert_eq!(memory.get_metric_history(&run, "loss").unwrap().len(), 2);
+
+        let path = std::env::temp_dir().join(format!("mlflow-memory-{}.json", std::process::id()));
+        memory.save(&path).unwrap();
+        let mut loaded = Memory::load(&path).unwrap();
+        assert_eq!(loaded.get_metric_history(&run, "loss").unwrap().len(), 2);
+        assert_ne!(
+            loaded.create_run(&experiment, 3, &[]).unwrap().info.run_id,
+            run
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,385 @@
+//! A [`Client`] decorator which writes to several backends, e.g. while migrating between servers.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use crate::{
+    api::{
+        artifact::FileInfo,
+        client::{Client, ViewType},
+        error::{BatchError, CreateError, DeleteError, GetError, StorageError, UpdateError},
+        experiment::Experiment,
+        run::{Metric, Param, Run, RunInfo, RunStatus, RunTag},
+        search::{ListRunsRequest, RunList, Search, SearchRunsRequest},
+    },
+    ExperimentId, RunId,
+};
+
+/// How a [`Mirror`] handles writes which fail on some of its clients.
+///
+/// A write which fails on the primary is never sent to the mirrors,
+/// because their ids are mapped from the ones assigned by the primary.
+/// So when a mirror fails, the write is already committed on the primary:
+/// such errors have a [`MirrorFailed`] context, which tells them apart from failures of the primary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Stop at the first mirror which fails and return its error,
+    /// keeping the write on the primary and the mirrors before it.
+    FailFast,
+    /// Only fail if the primary fails. Errors of the mirrors are kept in [`Mirror::errors`].
+    PrimaryOnly,
+    /// Write to all mirrors, then return the first error if any of them failed.
+    BestEffort,
+}
+
+/// The context of errors of a mirror, after the write succeeded on the primary.
+///
+/// ```
+/// # use mlflow::backend::mirror::MirrorFailed;
+/// # fn committed(error: &anyhow::Error) -> bool {
+/// error.downcast_ref::<MirrorFailed>().is_some()
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("failed to write to mirror {mirror}, after the write succeeded on the primary")]
+pub struct MirrorFailed {
+    /// The index of the mirror, in the order they were added.
+    pub mirror: usize,
+}
+
+/// Writes to a primary client and any number of mirrors, and reads from the primary.
+///
+/// Experiments and runs created through the mirror are created on every client.
+/// Their ids are mapped from the ids of the primary to the ids of each mirror.
+/// Experiments which already existed are looked up on the mirrors by name and created if missing.
+/// Writes to runs which were not created through the mirror only go to the primary.
+pub struct Mirror {
+    primary: Box<dyn Client + Send>,
+    mirrors: Vec<Target>,
+    policy: FailurePolicy,
+    errors: Vec<StorageError>,
+}
+
+struct Target {
+    client: Box<dyn Client + Send>,
+    experiments: HashMap<ExperimentId, ExperimentId>,
+    runs: HashMap<RunId, RunInfo>,
+}
+
+impl Target {
+    /// The id of the experiment on this mirror, which is looked up by name or created if necessary.
+    fn experiment(
+        &mut self,
+        primary: &mut dyn Client,
+        id: &ExperimentId,
+    ) -> Result<ExperimentId, StorageError> {
+        if let Some(mapped) = self.experiments.get(id) {
+            return Ok(mapped.clone());
+        }
+        let name = primary.get_experiment(id)?.name;
+        let mapped = match self.client.get_experiment_by_name(&name) {
+            Ok(experiment) => experiment.experiment_id,
+            Err(GetError::DoesNotExist(_)) => self.client.create_experiment(&name)?,
+            Err(error) => return Err(error.into()),
+        };
+        self.experiments.insert(id.clone(), mapped.clone());
+        Ok(mapped)
+    }
+}
+
+impl Mirror {
+    /// Defaults to [`FailurePolicy::FailFast`].
+    pub fn new(primary: impl Client + Send + 'static) -> Self {
+        Mirror {
+            primary: Box::new(primary),
+            mirrors: Vec::new(),
+            policy: FailurePolicy::FailFast,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Adds a client which receives a copy of all writes.
+    pub fn with(mut self, mirror: impl Client + Send + 'static) -> Self {
+        self.mirrors.push(Target {
+            client: Box::new(mirror),
+            experiments: HashMap::new(),
+            runs: HashMap::new(),
+        });
+        self
+    }
+
+    pub fn policy(mut self, policy: FailurePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The errors of the mirrors which were ignored due to [`FailurePolicy::PrimaryOnly`].
+    pub fn errors(&self) -> &[StorageError] {
+        &self.errors
+    }
+
+    /// Removes and returns the ignored errors.
+    pub fn take_errors(&mut self) -> Vec<StorageError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// The id of an experiment of the primary on the mirror with the index, in the order they were added.
+    pub fn experiment_id(&self, mirror: usize, id: &ExperimentId) -> Option<&ExperimentId> {
+        self.mirrors.get(mirror)?.experiments.get(id)
+    }
+
+    /// The id of a run of the primary on the mirror with the index, in the order they were added.
+    pub fn run_id(&self, mirror: usize, id: &RunId) -> Option<&RunId> {
+        let info = self.mirrors.get(mirror)?.runs.get(id)?;
+        Some(&info.run_id)
+    }
+
+    /// Applies the write to all mirrors according to the policy, after it succeeded on the primary.
+    fn mirror<F>(&mut self, mut write: F) -> Result<(), StorageError>
+    where
+        F: FnMut(&mut dyn Client, &mut Target) -> Result<(), StorageError>,
+    {
+        let mut first_error = None;
+        for (index, target) in self.mirrors.iter_mut().enumerate() {
+            let result =
+                write(self.primary.as_mut(), target).context(MirrorFailed { mirror: index });
+            if let Err(error) = result {
+                match self.policy {
+                    FailurePolicy::FailFast => return Err(error),
+                    FailurePolicy::PrimaryOnly => self.errors.push(error),
+                    FailurePolicy::BestEffort => {
+                        first_error.get_or_insert(error);
+                    }
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Applies a write to a run on all mirrors which have the run.
+    fn mirror_run<F>(&mut self, id: &RunId, mut write: F) -> Result<(), StorageError>
+    where
+        F: FnMut(&mut dyn Client, &mut RunInfo) -> Result<(), StorageError>,
+    {
+        self.mirror(|_, target| match target.runs.get_mut(id) {
+            Some(info) => write(target.client.as_mut(), info),
+            None => Ok(()),
+        })
+    }
+}
+
+impl Client for Mirror {
+    fn create_experiment(&mut self, name: &str) -> Result<ExperimentId, CreateError> {
+        let id = self.primary.create_experiment(name)?;
+        self.mirror(|_, target| {
+            let mapped = target.client.create_experiment(name)?;
+            target.experiments.insert(id.clone(), mapped);
+            Ok(())
+        })?;
+        Ok(id)
+    }
+
+    fn list_experiments(&mut self, view_type: ViewType) -> Result<Vec<Experiment>, StorageError> {
+        self.primary.list_experiments(view_type)
+    }
+
+    fn get_experiment(&mut self, id: &ExperimentId) -> Result<Experiment, GetError> {
+        self.primary.get_experiment(id)
+    }
+
+    fn get_experiment_by_name(&mut self, name: &str) -> Result<Experiment, GetError> {
+        self.primary.get_experiment_by_name(name)
+    }
+
+    fn delete_experiment(&mut self, id: &ExperimentId) -> Result<(), DeleteError> {
+        self.primary.delete_experiment(id)?;
+        self.mirror(|_, target| match target.experiments.get(id) {
+            Some(mapped) => Ok(target.client.delete_experiment(mapped)?),
+            None => Ok(()),
+        })?;
+        Ok(())
+    }
+
+    fn restore_experiment(&mut self, id: &ExperimentId) -> Result<(), UpdateError> {
+        self.primary.restore_experiment(id)?;
+        self.mirror(|_, target| match target.experiments.get(id) {
+            Some(mapped) => Ok(target.client.restore_experiment(mapped)?),
+            None => Ok(()),
+        })?;
+        Ok(())
+    }
+
+    fn update_experiment(
+        &mut self,
+        id: &ExperimentId,
+        new_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        self.primary.update_experiment(id, new_name)?;
+        self.mirror(|_, target| match target.experiments.get(id) {
+            Some(mapped) => target.client.update_experiment(mapped, new_name),
+            None => Ok(()),
+        })
+    }
+
+    fn create_run(
+        &mut self,
+        experiment: &ExperimentId,
+        start_time: i64,
+        tags: &[RunTag],
+    ) -> Result<Run, StorageError> {
+        let run = self.primary.create_run(experiment, start_time, tags)?;
+        let id = &run.info.run_id;
+        self.mirror(|primary, target| {
+            let experiment = target.experiment(primary, experiment)?;
+            let mirrored = target.client.create_run(&experiment, start_time, tags)?;
+            target.runs.insert(id.clone(), mirrored.info);
+            Ok(())
+        })?;
+        Ok(run)
+    }
+
+    fn delete_run(&mut self, id: &RunId) -> Result<(), DeleteError> {
+        self.primary.delete_run(id)?;
+        self.mirror_run(id, |client, info| Ok(client.delete_run(&info.run_id)?))?;
+        Ok(())
+    }
+
+    fn restore_run(&mut self, id: &RunId) -> Result<(), UpdateError> {
+        self.primary.restore_run(id)?;
+        self.mirror_run(id, |client, info| Ok(client.restore_run(&info.run_id)?))?;
+        Ok(())
+    }
+
+    fn get_run(&mut self, id: &RunId) -> Result<Run, GetError> {
+        self.primary.get_run(id)
+    }
+
+    fn update_run(
+        &mut self,
+        id: &RunId,
+        status: RunStatus,
+        end_time: Option<i64>,
+    ) -> Result<RunInfo, UpdateError> {
+        let info = self.primary.update_run(id, status, end_time)?;
+        self.mirror_run(id, |client, mirrored| {
+            *mirrored = client.update_run(&mirrored.run_id, status, end_time)?;
+            Ok(())
+        })?;
+        Ok(info)
+    }
+
+    fn search_runs(&mut self, request: &SearchRunsRequest) -> Result<Search, StorageError> {
+        self.primary.search_runs(request)
+    }
+
+    fn list_run_infos(&mut self, request: &ListRunsRequest) -> Result<RunList, StorageError> {
+        self.primary.list_run_infos(request)
+    }
+
+    fn get_metric_history(
+        &mut self,
+        run: &RunId,
+        metric: &str,
+    ) -> Result<Vec<Metric<'static>>, GetError> {
+        self.primary.get_metric_history(run, metric)
+    }
+
+    fn log_param(&mut self, run: &RunId, key: &str, value: &str) -> Result<(), StorageError> {
+        self.primary.log_param(run, key, value)?;
+        self.mirror_run(run, |client, info| {
+            client.log_param(&info.run_id, key, value)
+        })
+    }
+
+    fn log_metric(
+        &mut self,
+        run: &RunId,
+        key: &str,
+        value: f64,
+        timestamp: i64,
+        step: i64,
+    ) -> Result<(), StorageError> {
+        self.primary.log_metric(run, key, value, timestamp, step)?;
+        self.mirror_run(run, |client, info| {
+            client.log_metric(&info.run_id, key, value, timestamp, step)
+        })
+    }
+
+    fn log_batch(
+        &mut self,
+        run: &RunId,
+        metrics: &[Metric],
+        params: &[Param],
+        tags: &[RunTag],
+    ) -> Result<(), BatchError> {
+        self.primary.log_batch(run, metrics, params, tags)?;
+        self.mirror_run(run, |client, info| {
+            Ok(client.log_batch(&info.run_id, metrics, params, tags)?)
+        })?;
+        Ok(())
+    }
+
+    fn upload_artifact(
+        &mut self,
+        run: &RunInfo,
+        path: &str,
+        contents: &[u8],
+    ) -> Result<(), StorageError> {
+        self.primary.upload_artifact(run, path, contents)?;
+        self.mirror_run(&run.run_id, |client, info| {
+            client.upload_artifact(info, path, contents)
+        })
+    }
+
+    fn list_artifacts(&mut self, run: &RunInfo, path: &str) -> Result<Vec<FileInfo>, StorageError> {
+        self.primary.list_artifacts(run, path)
+    }
+
+    fn download_artifact(&mut self, run: &RunInfo, path: &str) -> Result<Vec<u8>, StorageError> {
+        self.primary.download_artifact(run, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FailurePolicy, Mirror, MirrorFailed};
+    use crate::{
+        api::{error::CreateError, run::RunStatus},
+        backend::memory::Memory,
+        testing::{Fault, MockServer},
+        Client,
+    };
+
+    #[test]
+    fn mirror_maps_ids_and_applies_policy() {
+        let offline = MockServer::start();
+        offline.inject(Fault::any());
+        // The mirror already has a run, so the ids of new runs differ from the primary ones.
+        let mut mirrored = Memory::new();
+        mirrored.create_run(&"0".into(), 0, &[]).unwrap();
+        let mut mirror = Mirror::new(Memory::new())
+            .with(mirrored)
+            .with(offline.client())
+            .policy(FailurePolicy::PrimaryOnly);
+        let experiment = mirror.create_experiment("exp").unwrap();
+        let run = mirror.create_run(&experiment, 0, &[]).unwrap().info.run_id;
+        mirror.log_param(&run, "lr", "0.1").unwrap();
+        mirror
+            .update_run(&run, RunStatus::Finished, Some(1))
+            .unwrap();
+        assert!(mirror.run_id(0, &run).is_some_and(|id| *id != run));
+        assert_eq!(mirror.run_id(1, &run), None);
+        assert_eq!(mirror.errors().len(), 2);
+
+        let mut mirror = mirror.policy(FailurePolicy::FailFast);
+        match mirror.create_experiment("other") {
+            Err(CreateError::Storage(error)) => {
+                assert_eq!(error.downcast_ref(), Some(&MirrorFailed { mirror: 1 }))
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(mirror.get_experiment_by_name("other").is_ok());
+        assert!(mirror.log_param(&run, "seed", "1").is_ok());
+    }
+}
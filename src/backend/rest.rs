@@ -1,20 +1,18 @@
+use super::cassette::{Body, Transport};
 use crate::{
     api::{
+        artifact::{self, ArtifactLocation, FileInfo},
         client::{Client, ViewType},
         error::{BatchError, CreateError, DeleteError, GetError, StorageError, UpdateError},
         experiment::Experiment,
-        limits,
         run::{Metric, Param, Run, RunData, RunInfo, RunStatus, RunTag},
-        search::{PageToken, RunList, Search},
+        search::{ListRunsRequest, PageToken, RunList, Search, SearchRunsRequest},
     },
     ExperimentId, RunId,
 };
 use anyhow::{Context, Error};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{
-    fmt::Display,
-    io::{Read, Write},
-};
+use std::{fmt::Display, path::PathBuf};
 
 #[derive(Deserialize)]
 struct RestErrorResponse {
@@ -46,7 +44,18 @@ impl From<&str> for RestErrorCode {
             "RESOURCE_ALREADY_EXISTS" => RestErrorCode::ResourceAlreadyExists,
             "RESOURCE_DOES_NOT_EXIST" => RestErrorCode::ResourceDoesNotExist,
             "INVALID_PARAMETER_VALUE" => RestErrorCode::InvalidParameterValue,
-            _ => return RestErrorCode::Unknown(value.to_owned()),
+            _ => RestErrorCode::Unknown(value.to_owned()),
+        }
+    }
+}
+impl RestErrorCode {
+    /// The code as sent by the server, e.g. `RESOURCE_DOES_NOT_EXIST`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            RestErrorCode::ResourceAlreadyExists => "RESOURCE_ALREADY_EXISTS",
+            RestErrorCode::ResourceDoesNotExist => "RESOURCE_DOES_NOT_EXIST",
+            RestErrorCode::InvalidParameterValue => "INVALID_PARAMETER_VALUE",
+            RestErrorCode::Unknown(code) => code,
         }
     }
 }
@@ -62,25 +71,21 @@ enum RestMethod {
     Post,
 }
 impl RestMethod {
-    fn handler(&self) -> fn (&str) -> ureq::Request {
+    fn as_str(&self) -> &'static str {
         match self {
-            Self::Get => ureq::get,
-            Self::Post => ureq::post,
+            Self::Get => "GET",
+            Self::Post => "POST",
         }
     }
 }
 
-
+#[derive(Debug, Clone)]
 pub struct Server {
     api_url: String,
+    transport: Transport,
 }
 
-fn parse_error(response: ureq::Response) -> RestError {
-    let status = response.status();
-    let body = response
-        .into_string()
-        .unwrap_or_else(|_| "Could not turn error body into String.".to_string());
-
+fn parse_error(status: u16, body: String) -> RestError {
     let response = serde_json::from_str::<RestErrorResponse>(&body).ok();
     if let Some(response) = response {
         RestError::Known {
@@ -97,6 +102,62 @@ impl Server {
     pub fn new(api_url: impl Into<String>) -> Self {
         Server {
             api_url: api_url.into(),
+            transport: Transport::Http,
+        }
+    }
+
+    /// Records all requests and responses to the [cassette][super::cassette] at `path`,
+    /// which is overwritten.
+    pub fn recording(mut self, path: impl Into<PathBuf>) -> Self {
+        self.transport = Transport::record(path.into());
+        self
+    }
+
+    /// Writes the [cassette][super::cassette] being recorded, which otherwise happens
+    /// when the last clone of this server is dropped. Does nothing unless recording.
+    pub fn save_cassette(&self) -> Result<(), StorageError> {
+        self.transport.save()
+    }
+
+    /// Serves the responses recorded in the [cassette][super::cassette] at `path`
+    /// instead of connecting to a server.
+    pub fn replaying(path: impl Into<PathBuf>) -> Result<Self, StorageError> {
+        Ok(Server {
+            api_url: String::new(),
+            transport: Transport::replay(path.into())?,
+        })
+    }
+
+    /// Connects to the tracking server given by the `MLFLOW_TRACKING_URI` environment variable.
+    ///
+    /// Like for the Python client, the uri is the address of the server, e.g. `http://localhost:5000`.
+    pub fn from_env() -> Result<Self, StorageError> {
+        let uri = std::env::var("MLFLOW_TRACKING_URI")
+            .context("the environment variable MLFLOW_TRACKING_URI is not set")?;
+        Self::from_tracking_uri(&uri)
+    }
+
+    /// Connects to the tracking server at `http://` or `https://` `uri`.
+    pub fn from_tracking_uri(uri: &str) -> Result<Self, StorageError> {
+        if !uri.starts_with("http://") && !uri.starts_with("https://") {
+            anyhow::bail!(
+                "only http and https tracking uris are supported, found {}",
+                uri
+            );
+        }
+        Ok(Server::new(format!("{}/api", uri.trim_end_matches('/'))))
+    }
+
+    /// The api url of the artifact proxy, which is this server unless the authority is given.
+    ///
+    /// Another authority is reached with the scheme of this server.
+    fn artifacts_url(&self, authority: Option<String>) -> String {
+        match authority {
+            Some(authority) => {
+                let scheme = self.api_url.split_once("://").map(|(scheme, _)| scheme);
+                format!("{}://{}/api", scheme.unwrap_or("http"), authority)
+            }
+            None => self.api_url.clone(),
         }
     }
 
@@ -106,23 +167,22 @@ impl Server {
         Hand: FnOnce(RestError) -> Err,
         Err: From<anyhow::Error>,
     {
-        let url = format!("{}/{}", self.api_url, Ep::PATH);
-
-        let http_response = if Ep::METHOD == RestMethod::Get {
+        let method = Ep::METHOD.as_str();
+        let response = if Ep::METHOD == RestMethod::Get {
             let query_str = Ep::write_request_query_string(&request).context("serializing request failed")?;
-            Ep::METHOD.handler()(&url).query_str(&query_str).call()
+            let body = Body::Query(&query_str);
+            self.transport.send(method, &self.api_url, Ep::PATH, body)?
         } else {
             let buffer = Ep::write_request_body_string(&request).context("serializing request failed")?;
-            Ep::METHOD.handler()(&url).send_string(&buffer)
+            let body = Body::Text(&buffer);
+            self.transport.send(method, &self.api_url, Ep::PATH, body)?
         };
 
-        if http_response.error() {
-            let error = parse_error(http_response);
+        if response.status >= 400 {
+            let error = parse_error(response.status, response.text());
             Err(error_handler(error))
         } else {
-            let response_string = http_response
-                .into_string()
-                .context("failed to turn response into string")?;
+            let response_string = response.text();
             let response = Ep::read_response_string(&response_string)
                 .with_context(|| format!("deserializing response failed:\n{}", &response_string))?;
             let value = Ep::extract(response);
@@ -187,6 +247,17 @@ impl Client for Server {
         })
     }
 
+    fn restore_experiment(&mut self, id: &ExperimentId) -> Result<(), UpdateError> {
+        let request = RestoreExperiment { experiment_id: id };
+        self.execute(request, |error| match error {
+            RestError::Known {
+                code: RestErrorCode::ResourceDoesNotExist,
+                ..
+            } => GetError::DoesNotExist(id.as_ref().to_string()),
+            _ => GetError::Storage(error.into()),
+        })
+    }
+
     fn update_experiment(
         &mut self,
         id: &ExperimentId,
@@ -224,6 +295,17 @@ impl Client for Server {
         })
     }
 
+    fn restore_run(&mut self, id: &RunId) -> Result<(), UpdateError> {
+        let request = RestoreRun { run_id: id };
+        self.execute(request, |error| match error {
+            RestError::Known {
+                code: RestErrorCode::ResourceDoesNotExist,
+                ..
+            } => GetError::DoesNotExist(id.as_ref().to_string()),
+            _ => GetError::Storage(error.into()),
+        })
+    }
+
     fn get_run(&mut self, id: &RunId) -> Result<Run, GetError> {
         let request = GetRun { run_id: id };
         self.execute(request, |error| match error {
@@ -239,7 +321,7 @@ impl Client for Server {
         &mut self,
         id: &RunId,
         status: RunStatus,
-        end_time: i64,
+        end_time: Option<i64>,
     ) -> Result<RunInfo, UpdateError> {
         let request = UpdateRun {
             run_id: id,
@@ -255,46 +337,35 @@ impl Client for Server {
         })
     }
 
-    fn search_runs(
-        &mut self,
-        experiment_ids: &[&ExperimentId],
-        filter: &str,
-        run_view_type: ViewType,
-        max_results: i32,
-        order_by: Option<&str>,
-        page_token: Option<&str>,
-    ) -> Result<Search, StorageError> {
+    fn search_runs(&mut self, request: &SearchRunsRequest) -> Result<Search, StorageError> {
         let request = SearchRuns {
-            experiment_ids,
-            filter,
-            run_view_type,
-            max_results,
-            order_by,
-            page_token,
+            experiment_ids: &request.experiment_ids,
+            filter: &request.filter,
+            run_view_type: request.run_view_type,
+            max_results: request.max_results,
+            order_by: &request.order_by,
+            page_token: request.page_token.as_ref(),
         };
         self.execute(request, StorageError::from)
     }
 
-    fn list_run_infos(
-        &mut self,
-        experiment: &ExperimentId,
-        run_view_type: ViewType,
-        max_results: i32,
-        order_by: Option<&str>,
-        page_token: Option<&str>,
-    ) -> Result<RunList, StorageError> {
+    fn list_run_infos(&mut self, request: &ListRunsRequest) -> Result<RunList, StorageError> {
         let request = ListRunInfos {
-            experiment_ids: &[experiment],
+            experiment_ids: std::slice::from_ref(&request.experiment_id),
             filter: "",
-            run_view_type,
-            max_results,
-            order_by,
-            page_token,
+            run_view_type: request.run_view_type,
+            max_results: request.max_results,
+            order_by: &request.order_by,
+            page_token: request.page_token.as_ref(),
         };
         self.execute(request, StorageError::from)
     }
 
-    fn get_metric_history(&mut self, run: &RunId, metric: &str) -> Result<Vec<Metric>, GetError> {
+    fn get_metric_history(
+        &mut self,
+        run: &RunId,
+        metric: &str,
+    ) -> Result<Vec<Metric<'static>>, GetError> {
         let request = GetHistory {
             run_id: run,
             metric_key: metric,
@@ -338,19 +409,7 @@ impl Client for Server {
         params: &[Param],
         tags: &[RunTag],
     ) -> Result<(), BatchError> {
-        if metrics.len() > limits::BATCH_METRICS {
-            return Err(BatchError::ToManyMetrics(metrics.len()));
-        }
-        if params.len() > limits::BATCH_PARAMS {
-            return Err(BatchError::ToManyParams(params.len()));
-        }
-        if tags.len() > limits::BATCH_TAGS {
-            return Err(BatchError::ToManyTags(tags.len()));
-        }
-        let total_len = metrics.len() + params.len() + tags.len();
-        if total_len > limits::BATCH_TOTAL {
-            return Err(BatchError::ToManyItems(total_len));
-        }
+        BatchError::check(metrics, params, tags)?;
         let request = LogBatch {
             run_id: run,
             metrics,
@@ -359,6 +418,92 @@ impl Client for Server {
         };
         self.execute(request, |err| BatchError::Storage(err.into()))
     }
+
+    fn upload_artifact(
+        &mut self,
+        run: &RunInfo,
+        path: &str,
+        contents: &[u8],
+    ) -> Result<(), StorageError> {
+        let (authority, root) = match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => return artifact::write_local(&root, path, contents),
+            ArtifactLocation::Proxied { authority, path } => (authority, path),
+        };
+        artifact::validate_path(path)?;
+        let api_url = self.artifacts_url(authority);
+        let path = format!(
+            "2.0/mlflow-artifacts/artifacts/{}",
+            encode_path(&artifact::join(&root, path))
+        );
+        let response = self
+            .transport
+            .send("PUT", &api_url, &path, Body::Bytes(contents))?;
+        if response.status >= 400 {
+            return Err(parse_error(response.status, response.text()).into());
+        }
+        Ok(())
+    }
+
+    fn list_artifacts(&mut self, run: &RunInfo, path: &str) -> Result<Vec<FileInfo>, StorageError> {
+        let (api_url, root) = match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => return artifact::list_local(&root, path),
+            ArtifactLocation::Proxied { authority, path } => (self.artifacts_url(authority), path),
+        };
+        let path = path.trim_matches('/');
+        let query = serde_qs::to_string(&ListArtifacts {
+            path: &artifact::join(&root, path),
+        })?;
+        let response = self.transport.send(
+            "GET",
+            &api_url,
+            "2.0/mlflow-artifacts/artifacts",
+            Body::Query(&query),
+        )?;
+        if response.status >= 400 {
+            return Err(parse_error(response.status, response.text()).into());
+        }
+        let listed = serde_json::from_slice::<ListArtifactsResponse>(&response.body)
+            .with_context(|| format!("deserializing response failed:\n{}", response.text()))?;
+        let files = listed.files.into_iter().map(|file| FileInfo {
+            // The server only returns the names of the files.
+            path: artifact::join(path, &file.path),
+            ..file
+        });
+        Ok(files.collect())
+    }
+
+    fn download_artifact(&mut self, run: &RunInfo, path: &str) -> Result<Vec<u8>, StorageError> {
+        let (api_url, root) = match ArtifactLocation::parse(&run.artifact_uri)? {
+            ArtifactLocation::Local(root) => return artifact::read_local(&root, path),
+            ArtifactLocation::Proxied { authority, path } => (self.artifacts_url(authority), path),
+        };
+        artifact::validate_path(path)?;
+        let path = format!(
+            "2.0/mlflow-artifacts/artifacts/{}",
+            encode_path(&artifact::join(&root, path))
+        );
+        let response = self
+            .transport
+            .send("GET", &api_url, &path, Body::Query(""))?;
+        if response.status >= 400 {
+            return Err(parse_error(response.status, response.text()).into());
+        }
+        Ok(response.body)
+    }
+}
+
+/// Percent-encodes each segment of the path, keeping the slashes between them.
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
 }
 
 trait Endpoint {
@@ -375,8 +520,6 @@ trait VoidEndpoint {
     const METHOD: RestMethod;
 }
 trait EndpointExt: Endpoint {
-    fn write_request(request: &Self, writer: impl Write) -> Result<(), Error>;
-    fn read_response(reader: impl Read) -> Result<Self::Response, Error>;
     fn read_response_string(response: &str) -> Result<Self::Response, Error>;
     fn write_request_body_string(request: &Self) -> Result<String, Error>;
     fn write_request_query_string(request: &Self) -> Result<String, Error>;
@@ -391,9 +534,7 @@ where
     type Response = VoidResponse;
     type Value = ();
 
-    fn extract(_response: Self::Response) -> Self::Value {
-        ()
-    }
+    fn extract(_response: Self::Response) -> Self::Value {}
 }
 impl<P, R, V> EndpointExt for P
 where
@@ -401,16 +542,6 @@ where
     R: DeserializeOwned,
     P: Endpoint<Response = R, Value = V>,
 {
-    fn write_request(request: &Self, writer: impl Write) -> Result<(), Error> {
-        serde_json::to_writer(writer, &request)?;
-        Ok(())
-    }
-
-    fn read_response(reader: impl Read) -> Result<Self::Response, Error> {
-        let response = serde_json::from_reader::<_, R>(reader)?;
-        Ok(response)
-    }
-
     fn read_response_string(response: &str) -> Result<Self::Response, Error> {
         let response = serde_json::from_str::<'_, R>(response)?;
         Ok(response)
@@ -483,6 +614,7 @@ struct ListExperiments {
 }
 #[derive(Deserialize)]
 struct ListExperimentsResponse {
+    #[serde(default)]
     experiments: Vec<Experiment>,
 }
 impl Endpoint for ListExperiments {
@@ -520,6 +652,15 @@ impl VoidEndpoint for DeleteExperiment<'_> {
     const METHOD: RestMethod = RestMethod::Post;
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+struct RestoreExperiment<'a> {
+    pub experiment_id: &'a ExperimentId,
+}
+impl VoidEndpoint for RestoreExperiment<'_> {
+    const PATH: &'static str = "2.0/mlflow/experiments/restore";
+    const METHOD: RestMethod = RestMethod::Post;
+}
+
 #[derive(Debug, Clone, Copy, Serialize)]
 struct CreateRun<'a> {
     pub experiment_id: &'a ExperimentId,
@@ -550,12 +691,21 @@ impl VoidEndpoint for DeleteRun<'_> {
     const METHOD: RestMethod = RestMethod::Post;
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+struct RestoreRun<'a> {
+    pub run_id: &'a RunId,
+}
+impl VoidEndpoint for RestoreRun<'_> {
+    const PATH: &'static str = "2.0/mlflow/runs/restore";
+    const METHOD: RestMethod = RestMethod::Post;
+}
+
 #[derive(Debug, Clone, Copy, Serialize)]
 struct GetRun<'a> {
     pub run_id: &'a RunId,
 }
 impl Endpoint for GetRun<'_> {
-    const PATH: &'static str = "";
+    const PATH: &'static str = "2.0/mlflow/runs/get";
     const METHOD: RestMethod = RestMethod::Get;
     type Response = GetRunResponse;
     type Value = Run;
@@ -580,6 +730,7 @@ impl VoidEndpoint for LogParam<'_> {
 struct LogMetric<'a> {
     pub run_id: &'a RunId,
     pub key: &'a str,
+    #[serde(with = "crate::api::non_finite")]
     pub value: f64,
     pub timestamp: i64,
     pub step: i64,
@@ -593,7 +744,8 @@ impl VoidEndpoint for LogMetric<'_> {
 struct UpdateRun<'a> {
     pub run_id: &'a RunId,
     pub status: RunStatus,
-    pub end_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<i64>,
 }
 #[derive(Deserialize)]
 struct UpdateRunResponse {
@@ -624,15 +776,15 @@ impl VoidEndpoint for LogBatch<'_> {
 
 #[derive(Debug, Clone, Copy, Serialize)]
 struct SearchRuns<'a> {
-    pub experiment_ids: &'a [&'a ExperimentId],
+    pub experiment_ids: &'a [ExperimentId],
     pub filter: &'a str,
     pub run_view_type: ViewType,
     pub max_results: i32,
-    pub order_by: Option<&'a str>,
-    pub page_token: Option<&'a str>,
+    pub order_by: &'a [String],
+    pub page_token: Option<&'a PageToken>,
 }
 impl Endpoint for SearchRuns<'_> {
-    const PATH: &'static str = "";
+    const PATH: &'static str = "2.0/mlflow/runs/search";
     const METHOD: RestMethod = RestMethod::Post;
     type Response = Search;
     type Value = Search;
@@ -644,12 +796,12 @@ impl Endpoint for SearchRuns<'_> {
 
 #[derive(Debug, Clone, Copy, Serialize)]
 struct ListRunInfos<'a> {
-    pub experiment_ids: &'a [&'a ExperimentId],
+    pub experiment_ids: &'a [ExperimentId],
     pub filter: &'a str,
     pub run_view_type: ViewType,
     pub max_results: i32,
-    pub order_by: Option<&'a str>,
-    pub page_token: Option<&'a str>,
+    pub order_by: &'a [String],
+    pub page_token: Option<&'a PageToken>,
 }
 #[derive(Deserialize)]
 struct ListRunInfosRun {
@@ -661,7 +813,9 @@ struct ListRunInfosRun {
 }
 #[derive(Deserialize)]
 struct ListRunInfosResponse {
+    #[serde(default)]
     pub runs: Vec<ListRunInfosRun>,
+    #[serde(default)]
     pub next_page_token: PageToken,
 }
 impl Endpoint for ListRunInfos<'_> {
@@ -685,6 +839,7 @@ struct GetHistory<'a> {
 }
 #[derive(Deserialize)]
 struct GetHistoryResponse {
+    #[serde(default)]
     metrics: Vec<Metric<'static>>,
 }
 impl Endpoint for GetHistory<'_> {
@@ -698,9 +853,55 @@ impl Endpoint for GetHistory<'_> {
     }
 }
 
+#[derive(Serialize)]
+struct ListArtifacts<'a> {
+    path: &'a str,
+}
+#[derive(Deserialize)]
+struct ListArtifactsResponse {
+    #[serde(default)]
+    files: Vec<FileInfo>,
+}
+
 #[cfg(test)]
 mod tests {
-    use super::GetExperimentResponse;
+    use super::{encode_path, GetExperimentResponse, ListRunInfosResponse, SearchRuns, Server};
+    use crate::{
+        api::search::SearchRunsRequest, testing::MockServer, tracking::TrackingRun, Client,
+    };
+
+    #[test]
+    fn server_from_tracking_uri() {
+        let server = Server::from_tracking_uri("http://localhost:5000/").unwrap();
+        assert_eq!(server.api_url, "http://localhost:5000/api");
+        assert!(Server::from_tracking_uri("./mlruns").is_err());
+    }
+
+    #[test]
+    fn artifacts_keep_the_scheme_and_encode_their_paths() {
+        let server = Server::from_tracking_uri("https://tracking:5000").unwrap();
+        assert_eq!(
+            server.artifacts_url(Some("proxy:8080".into())),
+            "https://proxy:8080/api"
+        );
+        assert_eq!(encode_path("0/a b/%.txt"), "0/a%20b/%25.txt");
+
+        let mock = MockServer::start();
+        let mut client = mock.client();
+        let experiment = client.create_experiment("Artifacts").unwrap();
+        let run = client.create_run(&experiment, 0, &[]).unwrap();
+        client
+            .upload_artifact(&run.info, "a b/50%.txt", b"half")
+            .unwrap();
+        assert_eq!(
+            client.download_artifact(&run.info, "a b/50%.txt").unwrap(),
+            b"half"
+        );
+        assert_eq!(
+            mock.store().artifact(&run.info.run_id, "a b/50%.txt"),
+            Some(&b"half"[..])
+        );
+    }
 
     #[test]
     fn parse_get_experiment_response() {
@@ -717,4 +918,50 @@ mod tests {
         let parsed = serde_json::from_str::<GetExperimentResponse>(response).unwrap();
         assert_eq!(parsed.experiment.experiment_id.as_ref(), "1");
     }
+
+    #[test]
+    fn parse_last_search_page() {
+        let parsed = serde_json::from_str::<ListRunInfosResponse>("{}").unwrap();
+        assert!(parsed.runs.is_empty());
+        assert!(parsed.next_page_token.as_ref().is_empty());
+    }
+
+    #[test]
+    fn serialize_search_runs_request() {
+        let request = SearchRunsRequest::new(&["1".into()])
+            .order_by("metrics.loss ASC")
+            .page_token(Some("next".into()));
+        let request = SearchRuns {
+            experiment_ids: &request.experiment_ids,
+            filter: &request.filter,
+            run_view_type: request.run_view_type,
+            max_results: request.max_results,
+            order_by: &request.order_by,
+            page_token: request.page_token.as_ref(),
+        };
+        let serialized = serde_json::to_value(request).unwrap();
+        assert_eq!(
+            serialized,
+            serde_json::json!({
+                "experiment_ids": ["1"],
+                "filter": "",
+                "run_view_type": "ACTIVE_ONLY",
+                "max_results": 1000,
+                "order_by": ["metrics.loss ASC"],
+                "page_token": "next",
+            })
+        );
+    }
+
+    #[test]
+    fn resumed_runs_are_fetched_from_runs_get() {
+        let mock = MockServer::start();
+        let mut client = mock.client();
+        let run = client.create_run(&"0".into(), 0, &[]).unwrap();
+        mock.clear_requests();
+        TrackingRun::resume(&mut client, &run.info.run_id).unwrap();
+        let requests = mock.requests();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "2.0/mlflow/runs/get");
+    }
 }
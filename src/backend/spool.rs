@@ -0,0 +1,660 @@
+//! A [`Client`] decorator which keeps logging while the tracking server is unreachable.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{
+        artifact::FileInfo,
+        client::{Client, ViewType},
+        error::{BatchError, CreateError, DeleteError, GetError, StorageError, UpdateError},
+        experiment::Experiment,
+        run::{Metric, Param, Run, RunData, RunInfo, RunStatus, RunTag},
+        search::{ListRunsRequest, RunList, Search, SearchRunsRequest},
+    },
+    ExperimentId, RunId,
+};
+
+const JOURNAL: &str = "journal.jsonl";
+const STATE: &str = "state.json";
+
+/// Writes all changes to runs to a journal on disk before sending them to the inner client.
+///
+/// Creating, updating and logging to runs always succeeds as soon as the change is journaled.
+/// The journal is then replayed to the inner client in order, which is retried on later changes
+/// once the [retry interval][Spool::retry_interval] has passed, or explicitly using [`Spool::sync`].
+/// Until the journal is replayed, created runs have a provisional id, which is mapped to the id
+/// assigned by the server afterwards. The mapping is kept on disk, so provisional ids stay valid.
+///
+/// Replayed changes are acknowledged on disk, so opening the spool again, e.g. after a reboot,
+/// continues with the first change which was not stored yet. A change may be replayed twice if
+/// the process stops right after the server stored it.
+///
+/// Experiments, deleted runs and artifacts are not journaled but passed to the inner client.
+/// Only one spool may use a directory at a time.
+pub struct Spool<C> {
+    inner: C,
+    dir: PathBuf,
+    journal: File,
+    pending: VecDeque<Entry>,
+    state: State,
+    next_seq: u64,
+    /// Runs created by this spool, to answer updates while they can not be replayed.
+    infos: HashMap<RunId, RunInfo>,
+    retry_interval: Duration,
+    retry_at: Option<Instant>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    /// The sequence number of the last replayed entry.
+    acked: u64,
+    /// Maps provisional run ids to the ids assigned by the server.
+    ids: HashMap<RunId, RunId>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    seq: u64,
+    #[serde(flatten)]
+    operation: Operation,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Operation {
+    CreateRun {
+        run_id: RunId,
+        experiment_id: ExperimentId,
+        start_time: i64,
+        tags: Vec<RunTag>,
+    },
+    UpdateRun {
+        run_id: RunId,
+        status: RunStatus,
+        end_time: Option<i64>,
+    },
+    LogBatch {
+        run_id: RunId,
+        metrics: Vec<Metric<'static>>,
+        params: Vec<Param>,
+        tags: Vec<RunTag>,
+    },
+}
+
+impl<C: Client> Spool<C> {
+    /// Opens the spool stored in `dir`, which is created if it does not exist.
+    pub fn open(inner: C, dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let state = match fs::read_to_string(dir.join(STATE)) {
+            Ok(state) => serde_json::from_str(&state).context("the spool state is corrupted")?,
+            Err(_) => State::default(),
+        };
+        let journal_path = dir.join(JOURNAL);
+        let mut pending = VecDeque::new();
+        let mut next_seq = state.acked + 1;
+        // The length of the complete entries, and whether the last one ends with a newline.
+        let mut valid_len = 0;
+        let mut terminated = true;
+        if let Ok(journal) = fs::read_to_string(&journal_path) {
+            let mut lines = journal.split_inclusive('\n').peekable();
+            while let Some(line) = lines.next() {
+                let entry = match serde_json::from_str::<Entry>(line) {
+                    Ok(entry) => entry,
+                    // The last line is incomplete if the process stopped while writing it.
+                    Err(_) if lines.peek().is_none() => break,
+                    Err(error) => return Err(error).context("the spool journal is corrupted"),
+                };
+                valid_len += line.len() as u64;
+                terminated = line.ends_with('\n');
+                next_seq = next_seq.max(entry.seq + 1);
+                if entry.seq > state.acked {
+                    pending.push_back(entry);
+                }
+            }
+        }
+        let mut journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)
+            .with_context(|| format!("failed to open {}", journal_path.display()))?;
+        // Removes an incomplete last line, so the next entry starts on a line of its own.
+        journal
+            .set_len(valid_len)
+            .and_then(|()| {
+                if terminated {
+                    Ok(())
+                } else {
+                    journal.write_all(b"\n")
+                }
+            })
+            .with_context(|| format!("failed to repair {}", journal_path.display()))?;
+        Ok(Spool {
+            inner,
+            dir,
+            journal,
+            pending,
+            state,
+            next_seq,
+            infos: HashMap::new(),
+            retry_interval: Duration::from_secs(30),
+            retry_at: None,
+        })
+    }
+
+    /// How long to wait after a failed replay before replaying on the next change. Defaults to 30s.
+    pub fn retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    /// The number of changes which were not replayed yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// The id assigned by the server to a run created with a provisional id, once it is replayed.
+    ///
+    /// Other ids are returned as they are.
+    pub fn resolve<'a>(&'a self, id: &'a RunId) -> Option<&'a RunId> {
+        if self.is_provisional(id) {
+            self.state.ids.get(id)
+        } else {
+            Some(id)
+        }
+    }
+
+    pub fn inner(&mut self) -> &mut C {
+        &mut self.inner
+    }
+
+    /// Replays all pending changes and returns how many were replayed.
+    ///
+    /// Stops at the first change which could not be replayed.
+    /// If the server permanently rejects a change, it can be dropped using [`Spool::discard_next`].
+    pub fn sync(&mut self) -> Result<usize, StorageError> {
+        let mut replayed = 0;
+        while let Some(seq) = self.pending.front().map(|entry| entry.seq) {
+            self.replay(seq)
+                .with_context(|| format!("failed to replay change {} of the spool", seq))?;
+            replayed += 1;
+        }
+        self.retry_at = None;
+        Ok(replayed)
+    }
+
+    /// Drops the next pending change without replaying it.
+    pub fn discard_next(&mut self) -> Result<(), StorageError> {
+        if let Some(entry) = self.pending.pop_front() {
+            self.acknowledge(entry.seq)?;
+        }
+        Ok(())
+    }
+
+    fn is_provisional(&self, id: &RunId) -> bool {
+        id.as_ref().starts_with("spool-")
+    }
+
+    fn map_id(&self, id: &RunId) -> Result<RunId, StorageError> {
+        self.resolve(id)
+            .cloned()
+            .ok_or_else(|| anyhow!("the run {} was not created on the server yet", id.as_ref()))
+    }
+
+    /// The info of the run on the inner client, which requires the run to be synced.
+    fn inner_info(&mut self, run: &RunInfo) -> Result<RunInfo, StorageError> {
+        self.try_sync();
+        let id = self.map_id(&run.run_id)?;
+        Ok(match self.infos.get(&run.run_id) {
+            Some(info) if info.run_id == id => info.clone(),
+            _ if id == run.run_id => run.clone(),
+            _ => self.inner.get_run(&id)?.info,
+        })
+    }
+
+    fn replay(&mut self, seq: u64) -> Result<(), StorageError> {
+        let entry = self
+            .pending
+            .front()
+            .expect("replay requires a pending entry");
+        debug_assert_eq!(entry.seq, seq);
+        match &entry.operation {
+            Operation::CreateRun {
+                run_id,
+                experiment_id,
+                start_time,
+                tags,
+            } => {
+                let run = self.inner.create_run(experiment_id, *start_time, tags)?;
+                self.state
+                    .ids
+                    .insert(run_id.clone(), run.info.run_id.clone());
+                self.infos.insert(run_id.clone(), run.info.clone());
+                self.infos.insert(run.info.run_id.clone(), run.info);
+            }
+            Operation::UpdateRun {
+                run_id,
+                status,
+                end_time,
+            } => {
+                let id = self.map_id(run_id)?;
+                self.inner.update_run(&id, *status, *end_time)?;
+            }
+            Operation::LogBatch {
+                run_id,
+                metrics,
+                params,
+                tags,
+            } => {
+                let id = self.map_id(run_id)?;
+                self.inner.log_batch(&id, metrics, params, tags)?;
+            }
+        }
+        self.pending.pop_front();
+        self.acknowledge(seq)
+    }
+
+    fn acknowledge(&mut self, seq: u64) -> Result<(), StorageError> {
+        self.state.acked = seq;
+        let path = self.dir.join(STATE);
+        let temporary = self.dir.join(format!("{}.tmp", STATE));
+        fs::write(&temporary, serde_json::to_vec(&self.state)?)
+            .with_context(|| format!("failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, &path)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        if self.pending.is_empty() {
+            self.journal.set_len(0)?;
+        }
+        Ok(())
+    }
+
+    /// Rejects params which conflict with ones waiting in the journal, as they would block the replay.
+    fn check_pending_params(&self, run: &RunId, params: &[Param]) -> Result<(), BatchError> {
+        for entry in &self.pending {
+            let pending = match &entry.operation {
+                Operation::LogBatch { run_id, params, .. } if run_id == run => params,
+                _ => continue,
+            };
+            for param in params {
+                let conflict = pending
+                    .iter()
+                    .find(|other| other.key == param.key && other.value != param.value);
+                if let Some(other) = conflict {
+                    return Err(BatchError::ConflictingParam {
+                        key: param.key.clone(),
+                        first: other.value.clone(),
+                        second: param.value.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, operation: Operation) -> Result<(), StorageError> {
+        let entry = Entry {
+            seq: self.next_seq,
+            operation,
+        };
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        let len = self.journal.metadata()?.len();
+        let written = self
+            .journal
+            .write_all(&line)
+            .and_then(|()| self.journal.sync_data());
+        if let Err(error) = written {
+            // Removes what was written of the entry, so it does not corrupt the next one.
+            let _ = self.journal.set_len(len);
+            return Err(error).context("failed to write the spool journal");
+        }
+        self.next_seq += 1;
+        self.pending.push_back(entry);
+        self.try_sync();
+        Ok(())
+    }
+
+    /// Replays the pending changes, unless the last attempt failed recently.
+    fn try_sync(&mut self) {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return;
+        }
+        if self.sync().is_err() {
+            self.retry_at = Some(Instant::now() + self.retry_interval);
+        }
+    }
+}
+
+#[allow(deprecated)]
+fn provisional_info(id: &RunId, experiment: &ExperimentId, start_time: i64) -> RunInfo {
+    RunInfo {
+        run_id: id.clone(),
+        run_uuid: id.as_ref().to_string(),
+        experiment_id: experiment.clone(),
+        user_id: String::new(),
+        status: RunStatus::Running,
+        start_time,
+        end_time: None,
+        artifact_uri: String::new(),
+        lifecycle_stage: "active".to_string(),
+    }
+}
+
+impl<C: Client> Client for Spool<C> {
+    fn create_experiment(&mut self, name: &str) -> Result<ExperimentId, CreateError> {
+        self.inner.create_experiment(name)
+    }
+
+    fn list_experiments(&mut self, view_type: ViewType) -> Result<Vec<Experiment>, StorageError> {
+        self.inner.list_experiments(view_type)
+    }
+
+    fn get_experiment(&mut self, id: &ExperimentId) -> Result<Experiment, GetError> {
+        self.inner.get_experiment(id)
+    }
+
+    fn get_experiment_by_name(&mut self, name: &str) -> Result<Experiment, GetError> {
+        self.inner.get_experiment_by_name(name)
+    }
+
+    fn delete_experiment(&mut self, id: &ExperimentId) -> Result<(), DeleteError> {
+        self.inner.delete_experiment(id)
+    }
+
+    fn restore_experiment(&mut self, id: &ExperimentId) -> Result<(), UpdateError> {
+        self.inner.restore_experiment(id)
+    }
+
+    fn update_experiment(
+        &mut self,
+        id: &ExperimentId,
+        new_name: Option<&str>,
+    ) -> Result<(), StorageError> {
+        self.inner.update_experiment(id, new_name)
+    }
+
+    fn create_run(
+        &mut self,
+        experiment: &ExperimentId,
+        start_time: i64,
+        tags: &[RunTag],
+    ) -> Result<Run, StorageError> {
+        let id = RunId::from(format!(
+            "spool-{:x}-{:x}-{}",
+            crate::timestamp(),
+            std::process::id(),
+            self.next_seq
+        ));
+        self.infos
+            .insert(id.clone(), provisional_info(&id, experiment, start_time));
+        self.append(Operation::CreateRun {
+            run_id: id.clone(),
+            experiment_id: experiment.clone(),
+            start_time,
+            tags: tags.to_vec(),
+        })?;
+        Ok(Run {
+            info: self.infos[&id].clone(),
+            data: RunData {
+                tags: Some(tags.to_vec()),
+                ..RunData::default()
+            },
+        })
+    }
+
+    fn delete_run(&mut self, id: &RunId) -> Result<(), DeleteError> {
+        let id = self.map_id(id)?;
+        self.inner.delete_run(&id)
+    }
+
+    fn restore_run(&mut self, id: &RunId) -> Result<(), UpdateError> {
+        let id = self.map_id(id)?;
+        self.inner.restore_run(&id)
+    }
+
+    fn get_run(&mut self, id: &RunId) -> Result<Run, GetError> {
+        self.try_sync();
+        let id = self.map_id(id)?;
+        self.inner.get_run(&id)
+    }
+
+    /// Returns the updated info of runs created by this spool.
+    /// For other runs, only the id, status and end time are known while the change is pending.
+    fn update_run(
+        &mut self,
+        id: &RunId,
+        status: RunStatus,
+        end_time: Option<i64>,
+    ) -> Result<RunInfo, UpdateError> {
+        self.append(Operation::UpdateRun {
+            run_id: id.clone(),
+            status,
+            end_time,
+        })?;
+        let info = self
+            .infos
+            .entry(id.clone())
+            .or_insert_with(|| provisional_info(id, &ExperimentId::from(""), 0));
+        info.status = status;
+        info.end_time = end_time.or(info.end_time);
+        Ok(info.clone())
+    }
+
+    fn search_runs(&mut self, request: &SearchRunsRequest) -> Result<Search, StorageError> {
+        self.inner.search_runs(request)
+    }
+
+    fn list_run_infos(&mut self, request: &ListRunsRequest) -> Result<RunList, StorageError> {
+        self.inner.list_run_infos(request)
+    }
+
+    fn get_metric_history(
+        &mut self,
+        run: &RunId,
+        metric: &str,
+    ) -> Result<Vec<Metric<'static>>, GetError> {
+        self.try_sync();
+        let run = self.map_id(run)?;
+        self.inner.get_metric_history(&run, metric)
+    }
+
+    fn log_param(&mut self, run: &RunId, key: &str, value: &str) -> Result<(), StorageError> {
+        let param = Param {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        self.check_pending_params(run, std::slice::from_ref(&param))?;
+        self.append(Operation::LogBatch {
+            run_id: run.clone(),
+            metrics: Vec::new(),
+            params: vec![param],
+            tags: Vec::new(),
+        })
+    }
+
+    fn log_metric(
+        &mut self,
+        run: &RunId,
+        key: &str,
+        value: f64,
+        timestamp: i64,
+        step: i64,
+    ) -> Result<(), StorageError> {
+        let metric = Metric {
+            key: key.to_string().into(),
+            value,
+            timestamp,
+            step,
+        };
+        self.append(Operation::LogBatch {
+            run_id: run.clone(),
+            metrics: vec![metric],
+            params: Vec::new(),
+            tags: Vec::new(),
+        })
+    }
+
+    fn log_batch(
+        &mut self,
+        run: &RunId,
+        metrics: &[Metric],
+        params: &[Param],
+        tags: &[RunTag],
+    ) -> Result<(), BatchError> {
+        // Invalid batches are rejected now, as they would block the replay of all later changes.
+        BatchError::check(metrics, params, tags)?;
+        self.check_pending_params(run, params)?;
+        self.append(Operation::LogBatch {
+            run_id: run.clone(),
+            metrics: metrics.iter().cloned().map(Metric::into_owned).collect(),
+            params: params.to_vec(),
+            tags: tags.to_vec(),
+        })?;
+        Ok(())
+    }
+
+    /// Uploads the artifact right away, which requires the run to be replayed already.
+    fn upload_artifact(
+        &mut self,
+        run: &RunInfo,
+        path: &str,
+        contents: &[u8],
+    ) -> Result<(), StorageError> {
+        let info = self.inner_info(run)?;
+        self.inner.upload_artifact(&info, path, contents)
+    }
+
+    fn list_artifacts(&mut self, run: &RunInfo, path: &str) -> Result<Vec<FileInfo>, StorageError> {
+        let info = self.inner_info(run)?;
+        self.inner.list_artifacts(&info, path)
+    }
+
+    fn download_artifact(&mut self, run: &RunInfo, path: &str) -> Result<Vec<u8>, StorageError> {
+        let info = self.inner_info(run)?;
+        self.inner.download_artifact(&info, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spool;
+    use crate::{
+        api::run::{Param, RunStatus},
+        testing::{Fault, MockServer},
+        Client,
+    };
+
+    #[test]
+    fn spool_replays_after_reopening() {
+        let dir = std::env::temp_dir().join(format!("mlflow-spool-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let param = Param {
+            key: "lr".to_string(),
+            value: "0.1".to_string(),
+        };
+
+        let server = MockServer::start();
+        server.inject(Fault::any());
+        let mut spool = Spool::open(server.client(), &dir).unwrap();
+        let run = spool.create_run(&"0".into(), 0, &[]).unwrap();
+        let provisional = run.info.run_id;
+        assert!(provisional.as_ref().starts_with("spool-"));
+        spool
+            .log_batch(&provisional, &[], std::slice::from_ref(&param), &[])
+            .unwrap();
+        spool
+            .update_run(&provisional, RunStatus::Finished, Some(1))
+            .unwrap();
+        assert_eq!(spool.pending(), 3);
+        let conflict = Param {
+            value: "0.2".to_string(),
+            ..param.clone()
+        };
+        assert!(spool.log_param(&provisional, "lr", "0.2").is_err());
+        assert!(spool
+            .log_batch(&provisional, &[], &[param.clone(), conflict], &[])
+            .is_err());
+        assert_eq!(spool.pending(), 3);
+        drop(spool);
+
+        server.clear_faults();
+        let mut spool = Spool::open(server.client(), &dir).unwrap();
+        assert_eq!(spool.pending(), 3);
+        assert_eq!(spool.sync().unwrap(), 3);
+        assert_eq!(spool.sync().unwrap(), 0);
+        let id = spool.resolve(&provisional).unwrap().clone();
+        assert_eq!(server.store().get_run(&id).unwrap().info.run_id, id);
+        let run = spool.get_run(&provisional).unwrap();
+        assert_eq!(run.data.params, Some(vec![param]));
+        assert_eq!(run.info.status, RunStatus::Finished);
+        drop(spool);
+
+        let spool = Spool::open(server.client(), &dir).unwrap();
+        assert_eq!(spool.pending(), 0);
+        assert_eq!(spool.resolve(&provisional), Some(&id));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn non_finite_metrics_survive_reopening() {
+        let dir = std::env::temp_dir().join(format!("mlflow-spool-nan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let server = MockServer::start();
+        server.inject(Fault::any());
+        let mut spool = Spool::open(server.client(), &dir).unwrap();
+        let run = spool.create_run(&"0".into(), 0, &[]).unwrap().info.run_id;
+        for (step, value) in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY]
+            .iter()
+            .enumerate()
+        {
+            spool
+                .log_metric(&run, "loss", *value, 1, step as i64)
+                .unwrap();
+        }
+        drop(spool);
+
+        server.clear_faults();
+        let mut spool = Spool::open(server.client(), &dir).unwrap();
+        assert_eq!(spool.sync().unwrap(), 4);
+        let history = spool.get_metric_history(&run, "loss").unwrap();
+        assert!(history[0].value.is_nan());
+        assert_eq!(history[1].value, f64::INFINITY);
+        assert_eq!(history[2].value, f64::NEG_INFINITY);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn torn_journal_lines_are_removed() {
+        let dir = std::env::temp_dir().join(format!("mlflow-spool-torn-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let server = MockServer::start();
+        server.inject(Fault::any());
+        let mut spool = Spool::open(server.client(), &dir).unwrap();
+        let run = spool.create_run(&"0".into(), 0, &[]).unwrap().info.run_id;
+        drop(spool);
+
+        // A crash while writing the second entry.
+        let mut journal = std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join("journal.jsonl"))
+            .unwrap();
+        std::io::Write::write_all(&mut journal, br#"{"seq":2,"op":"log_"#).unwrap();
+        let mut spool = Spool::open(server.client(), &dir).unwrap();
+        assert_eq!(spool.pending(), 1);
+        spool.log_param(&run, "lr", "0.1").unwrap();
+        drop(spool);
+
+        let spool = Spool::open(server.client(), &dir).unwrap();
+        assert_eq!(spool.pending(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
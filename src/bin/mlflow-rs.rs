@@ -0,0 +1,817 @@
+//! Manages the experiments and runs of a tracking server from the command line.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use mlflow::{
+    api::{
+        client::ViewType,
+        error::GetError,
+        experiment::Experiment,
+        params,
+        run::{Run, RunInfo, RunStatus, RunTag},
+        search::SearchRunsRequest,
+        tags,
+    },
+    backend::{file::FileStore, rest::Server, spool::Spool},
+    tracking::{context, LiveRun},
+    Client, ExperimentId, Paginate, RunId,
+};
+use pico_args::Arguments;
+use serde::Serialize;
+
+const HELP: &str = "\
+Manages the experiments and runs of an MLflow tracking server.
+
+USAGE:
+    mlflow-rs [OPTIONS] <COMMAND>
+
+COMMANDS:
+    experiments list [--view <VIEW>]
+    experiments create <NAME>
+    experiments rename <EXPERIMENT> <NAME>
+    experiments delete <EXPERIMENT>
+    experiments restore <EXPERIMENT>
+    runs list --experiment <EXPERIMENT> [--view <VIEW>] [--max-results <N>]
+    runs get <RUN>
+    runs delete <RUN>
+    runs restore <RUN>
+    runs search --experiment <EXPERIMENT>... [--filter <FILTER>] [--order-by <ORDER>]...
+                [--view <VIEW>] [--max-results <N>]
+    run [--experiment <EXPERIMENT>] [--name <NAME>] -- <PROGRAM> [ARGS]...
+    spool sync <DIR>
+
+    Experiments are given by name or id. VIEW is one of active, deleted or all [default: active].
+
+    run tracks a program as a run of the experiment [default: 0]. Its output is stored as the
+    artifact output.log, and stdout lines like `METRIC loss=0.25 step=3` are logged as metrics.
+    The command line and the variables MLFLOW_PARAM_<NAME> are logged as params. The program
+    can log into the run itself using MLFLOW_TRACKING_URI and MLFLOW_RUN_ID.
+
+    spool sync replays the changes journaled in the spool directory DIR to the tracking server.
+
+OPTIONS:
+    --tracking-uri <URI>    The tracking server or mlruns directory [default: $MLFLOW_TRACKING_URI]
+    --json                  Prints JSON instead of tables
+    -h, --help              Prints this help
+";
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    /// Prints the value as JSON, or the table built by `table` otherwise.
+    fn print<T: Serialize + ?Sized>(
+        &self,
+        value: &T,
+        table: impl FnOnce(&T) -> Table,
+    ) -> Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            print!("{}", table(value));
+        }
+        Ok(())
+    }
+
+    /// Confirms a change, which is silent with `--json` unless there is a result.
+    fn done(&self, message: impl Display) {
+        if !self.json {
+            println!("{}", message);
+        }
+    }
+}
+
+/// Columns which are padded to the width of their longest cell.
+struct Table {
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn new(header: &[&str]) -> Self {
+        Table {
+            rows: vec![header.iter().map(|cell| cell.to_string()).collect()],
+        }
+    }
+
+    fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or_default();
+        let widths = (0..columns)
+            .map(|column| {
+                let cells = self.rows.iter().filter_map(|row| row.get(column));
+                cells
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        for row in &self.rows {
+            let mut line = String::new();
+            for (cell, width) in row.iter().zip(&widths) {
+                line.push_str(&format!("{:1$}  ", cell, width));
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a MLflow timestamp as UTC date and time.
+fn format_time(timestamp: i64) -> String {
+    let seconds = timestamp.div_euclid(1000);
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+    // The civil date of the days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+fn format_duration(info: &RunInfo) -> String {
+    let seconds = match info.end_time {
+        Some(end_time) => (end_time - info.start_time) / 1000,
+        None => return String::new(),
+    };
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}h {}m", s / 3600, s % 3600 / 60),
+    }
+}
+
+fn run_name(run: &Run) -> String {
+    let mut run_tags = run.data.tags.iter().flatten();
+    let name = run_tags.find(|tag| tag.key == tags::RUN_NAME);
+    name.map(|tag| tag.value.clone()).unwrap_or_default()
+}
+
+fn experiments_table(experiments: &[Experiment]) -> Table {
+    let mut table = Table::new(&["ID", "NAME", "LIFECYCLE", "ARTIFACT LOCATION"]);
+    for experiment in experiments {
+        table.row(vec![
+            experiment.experiment_id.as_ref().to_string(),
+            experiment.name.clone(),
+            experiment.lifecycle_stage.clone(),
+            experiment.artifact_location.clone(),
+        ]);
+    }
+    table
+}
+
+fn runs_table(runs: &[Run]) -> Table {
+    let mut table = Table::new(&["RUN ID", "NAME", "STATUS", "START", "DURATION", "LIFECYCLE"]);
+    for run in runs {
+        table.row(vec![
+            run.info.run_id.as_ref().to_string(),
+            run_name(run),
+            format!("{:?}", run.info.status).to_uppercase(),
+            format_time(run.info.start_time),
+            format_duration(&run.info),
+            run.info.lifecycle_stage.clone(),
+        ]);
+    }
+    table
+}
+
+fn run_table(run: &Run) -> Table {
+    let info = &run.info;
+    let mut table = Table::new(&["FIELD", "VALUE", "STEP"]);
+    let fields = [
+        ("run_id", info.run_id.as_ref().to_string()),
+        ("experiment_id", info.experiment_id.as_ref().to_string()),
+        ("name", run_name(run)),
+        ("status", format!("{:?}", info.status).to_uppercase()),
+        ("start_time", format_time(info.start_time)),
+        (
+            "end_time",
+            info.end_time.map(format_time).unwrap_or_default(),
+        ),
+        ("lifecycle_stage", info.lifecycle_stage.clone()),
+        ("artifact_uri", info.artifact_uri.clone()),
+    ];
+    for (field, value) in fields.iter() {
+        table.row(vec![field.to_string(), value.clone()]);
+    }
+    for param in run.data.params.iter().flatten() {
+        table.row(vec![format!("params.{}", param.key), param.value.clone()]);
+    }
+    for metric in run.data.metrics.iter().flatten() {
+        table.row(vec![
+            format!("metrics.{}", metric.key),
+            metric.value.to_string(),
+            metric.step.to_string(),
+        ]);
+    }
+    for tag in run.data.tags.iter().flatten() {
+        table.row(vec![format!("tags.{}", tag.key), tag.value.clone()]);
+    }
+    table
+}
+
+fn parse_view(view: &str) -> Result<ViewType, String> {
+    match view {
+        "active" => Ok(ViewType::Active),
+        "deleted" => Ok(ViewType::Deleted),
+        "all" => Ok(ViewType::All),
+        _ => Err(format!(
+            "unknown view {}, expected active, deleted or all",
+            view
+        )),
+    }
+}
+
+/// The local `mlruns` directory of the uri, or `None` for a tracking server.
+fn file_path(uri: &str) -> Option<&str> {
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        return None;
+    }
+    let path = uri
+        .strip_prefix("file://")
+        .or_else(|| uri.strip_prefix("file:"));
+    Some(path.unwrap_or(uri))
+}
+
+/// Connects to a tracking server, or opens a local `mlruns` directory.
+fn open(uri: &str) -> Result<Box<dyn Client>> {
+    match file_path(uri) {
+        None => Ok(Box::new(Server::from_tracking_uri(uri)?)),
+        Some(path) => Ok(Box::new(FileStore::open(path)?)),
+    }
+}
+
+/// Finds an experiment by name, or by id if there is none with the name.
+fn experiment_id(client: &mut dyn Client, experiment: &str) -> Result<ExperimentId> {
+    match client.get_experiment_by_name(experiment) {
+        Ok(found) => Ok(found.experiment_id),
+        Err(GetError::DoesNotExist(_)) => {
+            let id = ExperimentId::from(experiment);
+            client
+                .get_experiment(&id)
+                .with_context(|| format!("there is no experiment {}", experiment))?;
+            Ok(id)
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// The single free argument of a command.
+fn argument(args: Arguments, name: &str) -> Result<String> {
+    let mut free = args.free()?;
+    if free.len() != 1 {
+        bail!("expected {} as the only argument, found {:?}", name, free);
+    }
+    Ok(free.remove(0))
+}
+
+fn experiments(client: &mut dyn Client, output: &Output, mut args: Arguments) -> Result<()> {
+    let command = args.subcommand()?.unwrap_or_default();
+    match command.as_str() {
+        "list" => {
+            let view = args.opt_value_from_fn("--view", parse_view)?;
+            args.finish()?;
+            let experiments = client.list_experiments(view.unwrap_or(ViewType::Active))?;
+            output.print(experiments.as_slice(), experiments_table)
+        }
+        "create" => {
+            let name = argument(args, "the name")?;
+            let id = client.create_experiment(&name)?;
+            if output.json {
+                println!("{}", serde_json::json!({ "experiment_id": id }));
+            }
+            output.done(format!(
+                "Created the experiment {} with the id {}",
+                name,
+                id.as_ref()
+            ));
+            Ok(())
+        }
+        "rename" => {
+            let free = args.free()?;
+            let (experiment, name) = match free.as_slice() {
+                [experiment, name] => (experiment, name),
+                _ => bail!("expected the experiment and its new name, found {:?}", free),
+            };
+            let id = experiment_id(client, experiment)?;
+            client.update_experiment(&id, Some(name))?;
+            output.done(format!(
+                "Renamed the experiment {} to {}",
+                id.as_ref(),
+                name
+            ));
+            Ok(())
+        }
+        "delete" => {
+            let id = experiment_id(client, &argument(args, "the experiment")?)?;
+            client.delete_experiment(&id)?;
+            output.done(format!("Deleted the experiment {}", id.as_ref()));
+            Ok(())
+        }
+        "restore" => {
+            let id = experiment_id(client, &argument(args, "the experiment")?)?;
+            client.restore_experiment(&id)?;
+            output.done(format!("Restored the experiment {}", id.as_ref()));
+            Ok(())
+        }
+        "" => bail!("missing the experiments command, see --help"),
+        other => bail!("unknown command experiments {}, see --help", other),
+    }
+}
+
+fn runs(client: &mut dyn Client, output: &Output, mut args: Arguments) -> Result<()> {
+    let command = args.subcommand()?.unwrap_or_default();
+    match command.as_str() {
+        "list" | "search" => {
+            let experiments: Vec<String> = args.values_from_str("--experiment")?;
+            let filter: Option<String> = args.opt_value_from_str("--filter")?;
+            let order_by: Vec<String> = args.values_from_str("--order-by")?;
+            let view = args.opt_value_from_fn("--view", parse_view)?;
+            let max_results: Option<usize> = args.opt_value_from_str("--max-results")?;
+            args.finish()?;
+            if experiments.is_empty() {
+                bail!("missing --experiment");
+            }
+            if command == "list" && (filter.is_some() || !order_by.is_empty()) {
+                bail!("runs list does not filter or order, use runs search instead");
+            }
+            let ids = experiments
+                .iter()
+                .map(|experiment| experiment_id(client, experiment))
+                .collect::<Result<Vec<_>>>()?;
+            let mut request = SearchRunsRequest::new(&ids)
+                .filter(filter.unwrap_or_default())
+                .view_type(view.unwrap_or(ViewType::Active));
+            for order in order_by {
+                request = request.order_by(order);
+            }
+            let mut search = client.search(request);
+            if let Some(max_results) = max_results {
+                search = search.limit(max_results);
+            }
+            let runs = search.iter().collect::<Result<Vec<_>, _>>()?;
+            output.print(runs.as_slice(), runs_table)
+        }
+        "get" => {
+            let id = RunId::from(argument(args, "the run id")?);
+            let run = client.get_run(&id)?;
+            output.print(&run, run_table)
+        }
+        "delete" => {
+            let id = RunId::from(argument(args, "the run id")?);
+            client.delete_run(&id)?;
+            output.done(format!("Deleted the run {}", id.as_ref()));
+            Ok(())
+        }
+        "restore" => {
+            let id = RunId::from(argument(args, "the run id")?);
+            client.restore_run(&id)?;
+            output.done(format!("Restored the run {}", id.as_ref()));
+            Ok(())
+        }
+        "" => bail!("missing the runs command, see --help"),
+        other => bail!("unknown command runs {}, see --help", other),
+    }
+}
+
+fn spool(uri: &str, output: &Output, mut args: Arguments) -> Result<()> {
+    let command = args.subcommand()?.unwrap_or_default();
+    match command.as_str() {
+        "sync" => {
+            let dir = argument(args, "the spool directory")?;
+            let replayed = match file_path(uri) {
+                None => sync_spool(Server::from_tracking_uri(uri)?, &dir),
+                Some(path) => sync_spool(FileStore::open(path)?, &dir),
+            }?;
+            if output.json {
+                println!("{}", serde_json::json!({ "replayed": replayed }));
+            }
+            output.done(format!(
+                "Replayed {} changes of the spool {}",
+                replayed, dir
+            ));
+            Ok(())
+        }
+        "" => bail!("missing the spool command, see --help"),
+        other => bail!("unknown command spool {}, see --help", other),
+    }
+}
+
+/// Replays all changes of the spool in `dir` and returns how many were replayed.
+fn sync_spool(client: impl Client, dir: &str) -> Result<usize> {
+    let mut spool = Spool::open(client, dir)?;
+    let replayed = spool.sync();
+    replayed.with_context(|| format!("{} changes are still pending", spool.pending()))
+}
+
+/// Quotes the argument for a POSIX shell, unless it only contains safe characters.
+fn quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// The command line of the program and the variables `MLFLOW_PARAM_<NAME>` as `name`,
+/// shortened to the longest param value.
+fn params(program: &[OsString]) -> BTreeMap<String, String> {
+    let mut params = std::env::vars_os()
+        .filter_map(|(name, value)| {
+            let name = name.to_str()?.strip_prefix("MLFLOW_PARAM_")?.to_lowercase();
+            Some((name, value.to_string_lossy().into_owned())).filter(|(name, _)| !name.is_empty())
+        })
+        .collect::<BTreeMap<_, _>>();
+    let command = program.iter().map(|arg| quote(&arg.to_string_lossy()));
+    params.insert("command".to_string(), command.collect::<Vec<_>>().join(" "));
+    params
+        .into_iter()
+        .map(|(name, value)| (name, params::shorten(value)))
+        .collect()
+}
+
+/// A line like `METRIC loss=0.25 accuracy=0.9 step=3` printed by the program.
+#[derive(Debug, PartialEq)]
+struct MetricLine<'a> {
+    metrics: Vec<(&'a str, f64)>,
+    step: Option<i64>,
+}
+
+fn parse_metrics(line: &str) -> Option<MetricLine<'_>> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "METRIC" {
+        return None;
+    }
+    let mut metrics = Vec::new();
+    let mut step = None;
+    for word in words {
+        match word.split_once('=')? {
+            ("step", value) => step = Some(value.parse().ok()?),
+            ("", _) => return None,
+            (key, value) => metrics.push((key, value.parse().ok()?)),
+        }
+    }
+    Some(MetricLine { metrics, step }).filter(|line| !line.metrics.is_empty())
+}
+
+/// Copies the lines of `input` to `output` and the log, passing each line to `on_line`.
+///
+/// The program keeps running if `output` is closed, only failing to write the log is an error.
+fn tee(
+    mut input: impl BufRead + Send + 'static,
+    mut output: impl Write + Send + 'static,
+    log: Arc<Mutex<File>>,
+    mut on_line: impl FnMut(&str) + Send + 'static,
+) -> JoinHandle<io::Result<()>> {
+    thread::spawn(move || {
+        let mut logged = Ok(());
+        let mut line = Vec::new();
+        while input.read_until(b'\n', &mut line)? > 0 {
+            let _ = output.write_all(&line).and_then(|_| output.flush());
+            if let Err(error) = log.lock().unwrap().write_all(&line) {
+                logged = logged.and(Err(error));
+            }
+            on_line(&String::from_utf8_lossy(&line));
+            line.clear();
+        }
+        logged
+    })
+}
+
+/// Keeps running when interrupted, so the run can be ended with the status of the program.
+///
+/// The terminal sends `SIGINT` to the program as well, `SIGTERM` is forwarded to it.
+#[cfg(unix)]
+fn forward_signals(child: u32) -> io::Result<()> {
+    use signal_hook::{
+        consts::{SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if signal == SIGTERM {
+                // SAFETY: `kill` has no memory safety requirements.
+                unsafe { libc::kill(child as libc::pid_t, SIGTERM) };
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The status of the run, the note explaining it and the exit code to pass on.
+fn outcome(exit: ExitStatus) -> (RunStatus, Option<String>, i32) {
+    match exit.code() {
+        Some(0) => (RunStatus::Finished, None, 0),
+        Some(code) => (
+            RunStatus::Failed,
+            Some(format!("The program exited with status {}.", code)),
+            code,
+        ),
+        None => {
+            #[cfg(unix)]
+            {
+                use std::os::unix::process::ExitStatusExt;
+
+                let signal = exit.signal().unwrap_or_default();
+                let name = match signal {
+                    libc::SIGHUP => "SIGHUP".to_string(),
+                    libc::SIGINT => "SIGINT".to_string(),
+                    libc::SIGKILL => "SIGKILL".to_string(),
+                    libc::SIGTERM => "SIGTERM".to_string(),
+                    libc::SIGSEGV => "SIGSEGV".to_string(),
+                    libc::SIGABRT => "SIGABRT".to_string(),
+                    other => format!("the signal {}", other),
+                };
+                let note = format!("The program was killed by {}.", name);
+                (RunStatus::Killed, Some(note), 128 + signal)
+            }
+            #[cfg(not(unix))]
+            (
+                RunStatus::Killed,
+                Some("The program was killed.".to_string()),
+                1,
+            )
+        }
+    }
+}
+
+/// Runs the program as a run of the experiment and returns its exit code.
+fn track(
+    mut client: impl Client + Send + 'static,
+    uri: &str,
+    mut args: Arguments,
+    program: Vec<OsString>,
+) -> Result<i32> {
+    let experiment: Option<String> = args.opt_value_from_str("--experiment")?;
+    let name: Option<String> = args.opt_value_from_str("--name")?;
+    args.finish()?;
+    let (executable, arguments) = program
+        .split_first()
+        .ok_or_else(|| anyhow!("missing the program to run after --"))?;
+    let experiment = match experiment {
+        Some(experiment) => experiment_id(&mut client, &experiment)?,
+        None => ExperimentId::from("0"),
+    };
+
+    let source = executable.to_string_lossy().into_owned();
+    let source_tag = RunTag {
+        key: tags::SOURCE_NAME.to_string(),
+        value: source.clone(),
+    };
+    context::register(move || vec![source_tag.clone()]);
+    let run = match name {
+        Some(name) => LiveRun::start_named(client, &experiment, name)?,
+        None => LiveRun::start(client, &experiment)?,
+    };
+    run.log_params_from(&params(&program))?;
+    eprintln!("Tracking {} as the run {}", source, run.run_id().as_ref());
+
+    let child = Command::new(executable)
+        .args(arguments)
+        .env("MLFLOW_TRACKING_URI", uri)
+        .env("MLFLOW_EXPERIMENT_ID", experiment.as_ref())
+        .env("MLFLOW_RUN_ID", run.run_id().as_ref())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(error) => {
+            let error = anyhow!(error).context(format!("failed to run {}", source));
+            let message = format!("{:#}", error);
+            run.fail(error)?;
+            bail!(message);
+        }
+    };
+
+    let dir = std::env::temp_dir().join(format!("mlflow-rs-{}", run.run_id().as_ref()));
+    std::fs::create_dir_all(&dir)?;
+    let log_path = dir.join("output.log");
+    let log = Arc::new(Mutex::new(File::create(&log_path)?));
+    let handle = run.handle();
+    // Metrics without a step are logged at the step after their last one.
+    let mut steps = HashMap::new();
+    let stdout = tee(
+        BufReader::new(child.stdout.take().expect("stdout is piped")),
+        io::stdout(),
+        log.clone(),
+        move |line| {
+            if let Some(line) = parse_metrics(line) {
+                for (key, value) in line.metrics {
+                    let next = steps.entry(key.to_string()).or_insert(0);
+                    let step = line.step.unwrap_or(*next);
+                    *next = step + 1;
+                    handle.log_metric(key, value, step);
+                }
+            }
+        },
+    );
+    let stderr = tee(
+        BufReader::new(child.stderr.take().expect("stderr is piped")),
+        io::stderr(),
+        log,
+        |_| {},
+    );
+    #[cfg(unix)]
+    forward_signals(child.id())?;
+
+    let exit = child.wait()?;
+    // The pipes are closed once the program and the processes it started have exited.
+    for output in [stdout, stderr] {
+        output
+            .join()
+            .map_err(|_| anyhow!("copying the output panicked"))?
+            .context("failed to write output.log")?;
+    }
+    let (status, note, code) = outcome(exit);
+    // A failed upload of the output does not change the outcome of the program.
+    let uploaded = run.log_artifact(&log_path, None).and_then(|()| run.flush());
+    let failure = uploaded
+        .err()
+        .map(|error| format!("The output could not be uploaded: {:#}", error));
+    let note = match (note, failure) {
+        (Some(note), Some(failure)) => Some(format!("{}\n\n{}", note, failure)),
+        (note, failure) => note.or(failure),
+    };
+    if let Some(note) = &note {
+        run.log_tag(tags::NOTE, note);
+    }
+    let run_id = run.run_id().clone();
+    let ended = run.end(status);
+    let _ = std::fs::remove_dir_all(&dir);
+    match ended {
+        Ok(info) => eprintln!(
+            "The run {} ended as {}",
+            info.run_id.as_ref(),
+            format!("{:?}", info.status).to_uppercase()
+        ),
+        Err(error) => eprintln!("Failed to end the run {}: {:#}", run_id.as_ref(), error),
+    }
+    Ok(code)
+}
+
+fn main() -> Result<()> {
+    // Everything after `--` belongs to the program tracked by `run`.
+    let mut argv = std::env::args_os().skip(1).collect::<Vec<_>>();
+    let program = match argv.iter().position(|arg| arg == "--") {
+        Some(separator) => {
+            let program = argv.split_off(separator + 1);
+            argv.pop();
+            program
+        }
+        None => Vec::new(),
+    };
+    let mut args = Arguments::from_vec(argv);
+    if args.contains(["-h", "--help"]) {
+        print!("{}", HELP);
+        return Ok(());
+    }
+    let output = Output {
+        json: args.contains("--json"),
+    };
+    let uri: Option<String> = args.opt_value_from_str("--tracking-uri")?;
+    let uri = match uri {
+        Some(uri) => uri,
+        None => std::env::var("MLFLOW_TRACKING_URI")
+            .map_err(|_| anyhow!("pass --tracking-uri or set MLFLOW_TRACKING_URI"))?,
+    };
+
+    let command = args.subcommand()?;
+    if command.as_deref() == Some("run") {
+        let code = match file_path(&uri) {
+            None => {
+                Server::from_tracking_uri(&uri).map(|client| track(client, &uri, args, program))
+            }
+            Some(path) => FileStore::open(path).map(|client| track(client, &uri, args, program)),
+        };
+        let code = code.with_context(|| format!("failed to open {}", uri))??;
+        std::process::exit(code);
+    }
+    if !program.is_empty() {
+        bail!("only run takes a program after --");
+    }
+    if command.as_deref() == Some("spool") {
+        return spool(&uri, &output, args);
+    }
+    let mut client = open(&uri).with_context(|| format!("failed to open {}", uri))?;
+    match command.as_deref() {
+        Some("experiments") => experiments(client.as_mut(), &output, args),
+        Some("runs") => runs(client.as_mut(), &output, args),
+        Some(other) => bail!("unknown command {}, see --help", other),
+        None => {
+            print!("{}", HELP);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metric_lines() {
+        let line = parse_metrics("METRIC loss=0.25 accuracy=0.9 step=3\n").unwrap();
+        assert_eq!(line.metrics, vec![("loss", 0.25), ("accuracy", 0.9)]);
+        assert_eq!(line.step, Some(3));
+        assert_eq!(parse_metrics("METRIC loss=1e-3").unwrap().step, None);
+        for ignored in [
+            "loss=0.25",
+            "METRIC",
+            "METRIC step=1",
+            "METRIC loss=high",
+            "METRIC =1",
+        ] {
+            assert_eq!(parse_metrics(ignored), None, "{}", ignored);
+        }
+        assert_eq!(quote("--lr=0.1"), "--lr=0.1");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn times_are_formatted_as_utc() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_time(1_709_210_096_000), "2024-02-29 12:34:56");
+        assert_eq!(format_time(951_782_400_999), "2000-02-29 00:00:00");
+        assert_eq!(format_time(-1), "1969-12-31 23:59:59");
+        assert_eq!(format_time(-31_536_000_000), "1969-01-01 00:00:00");
+    }
+
+    #[allow(deprecated)]
+    fn run_info(start_time: i64, end_time: Option<i64>) -> RunInfo {
+        RunInfo {
+            run_id: "run".into(),
+            run_uuid: "run".to_string(),
+            experiment_id: "0".into(),
+            user_id: String::new(),
+            status: RunStatus::Finished,
+            start_time,
+            end_time,
+            artifact_uri: String::new(),
+            lifecycle_stage: "active".to_string(),
+        }
+    }
+
+    #[test]
+    fn durations_use_the_largest_units() {
+        assert_eq!(format_duration(&run_info(1000, None)), "");
+        assert_eq!(format_duration(&run_info(1000, Some(60_999))), "59s");
+        assert_eq!(format_duration(&run_info(0, Some(61_000))), "1m 1s");
+        assert_eq!(format_duration(&run_info(0, Some(3_599_000))), "59m 59s");
+        assert_eq!(format_duration(&run_info(0, Some(7_380_000))), "2h 3m");
+    }
+
+    #[test]
+    fn tables_pad_columns_to_the_widest_cell() {
+        let mut table = Table::new(&["ID", "NAME", "STATUS"]);
+        table.row(vec!["1".into(), "a long name".into(), "FINISHED".into()]);
+        table.row(vec!["12345".into(), "".into(), "".into()]);
+        table.row(vec!["2".into()]);
+        assert_eq!(
+            table.to_string(),
+            "ID     NAME         STATUS\n\
+             1      a long name  FINISHED\n\
+             12345\n\
+             2\n"
+        );
+    }
+
+    #[test]
+    fn long_commands_are_shortened() {
+        let program = ["python".into(), "x".repeat(1000).into()];
+        let command = &params(&program)["command"];
+        assert_eq!(command.len(), mlflow::api::limits::PARAM_VALUE_LENGTH);
+        assert!(command.starts_with("python xxx"), "{}", command);
+    }
+}
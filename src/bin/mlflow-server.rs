@@ -0,0 +1,107 @@
+//! A tracking server which serves the MLflow REST API and the artifact proxy.
+
+use std::{path::PathBuf, sync::Mutex};
+
+use anyhow::{Context, Result};
+use mlflow::{
+    backend::{file::FileStore, memory::Memory, rest::Server},
+    server::{self, ArtifactStore, HttpServer},
+    Client,
+};
+
+const HELP: &str = "\
+Serves the MLflow REST API and the mlflow-artifacts proxy.
+
+USAGE:
+    mlflow-server [OPTIONS]
+
+OPTIONS:
+    -h, --host <HOST>                   The address to listen on [default: 127.0.0.1]
+    -p, --port <PORT>                   The port to listen on [default: 5000]
+    --backend-store-uri <URI>           Where experiments and runs are stored [default: memory]
+                                          memory         kept in memory until the server stops
+                                          http(s)://...  forwarded to another tracking server
+                                          [file:]<path>  stored in a mlruns directory
+    --default-artifact-root <URI>       Where new experiments of a mlruns directory store their
+                                        artifacts [default: mlflow-artifacts:/]
+    --artifacts-destination <DIR>       Where proxied artifacts are stored [default: ./mlartifacts]
+    --help                              Prints this help
+";
+
+struct Args {
+    host: String,
+    port: u16,
+    backend_store_uri: String,
+    default_artifact_root: String,
+    artifacts_destination: PathBuf,
+}
+
+impl Args {
+    fn from_env() -> Result<Option<Self>> {
+        let mut args = pico_args::Arguments::from_env();
+        if args.contains("--help") {
+            return Ok(None);
+        }
+        let parsed = Args {
+            host: args
+                .opt_value_from_str(["-h", "--host"])?
+                .unwrap_or_else(|| "127.0.0.1".to_string()),
+            port: args.opt_value_from_str(["-p", "--port"])?.unwrap_or(5000),
+            backend_store_uri: args
+                .opt_value_from_str("--backend-store-uri")?
+                .unwrap_or_else(|| "memory".to_string()),
+            default_artifact_root: args
+                .opt_value_from_str("--default-artifact-root")?
+                .unwrap_or_else(|| "mlflow-artifacts:/".to_string()),
+            artifacts_destination: args
+                .opt_value_from_str("--artifacts-destination")?
+                .unwrap_or_else(|| PathBuf::from("mlartifacts")),
+        };
+        args.finish()?;
+        Ok(Some(parsed))
+    }
+}
+
+/// Opens the store behind the server.
+fn open(uri: &str, artifact_root: &str) -> Result<Box<dyn Client + Send>> {
+    if uri == "memory" {
+        Ok(Box::new(Memory::new()))
+    } else if uri.starts_with("http://") || uri.starts_with("https://") {
+        Ok(Box::new(Server::from_tracking_uri(uri)?))
+    } else {
+        let path = uri
+            .strip_prefix("file://")
+            .or_else(|| uri.strip_prefix("file:"));
+        let store = FileStore::with_artifact_root(path.unwrap_or(uri), artifact_root)?;
+        Ok(Box::new(store))
+    }
+}
+
+fn main() -> Result<()> {
+    let args = match Args::from_env()? {
+        Some(args) => args,
+        None => {
+            print!("{}", HELP);
+            return Ok(());
+        }
+    };
+    let backend = open(&args.backend_store_uri, &args.default_artifact_root)
+        .with_context(|| format!("failed to open {}", args.backend_store_uri))?;
+    let backend = Mutex::new(backend);
+    let artifacts = ArtifactStore::new(&args.artifacts_destination);
+
+    let address = (args.host.as_str(), args.port);
+    let http = HttpServer::bind(address, move |request| {
+        let response = artifacts
+            .handle(request)
+            .unwrap_or_else(|| server::handle(backend.lock().unwrap().as_mut(), request));
+        if response.status >= 400 {
+            eprintln!("{} {} -> {}", request.method, request.path, response.status);
+        }
+        response
+    })
+    .with_context(|| format!("failed to listen on {}:{}", args.host, args.port))?;
+    println!("Listening at http://{}", http.local_addr());
+    http.join();
+    Ok(())
+}
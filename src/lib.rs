@@ -1,9 +1,16 @@
 pub mod api;
+pub mod archive;
 pub mod backend;
+pub mod migrate;
+#[cfg(any(test, feature = "server"))]
+pub mod server;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
 pub mod tracking;
 
 pub use api::client::Client;
 pub use api::id::{ExperimentId, RunId};
+pub use api::pagination::Paginate;
 
 /// Utility function to create a MLflow timestamp.
 pub fn timestamp() -> i64 {
@@ -0,0 +1,602 @@
+//! Copies experiments and runs from one [`Client`] to another,
+//! e.g. from a local `mlruns` directory to a tracking server.
+//!
+//! ```no_run
+//! use mlflow::{backend::{file::FileStore, rest::Server}, migrate::Migration};
+//!
+//! let mut source = FileStore::open("mlruns")?;
+//! let mut target = Server::from_tracking_uri("http://localhost:5000")?;
+//! let mut migration = Migration::new(&mut source, &mut target).include_deleted(true);
+//! print!("{}", migration.plan()?);
+//! print!("{}", migration.run()?);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::{collections::HashMap, fmt};
+
+use anyhow::{anyhow, Context};
+
+use crate::{
+    api::{
+        client::{Client, ViewType},
+        error::{GetError, StorageError},
+        experiment::Experiment,
+        run::{Metric, Run, RunInfo, RunTag},
+        tags,
+    },
+    tracking::batch::log_batches,
+    ExperimentId, Paginate, RunId,
+};
+
+/// The tag with the id of the run a copy was made from.
+pub const SOURCE_RUN_ID: &str = "mlflow-rs.migration.sourceRunId";
+/// The tag with the id of the experiment a copy was made from.
+pub const SOURCE_EXPERIMENT_ID: &str = "mlflow-rs.migration.sourceExperimentId";
+/// The tag which marks copies whose data was copied completely.
+pub const COMPLETE: &str = "mlflow-rs.migration.complete";
+
+/// Copies experiments and their runs including params, tags, full metric histories
+/// and artifacts, keeping the start and end times and the status of every run.
+///
+/// Experiments are matched by name and created on the target if they are missing.
+/// Every copied run is tagged with [`SOURCE_RUN_ID`] and [`SOURCE_EXPERIMENT_ID`],
+/// and with [`COMPLETE`] once everything was copied. This makes migrations resumable:
+/// complete copies are skipped, and incomplete ones are deleted and copied again.
+///
+/// Dataset inputs are not copied: the [`Client`] api has no way to read or log them yet,
+/// so copies of runs with inputs lack them.
+pub struct Migration<'a> {
+    source: &'a mut dyn Client,
+    target: &'a mut dyn Client,
+    experiments: Option<Vec<ExperimentId>>,
+    include_deleted: bool,
+    artifacts: bool,
+}
+
+/// What a migration did, or would do in a [dry run][Migration::plan].
+///
+/// It is displayed like a diff, e.g. `+ run 1a2b in 'exp': 2 params, ...`,
+/// where `+` marks copies, `~` incomplete copies which are replaced and `=` complete copies.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub experiments: Vec<ExperimentReport>,
+    pub runs: Vec<RunReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExperimentReport {
+    pub name: String,
+    pub source: ExperimentId,
+    /// The id on the target, which is missing if the experiment is not created in a dry run.
+    pub target: Option<ExperimentId>,
+    pub created: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunReport {
+    pub source: RunId,
+    /// The name of the experiment of the run.
+    pub experiment: String,
+    pub action: RunAction,
+    /// The id of the copy, which is missing if the run is not copied in a dry run.
+    pub target: Option<RunId>,
+    pub params: usize,
+    pub tags: usize,
+    /// The number of metrics, whose full histories are copied.
+    pub metrics: usize,
+    /// The number of artifact files, or of top-level artifacts in a dry run,
+    /// which does not list the artifact directories.
+    pub artifacts: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RunAction {
+    /// The run has not been copied yet.
+    Copy,
+    /// An earlier migration was interrupted while copying the run, so its copy is replaced.
+    Recopy,
+    /// The run has already been copied completely.
+    Skip,
+}
+
+impl Report {
+    /// The number of runs with the given action.
+    pub fn count(&self, action: RunAction) -> usize {
+        self.runs.iter().filter(|run| run.action == action).count()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for experiment in &self.experiments {
+            let sign = if experiment.created { '+' } else { '=' };
+            write!(
+                f,
+                "{} experiment '{}' ({}",
+                sign,
+                experiment.name,
+                experiment.source.as_ref()
+            )?;
+            if let Some(target) = &experiment.target {
+                write!(f, " -> {}", target.as_ref())?;
+            }
+            writeln!(f, ")")?;
+        }
+        for run in &self.runs {
+            let sign = match run.action {
+                RunAction::Copy => '+',
+                RunAction::Recopy => '~',
+                RunAction::Skip => '=',
+            };
+            write!(
+                f,
+                "{} run {} in '{}'",
+                sign,
+                run.source.as_ref(),
+                run.experiment
+            )?;
+            if let Some(target) = &run.target {
+                write!(f, " -> {}", target.as_ref())?;
+            }
+            if run.action == RunAction::Skip {
+                writeln!(f, ": already copied")?;
+            } else {
+                writeln!(
+                    f,
+                    ": {} params, {} tags, {} metrics, {} artifacts",
+                    run.params, run.tags, run.metrics, run.artifacts
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Everything which is copied for a run besides its params.
+struct Contents {
+    tags: Vec<RunTag>,
+    metrics: Vec<Metric<'static>>,
+    artifacts: Vec<String>,
+}
+
+/// A copy of a run made by an earlier migration.
+struct Existing {
+    info: RunInfo,
+    complete: bool,
+}
+
+fn tag<'r>(run: &'r Run, key: &str) -> Option<&'r str> {
+    let tags = run.data.tags.as_deref().unwrap_or_default();
+    tags.iter()
+        .find(|tag| tag.key == key)
+        .map(|tag| tag.value.as_str())
+}
+
+fn run_tag(key: &str, value: &str) -> RunTag {
+    RunTag {
+        key: key.to_string(),
+        value: value.to_string(),
+    }
+}
+
+/// How many ancestors of the run are part of the migration, so parents can be copied first.
+fn depth(run: &Run, parents: &HashMap<&str, &str>) -> usize {
+    let mut depth = 0;
+    let mut id = run.info.run_id.as_ref();
+    while let Some(parent) = parents.get(id) {
+        depth += 1;
+        id = parent;
+        if depth > parents.len() {
+            break;
+        }
+    }
+    depth
+}
+
+impl<'a> Migration<'a> {
+    /// Migrates all active experiments with all of their active runs and artifacts.
+    pub fn new(source: &'a mut dyn Client, target: &'a mut dyn Client) -> Self {
+        Migration {
+            source,
+            target,
+            experiments: None,
+            include_deleted: false,
+            artifacts: true,
+        }
+    }
+
+    /// Only migrates the experiments with the given ids on the source.
+    pub fn experiments(mut self, ids: impl IntoIterator<Item = ExperimentId>) -> Self {
+        self.experiments = Some(ids.into_iter().collect());
+        self
+    }
+
+    /// Also migrates deleted experiments and runs, which are deleted on the target after copying.
+    pub fn include_deleted(mut self, include_deleted: bool) -> Self {
+        self.include_deleted = include_deleted;
+        self
+    }
+
+    /// Whether artifacts are copied, defaults to `true`.
+    pub fn artifacts(mut self, artifacts: bool) -> Self {
+        self.artifacts = artifacts;
+        self
+    }
+
+    /// Reports what [`run`][Migration::run] would do, without changing the target.
+    pub fn plan(&mut self) -> Result<Report, StorageError> {
+        self.migrate(true)
+    }
+
+    /// Copies everything which has not been copied completely yet.
+    pub fn run(&mut self) -> Result<Report, StorageError> {
+        self.migrate(false)
+    }
+
+    fn view_type(&self) -> ViewType {
+        if self.include_deleted {
+            ViewType::All
+        } else {
+            ViewType::Active
+        }
+    }
+
+    fn migrate(&mut self, dry_run: bool) -> Result<Report, StorageError> {
+        let mut report = Report::default();
+        let mut experiments = self.source.list_experiments(self.view_type())?;
+        if let Some(ids) = &self.experiments {
+            experiments.retain(|e| ids.contains(&e.experiment_id));
+        }
+        for experiment in experiments {
+            self.migrate_experiment(&experiment, dry_run, &mut report)
+                .with_context(|| format!("failed to migrate the experiment {}", experiment.name))?;
+        }
+        Ok(report)
+    }
+
+    fn migrate_experiment(
+        &mut self,
+        experiment: &Experiment,
+        dry_run: bool,
+        report: &mut Report,
+    ) -> Result<(), StorageError> {
+        let deleted = experiment.lifecycle_stage == "deleted";
+        let existing = match self.target.get_experiment_by_name(&experiment.name) {
+            Ok(existing) => Some(existing),
+            Err(GetError::DoesNotExist(_)) => None,
+            Err(error) => return Err(error.into()),
+        };
+        if let Some(existing) = &existing {
+            if existing.lifecycle_stage == "deleted" && !deleted {
+                return Err(anyhow!("the experiment is deleted on the target"));
+            }
+        }
+        let created = existing.is_none();
+        let target = match existing {
+            Some(existing) => Some(existing.experiment_id),
+            None if dry_run => None,
+            None => Some(self.target.create_experiment(&experiment.name)?),
+        };
+        report.experiments.push(ExperimentReport {
+            name: experiment.name.clone(),
+            source: experiment.experiment_id.clone(),
+            target: target.clone(),
+            created,
+        });
+
+        let mut copies = HashMap::new();
+        if let Some(target) = &target {
+            let runs = self.target.runs(&[target]).view_type(ViewType::All).iter();
+            for run in runs {
+                let run = run?;
+                let source = match tag(&run, SOURCE_RUN_ID) {
+                    Some(source) => RunId::from(source),
+                    None => continue,
+                };
+                let complete = tag(&run, COMPLETE).is_some();
+                // Incomplete copies are deleted once they are replaced.
+                let replaced = !complete && run.info.lifecycle_stage == "deleted";
+                let known = copies.get(&source).is_some_and(|c: &Existing| c.complete);
+                if !replaced && !known {
+                    let info = run.info.clone();
+                    copies.insert(source, Existing { info, complete });
+                }
+            }
+        }
+
+        let view_type = self.view_type();
+        let runs = self.source.runs(&[&experiment.experiment_id]);
+        let runs = runs
+            .view_type(view_type)
+            .iter()
+            .collect::<Result<Vec<_>, _>>()?;
+        let parents = runs
+            .iter()
+            .filter_map(|run| Some((run.info.run_id.as_ref(), tag(run, tags::PARENT_RUN_ID)?)))
+            .collect::<HashMap<_, _>>();
+        let keys = runs
+            .iter()
+            .map(|run| (depth(run, &parents), run.info.start_time))
+            .collect::<Vec<_>>();
+        let mut runs = keys.into_iter().zip(runs).collect::<Vec<_>>();
+        runs.sort_by_key(|(key, _)| *key);
+
+        for (_, run) in &runs {
+            let copy = copies.get(&run.info.run_id);
+            let action = match copy {
+                Some(copy) if copy.complete => RunAction::Skip,
+                Some(_) => RunAction::Recopy,
+                None => RunAction::Copy,
+            };
+            let mut run_report = RunReport {
+                source: run.info.run_id.clone(),
+                experiment: experiment.name.clone(),
+                action,
+                target: copy.map(|copy| copy.info.run_id.clone()),
+                params: 0,
+                tags: 0,
+                metrics: 0,
+                artifacts: 0,
+            };
+            if action != RunAction::Skip {
+                run_report.params = run.data.params.as_ref().map_or(0, Vec::len);
+                run_report.metrics = run.data.metrics.as_ref().map_or(0, Vec::len);
+                if dry_run {
+                    // Only counts what a copy would contain, without fetching it.
+                    run_report.tags = copied_tags(run, &copies).len();
+                    if self.artifacts {
+                        run_report.artifacts = self.source.list_artifacts(&run.info, "")?.len();
+                    }
+                } else {
+                    let contents = self.contents(run, &copies)?;
+                    run_report.tags = contents.tags.len();
+                    run_report.artifacts = contents.artifacts.len();
+                    if let Some(copy) = copy {
+                        self.target.delete_run(&copy.info.run_id)?;
+                    }
+                    let target = target.as_ref().expect("the experiment exists");
+                    let info = self.copy_run(run, target, &contents).with_context(|| {
+                        format!("failed to copy the run {}", run.info.run_id.as_ref())
+                    })?;
+                    run_report.target = Some(info.run_id.clone());
+                    copies.insert(
+                        run.info.run_id.clone(),
+                        Existing {
+                            info,
+                            complete: true,
+                        },
+                    );
+                }
+            }
+            report.runs.push(run_report);
+        }
+
+        if deleted && !dry_run {
+            let target = target.as_ref().expect("the experiment exists");
+            if self.target.get_experiment(target)?.lifecycle_stage != "deleted" {
+                self.target.delete_experiment(target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the tags, metric histories and artifact paths of the source run.
+    fn contents(
+        &mut self,
+        run: &Run,
+        copies: &HashMap<RunId, Existing>,
+    ) -> Result<Contents, StorageError> {
+        let tags = copied_tags(run, copies);
+        let mut metrics = Vec::new();
+        for metric in run.data.metrics.iter().flatten() {
+            metrics.extend(
+                self.source
+                    .get_metric_history(&run.info.run_id, &metric.key)?,
+            );
+        }
+        let mut artifacts = Vec::new();
+        if self.artifacts {
+            list_files(&mut *self.source, &run.info, "", &mut artifacts)?;
+        }
+        Ok(Contents {
+            tags,
+            metrics,
+            artifacts,
+        })
+    }
+
+    fn copy_run(
+        &mut self,
+        run: &Run,
+        experiment: &ExperimentId,
+        contents: &Contents,
+    ) -> Result<RunInfo, StorageError> {
+        let source = &run.info;
+        // The run is findable by its source tags right away, so an interrupted copy is replaced.
+        let mut initial = vec![
+            run_tag(SOURCE_RUN_ID, source.run_id.as_ref()),
+            run_tag(SOURCE_EXPERIMENT_ID, source.experiment_id.as_ref()),
+        ];
+        let shown = [tags::RUN_NAME, tags::USER];
+        initial.extend(
+            contents
+                .tags
+                .iter()
+                .filter(|tag| shown.contains(&tag.key.as_str()))
+                .cloned(),
+        );
+        let info = self
+            .target
+            .create_run(experiment, source.start_time, &initial)?
+            .info;
+
+        let params = run.data.params.as_deref().unwrap_or_default();
+        let (_, result) = log_batches(
+            &mut *self.target,
+            &info.run_id,
+            &contents.metrics,
+            params,
+            &contents.tags,
+        );
+        result?;
+        for path in &contents.artifacts {
+            let artifact = self.source.download_artifact(source, path)?;
+            self.target.upload_artifact(&info, path, &artifact)?;
+        }
+        let info = self
+            .target
+            .update_run(&info.run_id, source.status, source.end_time)?;
+        self.target
+            .log_batch(&info.run_id, &[], &[], &[run_tag(COMPLETE, "true")])?;
+        if source.lifecycle_stage == "deleted" {
+            self.target.delete_run(&info.run_id)?;
+        }
+        Ok(info)
+    }
+}
+
+/// The tags of the copy of the run, pointing to the copy of its parent.
+fn copied_tags(run: &Run, copies: &HashMap<RunId, Existing>) -> Vec<RunTag> {
+    let own = [SOURCE_RUN_ID, SOURCE_EXPERIMENT_ID, COMPLETE];
+    let mut tags = Vec::new();
+    for tag in run.data.tags.iter().flatten() {
+        if own.contains(&tag.key.as_str()) {
+            continue;
+        }
+        let mut tag = tag.clone();
+        if tag.key == tags::PARENT_RUN_ID {
+            if let Some(parent) = copies.get(&RunId::from(tag.value.as_str())) {
+                tag.value = parent.info.run_id.as_ref().to_string();
+            }
+        }
+        tags.push(tag);
+    }
+    tags
+}
+
+/// Collects the paths of all files below the artifact directory `dir` of the run.
+pub(crate) fn list_files(
+    client: &mut dyn Client,
+    run: &RunInfo,
+    dir: &str,
+    files: &mut Vec<String>,
+) -> Result<(), StorageError> {
+    for file in client.list_artifacts(run, dir)? {
+        if file.is_dir {
+            list_files(client, run, &file.path, files)?;
+        } else {
+            files.push(file.path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Migration, RunAction, SOURCE_RUN_ID};
+    use crate::{
+        api::{
+            run::{RunStatus, RunTag},
+            search::SearchRunsRequest,
+            tags,
+        },
+        backend::memory::Memory,
+        Client,
+    };
+
+    #[test]
+    fn copy_resume_and_plan() {
+        let mut source = Memory::new();
+        let experiment = source.create_experiment("exp").unwrap();
+        let parent = source.create_run(&experiment, 10, &[]).unwrap().info;
+        let parent_tag = RunTag {
+            key: tags::PARENT_RUN_ID.to_string(),
+            value: parent.run_id.as_ref().to_string(),
+        };
+        let child = source
+            .create_run(&experiment, 5, &[parent_tag])
+            .unwrap()
+            .info;
+        for step in 0..3 {
+            source
+                .log_metric(&child.run_id, "loss", 1.0 / (step + 1) as f64, 20, step)
+                .unwrap();
+        }
+        source.log_param(&child.run_id, "lr", "0.1").unwrap();
+        source
+            .upload_artifact(&child, "model/weights.bin", b"123")
+            .unwrap();
+        source
+            .update_run(&child.run_id, RunStatus::Failed, Some(30))
+            .unwrap();
+        source.delete_run(&parent.run_id).unwrap();
+
+        let mut target = Memory::new();
+        let plan = Migration::new(&mut source, &mut target).plan().unwrap();
+        assert_eq!(plan.count(RunAction::Copy), 1);
+        assert!(plan.to_string().contains("+ experiment 'exp'"), "{}", plan);
+        assert_eq!((plan.runs[0].metrics, plan.runs[0].artifacts), (1, 1));
+        assert!(target.get_experiment_by_name("exp").is_err());
+
+        // Simulates an interrupted migration, which left behind an incomplete copy.
+        let copied = target.create_experiment("exp").unwrap();
+        let tag = RunTag {
+            key: SOURCE_RUN_ID.to_string(),
+            value: child.run_id.as_ref().to_string(),
+        };
+        target.create_run(&copied, 5, &[tag]).unwrap();
+        let report = Migration::new(&mut source, &mut target)
+            .include_deleted(true)
+            .run()
+            .unwrap();
+        assert_eq!(report.count(RunAction::Copy), 1);
+        assert_eq!(report.count(RunAction::Recopy), 1);
+        let child_report = report
+            .runs
+            .iter()
+            .find(|r| r.source == child.run_id)
+            .unwrap();
+        assert_eq!((child_report.metrics, child_report.artifacts), (1, 1));
+
+        let search =
+            SearchRunsRequest::new(std::slice::from_ref(&copied)).filter("params.lr = '0.1'");
+        let copy = target.search_runs(&search).unwrap().runs.remove(0);
+        assert_eq!((copy.info.start_time, copy.info.end_time), (5, Some(30)));
+        assert_eq!(copy.info.status, RunStatus::Failed);
+        assert_eq!(
+            target
+                .get_metric_history(&copy.info.run_id, "loss")
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(
+            target.artifact(&copy.info.run_id, "model/weights.bin"),
+            Some(&b"123"[..])
+        );
+        let parent_copy = report
+            .runs
+            .iter()
+            .find(|r| r.source == parent.run_id)
+            .unwrap();
+        let parent_copy = parent_copy.target.clone().unwrap();
+        let copied_parent = copy
+            .data
+            .tags
+            .unwrap()
+            .into_iter()
+            .find(|t| t.key == tags::PARENT_RUN_ID);
+        assert_eq!(copied_parent.unwrap().value, parent_copy.as_ref());
+        assert_eq!(
+            target.get_run(&parent_copy).unwrap().info.lifecycle_stage,
+            "deleted"
+        );
+
+        let again = Migration::new(&mut source, &mut target)
+            .include_deleted(true)
+            .run()
+            .unwrap();
+        assert_eq!(again.count(RunAction::Skip), 2);
+        assert!(again.to_string().contains("= run"), "{}", again);
+    }
+}
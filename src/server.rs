@@ -0,0 +1,21 @@
+//! Serving the MLflow REST API on top of any [`Client`][crate::Client], enabled by the `server` feature.
+//!
+//! ```no_run
+//! use std::sync::Mutex;
+//! use mlflow::{backend::memory::Memory, server};
+//!
+//! let store = Mutex::new(Memory::new());
+//! let http = server::HttpServer::bind("127.0.0.1:5000", move |request| {
+//!     server::handle(&mut *store.lock().unwrap(), request)
+//! })
+//! .unwrap();
+//! println!("listening on {}", http.local_addr());
+//! ```
+
+mod artifacts;
+mod http;
+mod routes;
+
+pub use artifacts::ArtifactStore;
+pub use http::{HttpServer, Request, Response};
+pub use routes::handle;
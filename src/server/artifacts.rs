@@ -0,0 +1,195 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use serde_json::json;
+
+use super::http::{Request, Response};
+use crate::api::artifact::{validate_path, write_local};
+
+/// The prefixes of the artifact proxy, followed by the path below `mlflow-artifacts:/`.
+pub(crate) const PREFIXES: [&str; 2] = [
+    "/api/2.0/mlflow-artifacts/artifacts",
+    "/ajax-api/2.0/mlflow-artifacts/artifacts",
+];
+
+/// Serves the `mlflow-artifacts` proxy from a local directory,
+/// like `mlflow server --artifacts-destination`.
+///
+/// Artifacts are uploaded with `PUT`, downloaded with `GET` and removed with `DELETE`
+/// on their path, and listed with `GET` and the directory as the `path` query parameter.
+#[derive(Debug, Clone)]
+pub struct ArtifactStore {
+    root: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct ListArtifacts {
+    path: Option<String>,
+}
+
+impl ArtifactStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ArtifactStore { root: root.into() }
+    }
+
+    /// Answers requests to the artifact proxy, and returns `None` for all other requests.
+    pub fn handle(&self, request: &Request) -> Option<Response> {
+        let path = PREFIXES
+            .iter()
+            .find_map(|prefix| request.path.strip_prefix(prefix))?;
+        let path = match path {
+            "" | "/" => None,
+            path => Some(path.strip_prefix('/')?),
+        };
+        let response = match (request.method.as_str(), path) {
+            ("GET", None) => match serde_qs::from_str::<ListArtifacts>(&request.query) {
+                Ok(query) => self.list(query.path.as_deref().unwrap_or_default()),
+                Err(error) => Response::error(400, "INVALID_PARAMETER_VALUE", error.to_string()),
+            },
+            ("GET", Some(path)) => self.download(path),
+            ("PUT", Some(path)) => match write_local(&self.root, path, &request.body) {
+                Ok(()) => Response::json(200, &json!({})),
+                Err(error) => invalid(error),
+            },
+            ("DELETE", Some(path)) => self.delete(path),
+            (method, _) => Response::error(
+                405,
+                "BAD_REQUEST",
+                format!("{} is not allowed on artifacts", method),
+            ),
+        };
+        Some(response)
+    }
+
+    fn resolve(&self, path: &str) -> Result<PathBuf, Response> {
+        let path = path.trim_matches('/');
+        if path.is_empty() {
+            return Ok(self.root.clone());
+        }
+        validate_path(path).map_err(invalid)?;
+        Ok(self.root.join(path))
+    }
+
+    fn list(&self, path: &str) -> Response {
+        let dir = match self.resolve(path) {
+            Ok(dir) => dir,
+            Err(response) => return response,
+        };
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            // Like the official server, missing directories are empty.
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return Response::json(200, &json!({}))
+            }
+            Err(error) => return io_error(&dir, error),
+        };
+        let mut files = entries
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let metadata = entry.metadata().ok()?;
+                let name = entry.file_name().to_string_lossy().into_owned();
+                Some(if metadata.is_dir() {
+                    json!({ "path": name, "is_dir": true })
+                } else {
+                    json!({ "path": name, "is_dir": false, "file_size": metadata.len() })
+                })
+            })
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+        Response::json(200, &json!({ "files": files }))
+    }
+
+    fn download(&self, path: &str) -> Response {
+        let file = match self.resolve(path) {
+            Ok(file) => file,
+            Err(response) => return response,
+        };
+        match fs::read(&file) {
+            Ok(contents) => Response {
+                status: 200,
+                content_type: "application/octet-stream".to_string(),
+                body: contents,
+            },
+            Err(error) => io_error(&file, error),
+        }
+    }
+
+    fn delete(&self, path: &str) -> Response {
+        let target = match self.resolve(path) {
+            Ok(target) if target != self.root => target,
+            Ok(_) => return invalid("the artifact root can not be deleted"),
+            Err(response) => return response,
+        };
+        let removed = if target.is_dir() {
+            fs::remove_dir_all(&target)
+        } else {
+            fs::remove_file(&target)
+        };
+        match removed {
+            Ok(()) => Response::json(200, &json!({})),
+            Err(error) => io_error(&target, error),
+        }
+    }
+}
+
+fn invalid(error: impl std::fmt::Display) -> Response {
+    Response::error(400, "INVALID_PARAMETER_VALUE", error.to_string())
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> Response {
+    let message = format!("{}: {}", path.display(), error);
+    if error.kind() == ErrorKind::NotFound {
+        Response::error(404, "RESOURCE_DOES_NOT_EXIST", message)
+    } else {
+        Response::error(500, "INTERNAL_ERROR", message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArtifactStore;
+    use crate::server::Request;
+
+    fn request(method: &str, path: &str, query: &str, body: &[u8]) -> Request {
+        Request {
+            method: method.to_string(),
+            path: format!("/api/2.0/mlflow-artifacts/artifacts{}", path),
+            query: query.to_string(),
+            headers: Vec::new(),
+            body: body.to_vec(),
+        }
+    }
+
+    #[test]
+    fn upload_list_download_and_delete() {
+        let root = std::env::temp_dir().join(format!("mlflow-artifacts-{}", std::process::id()));
+        let store = ArtifactStore::new(&root);
+        let handle = |request| store.handle(&request).unwrap();
+
+        let upload = handle(request("PUT", "/0/run/artifacts/a.txt", "", b"abc"));
+        assert_eq!(upload.status, 200);
+        let list = handle(request("GET", "", "path=0/run/artifacts", b""));
+        let files: serde_json::Value = serde_json::from_slice(&list.body).unwrap();
+        assert_eq!(files["files"][0]["path"], "a.txt");
+        assert_eq!(files["files"][0]["file_size"], 3);
+        let download = handle(request("GET", "/0/run/artifacts/a.txt", "", b""));
+        assert_eq!(download.body, b"abc");
+        assert_eq!(handle(request("GET", "/../secret", "", b"")).status, 400);
+        assert_eq!(handle(request("DELETE", "/0/run", "", b"")).status, 200);
+        assert_eq!(
+            handle(request("GET", "/0/run/artifacts/a.txt", "", b"")).status,
+            404
+        );
+        assert!(store
+            .handle(&Request {
+                path: "/api/2.0/mlflow/runs/get".into(),
+                ..request("GET", "", "", b"")
+            })
+            .is_none());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
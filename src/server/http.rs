@@ -0,0 +1,445 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// A HTTP request, whose body was read completely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// The percent-decoded path, e.g. `/api/2.0/mlflow/runs/get`.
+    pub path: String,
+    /// The raw query string without the leading `?`.
+    pub query: String,
+    /// The headers with lowercase names.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        let header = self.headers.iter().find(|(key, _)| *key == name);
+        header.map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, value: &serde_json::Value) -> Self {
+        Response {
+            status,
+            content_type: "application/json".to_string(),
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    /// An error in the format of MLflow, e.g. with the code `RESOURCE_DOES_NOT_EXIST`.
+    pub fn error(status: u16, error_code: &str, message: impl Into<String>) -> Self {
+        let body = serde_json::json!({
+            "error_code": error_code,
+            "message": message.into(),
+        });
+        Response::json(status, &body)
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+/// The largest request body which is accepted, larger ones are answered with `413`.
+const MAX_BODY_SIZE: usize = 16 << 20;
+/// The largest artifact upload which is accepted.
+const MAX_ARTIFACT_SIZE: usize = 1 << 30;
+/// The path of artifact uploads, which are allowed to be larger than other requests.
+const ARTIFACTS_PATH: &str = "/mlflow-artifacts/artifacts/";
+/// The longest request line or header line which is accepted, longer ones are answered with `431`.
+const MAX_LINE_LENGTH: usize = 8 << 10;
+const MAX_HEADERS: usize = 100;
+
+/// The open connections by their number, to close them when the server stops.
+type Connections = Mutex<HashMap<u64, TcpStream>>;
+
+/// A small HTTP/1.1 server, which calls the handler for each request on a thread per connection.
+///
+/// The server stops when it is dropped or [shut down][HttpServer::shutdown].
+/// Request bodies larger than 16 MiB, or 1 GiB for artifact uploads, are rejected with
+/// `413 Payload Too Large`, and lines longer than 8 KiB or more than 100 headers
+/// with `431 Request Header Fields Too Large`.
+pub struct HttpServer {
+    addr: SocketAddr,
+    running: Arc<AtomicBool>,
+    connections: Arc<Connections>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+    /// Listens on `addr`, where port `0` picks a free port.
+    pub fn bind(
+        addr: impl ToSocketAddrs,
+        handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let running = Arc::new(AtomicBool::new(true));
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let thread = {
+            let running = running.clone();
+            let connections = connections.clone();
+            thread::spawn(move || accept(listener, &running, connections, handler))
+        };
+        Ok(HttpServer {
+            addr,
+            running,
+            connections,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Blocks the current thread while the server is running.
+    pub fn join(mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
+    /// Stops accepting connections and closes the open ones.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        // Wake up the accepting thread, which then notices that the server stopped.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        for (_, connection) in self.connections.lock().unwrap().drain() {
+            let _ = connection.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn accept(
+    listener: TcpListener,
+    running: &AtomicBool,
+    connections: Arc<Connections>,
+    handler: Arc<Handler>,
+) {
+    for (number, stream) in (0..).zip(listener.incoming()) {
+        if !running.load(Ordering::SeqCst) {
+            break;
+        }
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        if let Ok(clone) = stream.try_clone() {
+            connections.lock().unwrap().insert(number, clone);
+        }
+        let handler = handler.clone();
+        let connections = connections.clone();
+        thread::spawn(move || {
+            let _ = serve(stream, handler.as_ref());
+            connections.lock().unwrap().remove(&number);
+        });
+    }
+}
+
+/// Answers the requests on a connection until it is closed.
+fn serve(stream: TcpStream, handler: &Handler) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(error) => {
+                // The rest of the request is not read, so the connection cannot be reused.
+                let limit = error
+                    .get_ref()
+                    .and_then(|error| error.downcast_ref::<Limit>());
+                if let Some(limit) = limit {
+                    let response = Response::error(
+                        limit.status,
+                        "INVALID_PARAMETER_VALUE",
+                        limit.message.clone(),
+                    );
+                    write_response(&mut writer, &response)?;
+                    // Reads a bit of what the client is still sending before closing,
+                    // so the response is not lost to a connection reset.
+                    writer.shutdown(Shutdown::Write)?;
+                    writer.set_read_timeout(Some(Duration::from_secs(1)))?;
+                    let _ = io::copy(&mut reader.take(1 << 20), &mut io::sink());
+                }
+                return Err(error);
+            }
+        };
+        let response = handler(&request);
+        write_response(&mut writer, &response)?;
+        if request
+            .header("connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"))
+        {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A request which exceeds a limit, which is answered with the status.
+#[derive(Debug)]
+struct Limit {
+    status: u16,
+    message: String,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Limit {}
+
+fn limit(status: u16, message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, Limit { status, message })
+}
+
+fn check_body_size(size: usize, max_size: usize) -> io::Result<()> {
+    if size > max_size {
+        let message = format!("the request body is limited to {} bytes", max_size);
+        return Err(limit(413, message));
+    }
+    Ok(())
+}
+
+/// Reads a line of at most [`MAX_LINE_LENGTH`] bytes, which is empty at the end of the input.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = Vec::new();
+    let max_length = MAX_LINE_LENGTH as u64 + 1;
+    reader.take(max_length).read_until(b'\n', &mut line)?;
+    if line.len() > MAX_LINE_LENGTH {
+        let message = format!("lines are limited to {} bytes", MAX_LINE_LENGTH);
+        return Err(limit(431, message));
+    }
+    String::from_utf8(line).map_err(|_| invalid("the request is not valid UTF-8"))
+}
+
+/// Reads the next request, or `None` if the connection was closed.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let line = read_line(reader)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err(invalid("invalid request line")),
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            let message = format!("requests are limited to {} headers", MAX_HEADERS);
+            return Err(limit(431, message));
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid("invalid header"))?;
+        headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+    let header = |name: &str| headers.iter().find(|(key, _)| key == name).map(|(_, v)| v);
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let path = percent_decode(path);
+    let max_body_size = if path.contains(ARTIFACTS_PATH) {
+        MAX_ARTIFACT_SIZE
+    } else {
+        MAX_BODY_SIZE
+    };
+
+    let mut body = Vec::new();
+    if header("transfer-encoding").is_some_and(|value| value.eq_ignore_ascii_case("chunked")) {
+        loop {
+            let size = read_line(reader)?;
+            let size = size.trim().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size, 16).map_err(|_| invalid("invalid chunk"))?;
+            if size == 0 {
+                // Skip the trailers.
+                while read_line(reader)?.len() > 2 {}
+                break;
+            }
+            let start = body.len();
+            check_body_size(start.saturating_add(size), max_body_size)?;
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            read_line(reader)?;
+        }
+    } else if let Some(length) = header("content-length") {
+        let length = length
+            .parse()
+            .map_err(|_| invalid("invalid content length"))?;
+        check_body_size(length, max_body_size)?;
+        // The body grows as it arrives, instead of trusting the length up front.
+        reader.by_ref().take(length as u64).read_to_end(&mut body)?;
+        if body.len() != length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+    }
+
+    Ok(Some(Request {
+        method,
+        path,
+        query: query.to_string(),
+        headers,
+        body,
+    }))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok());
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) if bytes[i] == b'%' => {
+                decoded.push(byte);
+                i += 3;
+            }
+            _ => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn write_response(writer: &mut impl Write, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+        response.status,
+        reason,
+        response.content_type,
+        response.body.len()
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpStream,
+        thread,
+        time::Duration,
+    };
+
+    use super::{percent_decode, read_request, HttpServer, Response};
+
+    #[test]
+    fn read_chunked_request() {
+        let raw = "POST /a%20b?x=1 HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        let request = read_request(&mut raw.as_bytes()).unwrap().unwrap();
+        assert_eq!(request.path, "/a b");
+        assert_eq!(request.query, "x=1");
+        assert_eq!(request.body, b"abcde");
+        assert_eq!(percent_decode("%zz%41"), "%zzA");
+    }
+
+    #[test]
+    fn reject_large_bodies_and_forget_closed_connections() {
+        let server =
+            HttpServer::bind("127.0.0.1:0", |_| Response::json(200, &"ok".into())).unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream
+            .write_all(b"POST / HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"), "{}", response);
+        drop(stream);
+
+        for _ in 0..100 {
+            if server.connections.lock().unwrap().is_empty() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the closed connection is still tracked");
+    }
+
+    #[test]
+    fn reject_long_lines_and_many_headers() {
+        let server =
+            HttpServer::bind("127.0.0.1:0", |_| Response::json(200, &"ok".into())).unwrap();
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10_000));
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(101));
+        let large_body =
+            "POST /api/2.0/mlflow/runs/log-batch HTTP/1.1\r\nContent-Length: 20000000\r\n\r\n";
+        for (request, status) in [
+            (long_line.as_str(), "431"),
+            (many_headers.as_str(), "431"),
+            (large_body, "413"),
+        ] {
+            let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            assert!(
+                response.starts_with(&format!("HTTP/1.1 {}", status)),
+                "{}",
+                response
+            );
+        }
+    }
+}
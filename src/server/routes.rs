@@ -0,0 +1,588 @@
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use super::{
+    artifacts,
+    http::{Request, Response},
+};
+use crate::{
+    api::{
+        artifact::ArtifactLocation,
+        client::{Client, ViewType},
+        error::{BatchError, CreateError, GetError, StorageError},
+        run::{Metric, Param, RunInfo, RunStatus, RunTag},
+        search::{PageToken, SearchRunsRequest, DEFAULT_MAX_RESULTS},
+        tags,
+    },
+    backend::rest::RestError,
+    ExperimentId, RunId,
+};
+
+/// The prefixes of the REST API, where the UI uses `ajax-api`.
+const PREFIXES: [&str; 2] = ["/api/2.0/mlflow/", "/ajax-api/2.0/mlflow/"];
+
+/// Answers a request to the MLflow REST API using the client.
+///
+/// The endpoints are the ones of the official tracking server. Responses omit empty lists and
+/// missing values like the official server does. Errors of the client are mapped to the MLflow
+/// error codes, e.g. [`GetError::DoesNotExist`] to `RESOURCE_DOES_NOT_EXIST`.
+pub fn handle(client: &mut dyn Client, request: &Request) -> Response {
+    let artifact = artifacts::PREFIXES
+        .iter()
+        .find_map(|prefix| request.path.strip_prefix(prefix)?.strip_prefix('/'));
+    if let Some(path) = artifact {
+        return match request.method.as_str() {
+            "PUT" => upload_artifact(client, path, &request.body).unwrap_or_else(|error| error),
+            "GET" => download_artifact(client, path).unwrap_or_else(|error| error),
+            _ => Response::error(
+                405,
+                "BAD_REQUEST",
+                format!("{} is not allowed on artifacts", request.method),
+            ),
+        };
+    }
+    let endpoint = PREFIXES
+        .iter()
+        .find_map(|prefix| request.path.strip_prefix(prefix));
+    let endpoint = match endpoint {
+        Some(endpoint) => endpoint.trim_end_matches('/'),
+        None => return not_found(request),
+    };
+    let result = match (request.method.as_str(), endpoint) {
+        ("POST", "experiments/create") => create_experiment(client, request),
+        ("GET", "experiments/get") => get_experiment(client, request),
+        ("GET", "experiments/get-by-name") => get_experiment_by_name(client, request),
+        ("GET", "experiments/list")
+        | ("GET", "experiments/search")
+        | ("POST", "experiments/search") => list_experiments(client, request),
+        ("POST", "experiments/delete") => delete_experiment(client, request),
+        ("POST", "experiments/restore") => restore_experiment(client, request),
+        ("POST", "experiments/update") => update_experiment(client, request),
+        ("POST", "runs/create") => create_run(client, request),
+        ("POST", "runs/delete") => delete_run(client, request),
+        ("POST", "runs/restore") => restore_run(client, request),
+        ("GET", "runs/get") => get_run(client, request),
+        ("POST", "runs/update") => update_run(client, request),
+        ("POST", "runs/search") => search_runs(client, request),
+        ("GET", "metrics/get-history") => get_metric_history(client, request),
+        ("POST", "runs/log-parameter") => log_param(client, request),
+        ("POST", "runs/log-metric") => log_metric(client, request),
+        ("POST", "runs/set-tag") => set_tag(client, request),
+        ("POST", "runs/log-batch") => log_batch(client, request),
+        (_, endpoint) if is_endpoint(endpoint) => Err(Response::error(
+            405,
+            "BAD_REQUEST",
+            format!("{} is not allowed on {}", request.method, request.path),
+        )),
+        _ => return not_found(request),
+    };
+    match result {
+        Ok(mut value) => {
+            omit_empty(&mut value);
+            Response::json(200, &value)
+        }
+        Err(response) => response,
+    }
+}
+
+fn is_endpoint(endpoint: &str) -> bool {
+    const ENDPOINTS: [&str; 19] = [
+        "experiments/create",
+        "experiments/get",
+        "experiments/get-by-name",
+        "experiments/list",
+        "experiments/search",
+        "experiments/delete",
+        "experiments/restore",
+        "experiments/update",
+        "runs/create",
+        "runs/delete",
+        "runs/restore",
+        "runs/get",
+        "runs/update",
+        "runs/search",
+        "metrics/get-history",
+        "runs/log-parameter",
+        "runs/log-metric",
+        "runs/set-tag",
+        "runs/log-batch",
+    ];
+    ENDPOINTS.contains(&endpoint)
+}
+
+fn not_found(request: &Request) -> Response {
+    let message = format!("no endpoint {} {}", request.method, request.path);
+    Response::error(404, "ENDPOINT_NOT_FOUND", message)
+}
+
+/// Removes empty lists and `null`s, which the official server never sends.
+fn omit_empty(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            object.retain(|_, value| {
+                omit_empty(value);
+                !value.is_null() && value.as_array().is_none_or(|array| !array.is_empty())
+            });
+        }
+        Value::Array(array) => array.iter_mut().for_each(omit_empty),
+        _ => {}
+    }
+}
+
+fn error_response(error: &StorageError) -> Response {
+    if let Some(error) = error.downcast_ref::<GetError>() {
+        return get_error(error);
+    }
+    if let Some(error) = error.downcast_ref::<CreateError>() {
+        return create_error(error);
+    }
+    if let Some(error) = error.downcast_ref::<BatchError>() {
+        return batch_error(error);
+    }
+    // Errors of a tracking server behind this one are passed on.
+    if let Some(RestError::Known {
+        status,
+        code,
+        message,
+    }) = error.downcast_ref::<RestError>()
+    {
+        return Response::error(*status, code.as_str(), message.clone());
+    }
+    Response::error(500, "INTERNAL_ERROR", format!("{:#}", error))
+}
+
+fn get_error(error: &GetError) -> Response {
+    match error {
+        GetError::DoesNotExist(_) => {
+            Response::error(404, "RESOURCE_DOES_NOT_EXIST", error.to_string())
+        }
+        GetError::Storage(error) => error_response(error),
+    }
+}
+
+fn create_error(error: &CreateError) -> Response {
+    match error {
+        CreateError::AlreadyExists(_) => {
+            Response::error(400, "RESOURCE_ALREADY_EXISTS", error.to_string())
+        }
+        CreateError::Storage(error) => error_response(error),
+    }
+}
+
+fn batch_error(error: &BatchError) -> Response {
+    match error {
+        BatchError::Storage(error) => error_response(error),
+        _ => Response::error(400, "INVALID_PARAMETER_VALUE", error.to_string()),
+    }
+}
+
+/// Parses the query string of `GET` requests and the JSON body of all others.
+fn parse<T: DeserializeOwned>(request: &Request) -> Result<T> {
+    let parsed = if request.method == "GET" {
+        serde_qs::from_str(&request.query).map_err(|error| error.to_string())
+    } else {
+        serde_json::from_slice(&request.body).map_err(|error| error.to_string())
+    };
+    parsed.map_err(|error| {
+        let message = format!("invalid request to {}: {}", request.path, error);
+        Response::error(400, "INVALID_PARAMETER_VALUE", message)
+    })
+}
+
+type Result<T = Value> = std::result::Result<T, Response>;
+
+#[derive(Deserialize)]
+struct CreateExperiment {
+    name: String,
+}
+
+fn create_experiment(client: &mut dyn Client, request: &Request) -> Result {
+    let body: CreateExperiment = parse(request)?;
+    let id = client
+        .create_experiment(&body.name)
+        .map_err(|e| create_error(&e))?;
+    Ok(json!({ "experiment_id": id }))
+}
+
+#[derive(Deserialize)]
+struct ExperimentById {
+    experiment_id: ExperimentId,
+}
+
+fn get_experiment(client: &mut dyn Client, request: &Request) -> Result {
+    let query: ExperimentById = parse(request)?;
+    let experiment = client
+        .get_experiment(&query.experiment_id)
+        .map_err(|e| get_error(&e))?;
+    Ok(json!({ "experiment": experiment }))
+}
+
+#[derive(Deserialize)]
+struct ExperimentByName {
+    experiment_name: String,
+}
+
+fn get_experiment_by_name(client: &mut dyn Client, request: &Request) -> Result {
+    let query: ExperimentByName = parse(request)?;
+    let experiment = client
+        .get_experiment_by_name(&query.experiment_name)
+        .map_err(|e| get_error(&e))?;
+    Ok(json!({ "experiment": experiment }))
+}
+
+#[derive(Deserialize)]
+struct ListExperiments {
+    view_type: Option<ViewType>,
+}
+
+fn list_experiments(client: &mut dyn Client, request: &Request) -> Result {
+    let query: ListExperiments = parse(request)?;
+    let view_type = query.view_type.unwrap_or(ViewType::Active);
+    let experiments = client
+        .list_experiments(view_type)
+        .map_err(|e| error_response(&e))?;
+    Ok(json!({ "experiments": experiments }))
+}
+
+fn delete_experiment(client: &mut dyn Client, request: &Request) -> Result {
+    let body: ExperimentById = parse(request)?;
+    client
+        .delete_experiment(&body.experiment_id)
+        .map_err(|e| get_error(&e))?;
+    Ok(json!({}))
+}
+
+fn restore_experiment(client: &mut dyn Client, request: &Request) -> Result {
+    let body: ExperimentById = parse(request)?;
+    client
+        .restore_experiment(&body.experiment_id)
+        .map_err(|e| get_error(&e))?;
+    Ok(json!({}))
+}
+
+#[derive(Deserialize)]
+struct UpdateExperiment {
+    experiment_id: ExperimentId,
+    new_name: Option<String>,
+}
+
+fn update_experiment(client: &mut dyn Client, request: &Request) -> Result {
+    let body: UpdateExperiment = parse(request)?;
+    client
+        .update_experiment(&body.experiment_id, body.new_name.as_deref())
+        .map_err(|e| error_response(&e))?;
+    Ok(json!({}))
+}
+
+#[derive(Deserialize)]
+struct CreateRun {
+    experiment_id: ExperimentId,
+    start_time: Option<i64>,
+    #[serde(default)]
+    tags: Vec<RunTag>,
+    run_name: Option<String>,
+    user_id: Option<String>,
+}
+
+fn create_run(client: &mut dyn Client, request: &Request) -> Result {
+    let mut body: CreateRun = parse(request)?;
+    // Newer clients send the name and user as fields instead of tags.
+    let fields = [(tags::RUN_NAME, body.run_name), (tags::USER, body.user_id)];
+    for (key, value) in fields.iter() {
+        if let Some(value) = value {
+            if !body.tags.iter().any(|tag| tag.key == *key) {
+                body.tags.push(RunTag {
+                    key: key.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+    let start_time = body.start_time.unwrap_or_else(crate::timestamp);
+    let run = client
+        .create_run(&body.experiment_id, start_time, &body.tags)
+        .map_err(|e| error_response(&e))?;
+    Ok(json!({ "run": run }))
+}
+
+#[derive(Deserialize)]
+struct RunById {
+    run_id: Option<RunId>,
+    /// The deprecated name of `run_id`, which older clients still send.
+    run_uuid: Option<RunId>,
+}
+
+impl RunById {
+    fn id(self) -> Result<RunId> {
+        self.run_id
+            .or(self.run_uuid)
+            .ok_or_else(|| Response::error(400, "INVALID_PARAMETER_VALUE", "missing run_id"))
+    }
+}
+
+fn delete_run(client: &mut dyn Client, request: &Request) -> Result {
+    let id = parse::<RunById>(request)?.id()?;
+    client.delete_run(&id).map_err(|e| get_error(&e))?;
+    Ok(json!({}))
+}
+
+fn restore_run(client: &mut dyn Client, request: &Request) -> Result {
+    let id = parse::<RunById>(request)?.id()?;
+    client.restore_run(&id).map_err(|e| get_error(&e))?;
+    Ok(json!({}))
+}
+
+fn get_run(client: &mut dyn Client, request: &Request) -> Result {
+    let id = parse::<RunById>(request)?.id()?;
+    let run = client.get_run(&id).map_err(|e| get_error(&e))?;
+    Ok(json!({ "run": run }))
+}
+
+#[derive(Deserialize)]
+struct UpdateRun {
+    #[serde(flatten)]
+    id: RunById,
+    status: RunStatus,
+    end_time: Option<i64>,
+}
+
+fn update_run(client: &mut dyn Client, request: &Request) -> Result {
+    let body: UpdateRun = parse(request)?;
+    let id = body.id.id()?;
+    let info = client
+        .update_run(&id, body.status, body.end_time)
+        .map_err(|e| get_error(&e))?;
+    Ok(json!({ "run_info": info }))
+}
+
+#[derive(Deserialize)]
+struct SearchRuns {
+    #[serde(default)]
+    experiment_ids: Vec<ExperimentId>,
+    #[serde(default)]
+    filter: String,
+    run_view_type: Option<ViewType>,
+    max_results: Option<i32>,
+    #[serde(default)]
+    order_by: Vec<String>,
+    page_token: Option<PageToken>,
+}
+
+fn search_runs(client: &mut dyn Client, request: &Request) -> Result {
+    let body: SearchRuns = parse(request)?;
+    let mut search = SearchRunsRequest::new(&body.experiment_ids)
+        .filter(body.filter)
+        .view_type(body.run_view_type.unwrap_or(ViewType::Active))
+        .max_results(body.max_results.unwrap_or(DEFAULT_MAX_RESULTS))
+        .page_token(body.page_token.filter(|token| !token.as_ref().is_empty()));
+    search.order_by = body.order_by;
+    let search = client
+        .search_runs(&search)
+        .map_err(|e| error_response(&e))?;
+    Ok(json!({
+        "runs": search.runs,
+        "next_page_token": Some(search.next_page_token).filter(|token| !token.as_ref().is_empty()),
+    }))
+}
+
+#[derive(Deserialize)]
+struct GetMetricHistory {
+    #[serde(flatten)]
+    id: RunById,
+    metric_key: String,
+}
+
+fn get_metric_history(client: &mut dyn Client, request: &Request) -> Result {
+    let query: GetMetricHistory = parse(request)?;
+    let id = query.id.id()?;
+    let metrics = client
+        .get_metric_history(&id, &query.metric_key)
+        .map_err(|e| get_error(&e))?;
+    Ok(json!({ "metrics": metrics }))
+}
+
+#[derive(Deserialize)]
+struct LogParam {
+    #[serde(flatten)]
+    id: RunById,
+    key: String,
+    value: String,
+}
+
+fn log_param(client: &mut dyn Client, request: &Request) -> Result {
+    let body: LogParam = parse(request)?;
+    let param = Param {
+        key: body.key,
+        value: body.value,
+    };
+    log(client, body.id.id()?, &[], &[param], &[])
+}
+
+fn set_tag(client: &mut dyn Client, request: &Request) -> Result {
+    let body: LogParam = parse(request)?;
+    let tag = RunTag {
+        key: body.key,
+        value: body.value,
+    };
+    log(client, body.id.id()?, &[], &[], &[tag])
+}
+
+/// A metric as sent by clients, which may leave out the step.
+#[derive(Deserialize)]
+struct LoggedMetric {
+    key: String,
+    #[serde(with = "crate::api::non_finite")]
+    value: f64,
+    timestamp: i64,
+    #[serde(default)]
+    step: i64,
+}
+
+impl From<LoggedMetric> for Metric<'static> {
+    fn from(metric: LoggedMetric) -> Self {
+        Metric {
+            key: metric.key.into(),
+            value: metric.value,
+            timestamp: metric.timestamp,
+            step: metric.step,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LogMetric {
+    #[serde(flatten)]
+    id: RunById,
+    #[serde(flatten)]
+    metric: LoggedMetric,
+}
+
+fn log_metric(client: &mut dyn Client, request: &Request) -> Result {
+    let body: LogMetric = parse(request)?;
+    log(client, body.id.id()?, &[body.metric.into()], &[], &[])
+}
+
+#[derive(Deserialize)]
+struct LogBatch {
+    run_id: RunId,
+    #[serde(default)]
+    metrics: Vec<LoggedMetric>,
+    #[serde(default)]
+    params: Vec<Param>,
+    #[serde(default)]
+    tags: Vec<RunTag>,
+}
+
+fn log_batch(client: &mut dyn Client, request: &Request) -> Result {
+    let body: LogBatch = parse(request)?;
+    let metrics = body
+        .metrics
+        .into_iter()
+        .map(Metric::from)
+        .collect::<Vec<_>>();
+    log(client, body.run_id, &metrics, &body.params, &body.tags)
+}
+
+fn log(
+    client: &mut dyn Client,
+    run: RunId,
+    metrics: &[Metric],
+    params: &[Param],
+    tags: &[RunTag],
+) -> Result {
+    client
+        .log_batch(&run, metrics, params, tags)
+        .map_err(|e| batch_error(&e))?;
+    Ok(json!({}))
+}
+
+/// The run of an artifact at `<experiment>/<run>/artifacts/<path>`, and the path below its artifacts.
+///
+/// Only artifacts below the `artifact_uri` of their run are accepted.
+fn artifact_of_run<'a>(client: &mut dyn Client, path: &'a str) -> Result<(RunInfo, &'a str)> {
+    let invalid = || {
+        let message = format!("the artifact path {} does not belong to a run", path);
+        Response::error(400, "INVALID_PARAMETER_VALUE", message)
+    };
+    let mut parts = path.splitn(4, '/');
+    let run = match (parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(run), Some("artifacts")) => RunId::from(run),
+        _ => return Err(invalid()),
+    };
+    let artifact = parts.next().ok_or_else(invalid)?;
+    let info = client.get_run(&run).map_err(|e| get_error(&e))?.info;
+    let root = match ArtifactLocation::parse(&info.artifact_uri) {
+        Ok(ArtifactLocation::Proxied { path, .. }) => path,
+        _ => return Err(invalid()),
+    };
+    if path
+        .strip_prefix(&root)
+        .and_then(|rest| rest.strip_prefix('/'))
+        != Some(artifact)
+    {
+        return Err(invalid());
+    }
+    Ok((info, artifact))
+}
+
+/// Stores an artifact using the run of its path.
+fn upload_artifact(client: &mut dyn Client, path: &str, contents: &[u8]) -> Result<Response> {
+    let (info, artifact) = artifact_of_run(client, path)?;
+    client
+        .upload_artifact(&info, artifact, contents)
+        .map_err(|e| error_response(&e))?;
+    Ok(Response::json(200, &json!({})))
+}
+
+fn download_artifact(client: &mut dyn Client, path: &str) -> Result<Response> {
+    let (info, artifact) = artifact_of_run(client, path)?;
+    let contents = client
+        .download_artifact(&info, artifact)
+        .map_err(|e| error_response(&e))?;
+    Ok(Response {
+        status: 200,
+        content_type: "application/octet-stream".to_string(),
+        body: contents,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::handle;
+    use crate::{backend::memory::Memory, server::Request};
+
+    fn request(method: &str, path: &str, query: &str, body: &str) -> Request {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: query.to_string(),
+            headers: Vec::new(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn route_requests_to_the_client() {
+        let mut memory = Memory::new();
+        let create = request(
+            "POST",
+            "/api/2.0/mlflow/experiments/create",
+            "",
+            r#"{"name":"e"}"#,
+        );
+        let response = handle(&mut memory, &create);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, br#"{"experiment_id":"1"}"#);
+        assert_eq!(handle(&mut memory, &create).status, 400);
+
+        let get = request("GET", "/ajax-api/2.0/mlflow/runs/get", "run_id=missing", "");
+        let response = handle(&mut memory, &get);
+        assert_eq!(response.status, 404);
+        assert!(String::from_utf8_lossy(&response.body).contains("RESOURCE_DOES_NOT_EXIST"));
+
+        let wrong_method = request("GET", "/api/2.0/mlflow/runs/create", "", "");
+        assert_eq!(handle(&mut memory, &wrong_method).status, 405);
+        let unknown = request("GET", "/api/2.0/mlflow/unknown", "", "");
+        assert_eq!(handle(&mut memory, &unknown).status, 404);
+    }
+}
@@ -0,0 +1,325 @@
+//! Support for testing code which talks to a tracking server, enabled by the `test-support` feature.
+//!
+//! ```
+//! use mlflow::{testing::MockServer, Client};
+//!
+//! let server = MockServer::start();
+//! let mut client = server.client();
+//! let experiment = client.create_experiment("test").unwrap();
+//! assert!(server.store().get_experiment(&experiment).is_ok());
+//! assert_eq!(server.requests()[0].path, "2.0/mlflow/experiments/create");
+//! ```
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    backend::{memory::Memory, rest::Server},
+    server::{self, HttpServer, Request, Response},
+};
+
+/// A tracking server on a random localhost port, which stores everything in a [`Memory`].
+///
+/// It records all requests it receives and can be told to [fail][MockServer::inject]
+/// or to [slow down][MockServer::set_latency]. The server stops when it is dropped.
+pub struct MockServer {
+    http: HttpServer,
+    store: Arc<Mutex<Memory>>,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    requests: Vec<RecordedRequest>,
+    faults: Vec<Fault>,
+    latency: Duration,
+}
+
+/// A request received by a [`MockServer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// The endpoint relative to the api url, e.g. `2.0/mlflow/runs/get`.
+    pub path: String,
+    pub query: String,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// The body parsed as JSON, if it is valid JSON.
+    pub fn json(&self) -> Option<serde_json::Value> {
+        serde_json::from_str(&self.body).ok()
+    }
+}
+
+/// A failure the [`MockServer`] returns instead of answering requests to an endpoint.
+///
+/// Defaults to a `500` with the error code `INTERNAL_ERROR` for all following requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    endpoint: Option<String>,
+    status: u16,
+    error_code: String,
+    times: Option<usize>,
+}
+
+impl Fault {
+    /// Fails requests to the endpoint, e.g. `runs/log-batch` or `2.0/mlflow/runs/log-batch`.
+    pub fn on(endpoint: impl Into<String>) -> Self {
+        Fault {
+            endpoint: Some(endpoint.into()),
+            ..Fault::any()
+        }
+    }
+
+    /// Fails requests to all endpoints.
+    pub fn any() -> Self {
+        Fault {
+            endpoint: None,
+            status: 500,
+            error_code: "INTERNAL_ERROR".to_string(),
+            times: None,
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// The MLflow error code, e.g. `RESOURCE_DOES_NOT_EXIST`.
+    pub fn error_code(mut self, error_code: impl Into<String>) -> Self {
+        self.error_code = error_code.into();
+        self
+    }
+
+    /// Only fails the next `times` requests instead of all of them.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let endpoint = self.endpoint.as_deref().map(|e| e.trim_matches('/'));
+        endpoint.is_none_or(|endpoint| path.ends_with(endpoint)) && self.times != Some(0)
+    }
+}
+
+impl MockServer {
+    /// Starts a server with an empty store.
+    ///
+    /// Panics if no localhost port is available.
+    pub fn start() -> Self {
+        Self::with_store(Memory::new())
+    }
+
+    /// Starts a server with the experiments and runs of the store.
+    pub fn with_store(store: Memory) -> Self {
+        let store = Arc::new(Mutex::new(store));
+        let state = Arc::new(Mutex::new(State::default()));
+        let http = {
+            let store = store.clone();
+            let state = state.clone();
+            HttpServer::bind("127.0.0.1:0", move |request| {
+                respond(&store, &state, request)
+            })
+            .expect("failed to start the mock server")
+        };
+        MockServer { http, store, state }
+    }
+
+    /// The tracking uri of the server, e.g. `http://127.0.0.1:43567`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.http.local_addr())
+    }
+
+    /// A client which talks to this server.
+    pub fn client(&self) -> Server {
+        Server::from_tracking_uri(&self.url()).unwrap()
+    }
+
+    /// The store which holds the experiments and runs of the server.
+    pub fn store(&self) -> MutexGuard<'_, Memory> {
+        self.store.lock().unwrap()
+    }
+
+    /// All requests received so far, in the order they were received.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+
+    /// Fails requests as described by the fault, before the faults injected earlier.
+    pub fn inject(&self, fault: Fault) {
+        self.state.lock().unwrap().faults.insert(0, fault);
+    }
+
+    /// Removes all injected faults.
+    pub fn clear_faults(&self) {
+        self.state.lock().unwrap().faults.clear();
+    }
+
+    /// Delays every response by the duration.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+}
+
+fn respond(store: &Mutex<Memory>, state: &Mutex<State>, request: &Request) -> Response {
+    let path = ["/api/", "/ajax-api/"]
+        .iter()
+        .find_map(|prefix| request.path.strip_prefix(prefix))
+        .unwrap_or(&request.path);
+    let (latency, fault) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(RecordedRequest {
+            method: request.method.clone(),
+            path: path.to_string(),
+            query: request.query.clone(),
+            body: String::from_utf8_lossy(&request.body).into_owned(),
+        });
+        let fault = state.faults.iter_mut().find(|fault| fault.matches(path));
+        let fault = fault.map(|fault| {
+            if let Some(times) = &mut fault.times {
+                *times -= 1;
+            }
+            fault.clone()
+        });
+        (state.latency, fault)
+    };
+    if !latency.is_zero() {
+        thread::sleep(latency);
+    }
+    match fault {
+        Some(fault) => {
+            let message = format!("injected failure of {}", path);
+            Response::error(fault.status, &fault.error_code, message)
+        }
+        None => server::handle(&mut *store.lock().unwrap(), request),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{Fault, MockServer};
+    use crate::{
+        api::{
+            error::GetError,
+            run::{Metric, Param, RunStatus, RunTag},
+            search::SearchRunsRequest,
+        },
+        tracking::TrackingRun,
+        Client,
+    };
+
+    #[test]
+    fn rest_client_round_trips_through_the_server() {
+        let server = MockServer::start();
+        let mut client = server.client();
+        let experiment = client.create_experiment("exp").unwrap();
+        assert!(client.create_experiment("exp").is_err());
+        assert_eq!(
+            client.get_experiment_by_name("exp").unwrap().experiment_id,
+            experiment
+        );
+
+        let tag = RunTag {
+            key: "team".to_string(),
+            value: "a".to_string(),
+        };
+        let run = client.create_run(&experiment, 1, &[tag]).unwrap();
+        let id = &run.info.run_id;
+        let metrics = [0.5, 0.25].iter().enumerate().map(|(step, &value)| Metric {
+            key: "loss".into(),
+            value,
+            timestamp: 2,
+            step: step as i64,
+        });
+        let param = Param {
+            key: "lr".to_string(),
+            value: "0.1".to_string(),
+        };
+        let metrics = metrics.collect::<Vec<_>>();
+        client.log_batch(id, &metrics, &[param], &[]).unwrap();
+        let info = client.update_run(id, RunStatus::Finished, Some(3)).unwrap();
+        assert_eq!(info.end_time, Some(3));
+
+        let fetched = client.get_run(id).unwrap();
+        assert_eq!(fetched.data.metrics.unwrap()[0].value, 0.25);
+        assert_eq!(client.get_metric_history(id, "loss").unwrap().len(), 2);
+        assert!(client.get_metric_history(id, "acc").unwrap().is_empty());
+        let search = SearchRunsRequest::new(&[experiment]).filter("params.lr = '0.1'");
+        assert_eq!(client.search_runs(&search).unwrap().runs.len(), 1);
+        assert!(matches!(
+            client.get_run(&"missing".into()),
+            Err(GetError::DoesNotExist(_))
+        ));
+
+        let get_run = server
+            .requests()
+            .into_iter()
+            .find(|r| r.method == "GET" && r.query.contains("run_id"));
+        assert_eq!(get_run.unwrap().path, "2.0/mlflow/runs/get");
+    }
+
+    #[test]
+    fn tracking_run_submits_through_the_server() {
+        let server = MockServer::start();
+        let mut run = TrackingRun::new();
+        run.log_param("seed", 1);
+        run.log_metric("loss", 0.5, 0);
+        run.log_text("done", "notes/summary.txt").unwrap();
+        let run = run.submit(&mut server.client(), &"0".into()).unwrap();
+        let id = &run.info.run_id;
+        let store = server.store();
+        assert_eq!(store.artifact(id, "notes/summary.txt"), Some(&b"done"[..]));
+        let body = server.requests().iter().find_map(|r| {
+            if r.path.ends_with("runs/log-batch") {
+                r.json()
+            } else {
+                None
+            }
+        });
+        assert_eq!(body.unwrap()["params"][0]["key"], "seed");
+    }
+
+    #[test]
+    fn inject_faults_and_latency() {
+        let server = MockServer::start();
+        let mut client = server.client();
+        server.inject(
+            Fault::on("experiments/create")
+                .status(503)
+                .error_code("TEMPORARILY_UNAVAILABLE")
+                .times(1),
+        );
+        let error = client.create_experiment("exp").unwrap_err();
+        let error = format!("{:?}", error);
+        assert!(error.contains("TEMPORARILY_UNAVAILABLE"), "{}", error);
+        assert!(client.create_experiment("exp").is_ok());
+
+        server.inject(
+            Fault::any()
+                .status(400)
+                .error_code("RESOURCE_DOES_NOT_EXIST"),
+        );
+        match client.get_experiment(&"1".into()) {
+            Err(GetError::DoesNotExist(_)) => {}
+            other => panic!("expected a missing experiment, got {:?}", other.map(|_| ())),
+        }
+        server.clear_faults();
+
+        server.set_latency(Duration::from_millis(50));
+        let start = Instant::now();
+        assert!(client.get_experiment(&"1".into()).is_ok());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
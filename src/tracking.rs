@@ -1,2 +1,16 @@
+mod artifact;
+pub(crate) mod batch;
+pub mod context;
+mod fluent;
+mod guard;
+mod handle;
+mod live;
+pub mod metrics;
 mod run;
+pub mod system;
+pub use fluent::{end_run, log_metric, log_param, set_tag, start_run};
+#[cfg(unix)]
+pub use guard::kill_runs_on_signal;
+pub use handle::RunHandle;
+pub use live::{FlushPolicy, LiveRun};
 pub use run::TrackingRun;
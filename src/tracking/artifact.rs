@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    api::{
+        artifact::validate_path,
+        error::StorageError,
+        run::{RunInfo, RunTag},
+        tags,
+    },
+    Client,
+};
+
+/// An artifact waiting to be uploaded.
+///
+/// Files are only read when they are uploaded.
+pub(crate) enum Artifact {
+    Bytes { path: String, contents: Vec<u8> },
+    File { path: String, source: PathBuf },
+}
+
+impl Artifact {
+    pub fn bytes(path: &str, contents: Vec<u8>) -> Result<Self, StorageError> {
+        validate_path(path)?;
+        Ok(Artifact::Bytes {
+            path: path.to_string(),
+            contents,
+        })
+    }
+
+    /// Serializes the value as JSON or YAML, depending on the extension of the path.
+    pub fn dict(path: &str, value: &impl Serialize) -> Result<Self, StorageError> {
+        let contents = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::to_vec_pretty(value)?,
+            Some("yaml") | Some("yml") => serde_yaml::to_string(value)?.into_bytes(),
+            _ => bail!(
+                "the artifact path {:?} must end with .json, .yaml or .yml",
+                path
+            ),
+        };
+        Self::bytes(path, contents)
+    }
+
+    /// The file is stored as `artifact_path/file_name`.
+    pub fn file(source: &Path, artifact_path: Option<&str>) -> Result<Self, StorageError> {
+        if !source.is_file() {
+            bail!("{} is not a file", source.display());
+        }
+        let name = source
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("{} has no valid file name", source.display()))?;
+        let path = join(artifact_path, name);
+        validate_path(&path)?;
+        Ok(Artifact::File {
+            path,
+            source: source.to_path_buf(),
+        })
+    }
+
+    /// All files below `dir`, keeping their relative paths below `artifact_path`.
+    pub fn dir(dir: &Path, artifact_path: Option<&str>) -> Result<Vec<Self>, StorageError> {
+        let mut artifacts = Vec::new();
+        let mut pending = vec![(dir.to_path_buf(), artifact_path.map(str::to_string))];
+        while let Some((dir, artifact_path)) = pending.pop() {
+            let entries = std::fs::read_dir(&dir)
+                .with_context(|| format!("failed to read {}", dir.display()))?;
+            for entry in entries {
+                let source = entry?.path();
+                if source.is_dir() {
+                    let name = source.file_name().and_then(|name| name.to_str());
+                    let name = name
+                        .with_context(|| format!("{} has no valid file name", source.display()))?;
+                    pending.push((source.clone(), Some(join(artifact_path.as_deref(), name))));
+                } else {
+                    artifacts.push(Self::file(&source, artifact_path.as_deref())?);
+                }
+            }
+        }
+        Ok(artifacts)
+    }
+
+    pub fn upload(
+        &self,
+        client: &mut (impl Client + ?Sized),
+        run: &RunInfo,
+    ) -> Result<(), StorageError> {
+        match self {
+            Artifact::Bytes { path, contents } => client.upload_artifact(run, path, contents),
+            Artifact::File { path, source } => {
+                let contents = std::fs::read(source)
+                    .with_context(|| format!("failed to read {}", source.display()))?;
+                client.upload_artifact(run, path, &contents)
+            }
+        }
+    }
+}
+
+/// A table in the format written by `mlflow.log_table`, which the UI shows in its evaluation view.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Table {
+    columns: Vec<String>,
+    data: Vec<Vec<Value>>,
+}
+
+impl Table {
+    pub fn new(columns: &[&str]) -> Self {
+        Table {
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            data: Vec::new(),
+        }
+    }
+
+    pub fn has_columns(&self, columns: &[&str]) -> bool {
+        self.columns
+            .iter()
+            .map(String::as_str)
+            .eq(columns.iter().copied())
+    }
+
+    /// Appends rows, each of which has to serialize to a sequence with one value per column.
+    pub fn append<R: Serialize>(
+        &mut self,
+        rows: impl IntoIterator<Item = R>,
+    ) -> Result<(), StorageError> {
+        let mut data = Vec::new();
+        for row in rows {
+            match serde_json::to_value(row)? {
+                Value::Array(row) if row.len() == self.columns.len() => data.push(row),
+                row => bail!(
+                    "the row {} does not match the {} columns of the table",
+                    row,
+                    self.columns.len()
+                ),
+            }
+        }
+        self.data.extend(data);
+        Ok(())
+    }
+
+    /// Appends the rows of the other table, which has to have the same columns.
+    pub fn extend(&mut self, other: Table) -> Result<(), StorageError> {
+        if self.columns != other.columns {
+            bail!(
+                "the columns {:?} differ from the columns {:?} of the table",
+                other.columns,
+                self.columns
+            );
+        }
+        self.data.extend(other.data);
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LoggedArtifact {
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+fn parse_logged(existing: Option<&str>) -> Result<Vec<LoggedArtifact>, StorageError> {
+    match existing {
+        Some(existing) => serde_json::from_str(existing)
+            .with_context(|| format!("the tag {} is not valid", tags::LOGGED_ARTIFACTS)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The paths of the tables in the [`LOGGED_ARTIFACTS`][tags::LOGGED_ARTIFACTS] tag.
+pub(crate) fn logged_tables(existing: Option<&str>) -> Result<Vec<String>, StorageError> {
+    let logged = parse_logged(existing)?.into_iter();
+    Ok(logged
+        .filter(|artifact| artifact.kind == "table")
+        .map(|artifact| artifact.path)
+        .collect())
+}
+
+/// Adds the tables to the [`LOGGED_ARTIFACTS`][tags::LOGGED_ARTIFACTS] tag, keeping its existing entries.
+pub(crate) fn logged_tables_tag<'a>(
+    existing: Option<&str>,
+    tables: impl IntoIterator<Item = &'a str>,
+) -> Result<RunTag, StorageError> {
+    let mut logged = parse_logged(existing)?;
+    for path in tables {
+        if !logged.iter().any(|artifact| artifact.path == path) {
+            logged.push(LoggedArtifact {
+                path: path.to_string(),
+                kind: "table".to_string(),
+            });
+        }
+    }
+    Ok(RunTag {
+        key: tags::LOGGED_ARTIFACTS.to_string(),
+        value: serde_json::to_string(&logged)?,
+    })
+}
+
+fn join(artifact_path: Option<&str>, name: &str) -> String {
+    match artifact_path.map(|path| path.trim_end_matches('/')) {
+        Some(path) if !path.is_empty() => format!("{}/{}", path, name),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join, logged_tables_tag, Artifact, Table};
+
+    #[test]
+    fn dict_artifacts_use_the_extension() {
+        let value = serde_json::json!({ "lr": 0.1 });
+        match Artifact::dict("config.yaml", &value).unwrap() {
+            Artifact::Bytes { contents, .. } => assert_eq!(contents, b"lr: 0.1\n"),
+            Artifact::File { .. } => unreachable!(),
+        }
+        assert!(Artifact::dict("config.txt", &value).is_err());
+        assert!(Artifact::bytes("../escape.txt", Vec::new()).is_err());
+        assert_eq!(join(Some("plots/"), "loss.png"), "plots/loss.png");
+        assert_eq!(join(None, "loss.png"), "loss.png");
+    }
+
+    #[test]
+    fn tables_are_appended_and_registered() {
+        let mut table = Table::new(&["input", "score"]);
+        table.append(vec![("a", 0.5)]).unwrap();
+        table.append(vec![("b", 1.0)]).unwrap();
+        assert!(table.append(vec![("c",)]).is_err());
+        assert_eq!(
+            serde_json::to_string(&table).unwrap(),
+            r#"{"columns":["input","score"],"data":[["a",0.5],["b",1.0]]}"#
+        );
+
+        let existing = r#"[{"path":"eval.json","type":"table"}]"#;
+        let tag = logged_tables_tag(Some(existing), vec!["eval.json", "preds.json"]).unwrap();
+        assert_eq!(
+            tag.value,
+            r#"[{"path":"eval.json","type":"table"},{"path":"preds.json","type":"table"}]"#
+        );
+    }
+}
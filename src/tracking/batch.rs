@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use crate::{
+    api::{
+        error::BatchError,
+        limits,
+        run::{Metric, Param, RunTag},
+    },
+    backend::rest::{RestError, RestErrorCode},
+    Client, RunId,
+};
+
+/// A single `log_batch` request worth of items.
+pub(crate) struct Batch<'a, 'm> {
+    pub metrics: &'a [Metric<'m>],
+    pub params: &'a [Param],
+    pub tags: &'a [RunTag],
+}
+
+/// Splits the items into batches which respect the [`limits`].
+///
+/// Params and tags are the scarcer resource, so every batch takes as many of them as
+/// allowed and fills up the remaining space with metrics.
+pub(crate) fn batches<'a, 'm>(
+    mut metrics: &'a [Metric<'m>],
+    mut params: &'a [Param],
+    mut tags: &'a [RunTag],
+) -> impl Iterator<Item = Batch<'a, 'm>> {
+    std::iter::from_fn(move || {
+        if metrics.is_empty() && params.is_empty() && tags.is_empty() {
+            return None;
+        }
+        let (batch_params, rest) = params.split_at(params.len().min(limits::BATCH_PARAMS));
+        params = rest;
+        let (batch_tags, rest) = tags.split_at(tags.len().min(limits::BATCH_TAGS));
+        tags = rest;
+        let space = (limits::BATCH_TOTAL - batch_params.len() - batch_tags.len())
+            .min(limits::BATCH_METRICS);
+        let (batch_metrics, rest) = metrics.split_at(metrics.len().min(space));
+        metrics = rest;
+        Some(Batch {
+            metrics: batch_metrics,
+            params: batch_params,
+            tags: batch_tags,
+        })
+    })
+}
+
+/// Logs all items using as few `log_batch` requests as possible.
+///
+/// Returns how many metrics, params and tags were logged successfully, even if a later batch failed.
+pub(crate) fn log_batches(
+    client: &mut (impl Client + ?Sized),
+    run: &RunId,
+    metrics: &[Metric],
+    params: &[Param],
+    tags: &[RunTag],
+) -> (Logged, Result<(), BatchError>) {
+    let mut logged = Logged::default();
+    for batch in batches(metrics, params, tags) {
+        if let Err(error) = client.log_batch(run, batch.metrics, batch.params, batch.tags) {
+            return (logged, Err(error));
+        }
+        logged.metrics += batch.metrics.len();
+        logged.params += batch.params.len();
+        logged.tags += batch.tags.len();
+    }
+    (logged, Ok(()))
+}
+
+/// Removes repeated params, failing if a key was logged with different values.
+///
+/// MLflow does not allow changing a param once it is logged, so this catches conflicts before uploading anything.
+pub(crate) fn dedup_params(params: &[Param]) -> Result<Vec<Param>, BatchError> {
+    let mut seen = HashMap::<&str, &str>::new();
+    let mut unique = Vec::with_capacity(params.len());
+    for param in params {
+        match seen.insert(&param.key, &param.value) {
+            None => unique.push(param.clone()),
+            Some(first) if first == param.value => {}
+            Some(first) => {
+                return Err(BatchError::ConflictingParam {
+                    key: param.key.clone(),
+                    first: first.to_string(),
+                    second: param.value.clone(),
+                })
+            }
+        }
+    }
+    Ok(unique)
+}
+
+/// Removes the params which have already been logged with the same value.
+pub(crate) fn skip_logged(params: Vec<Param>, logged: &[Param]) -> Result<Vec<Param>, BatchError> {
+    let logged = logged
+        .iter()
+        .map(|param| (param.key.as_str(), param.value.as_str()))
+        .collect::<HashMap<_, _>>();
+    let mut missing = Vec::with_capacity(params.len());
+    for param in params {
+        match logged.get(param.key.as_str()) {
+            None => missing.push(param),
+            Some(first) if *first == param.value => {}
+            Some(first) => {
+                return Err(BatchError::ConflictingParam {
+                    first: first.to_string(),
+                    key: param.key,
+                    second: param.value,
+                })
+            }
+        }
+    }
+    Ok(missing)
+}
+
+/// Whether sending the same batch again can never succeed.
+///
+/// Besides the errors found by [`BatchError::check`], this includes batches a server rejected as invalid,
+/// e.g. because a param was already logged with another value.
+pub(crate) fn is_permanent(error: &BatchError) -> bool {
+    match error {
+        BatchError::Storage(error) => matches!(
+            error.downcast_ref(),
+            Some(RestError::Known {
+                code: RestErrorCode::InvalidParameterValue,
+                ..
+            })
+        ),
+        _ => true,
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Logged {
+    pub metrics: usize,
+    pub params: usize,
+    pub tags: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batches, dedup_params};
+    use crate::api::{
+        error::BatchError,
+        run::{Metric, Param},
+    };
+
+    #[test]
+    fn batches_respect_limits() {
+        let metrics = (0..1900)
+            .map(|step| Metric {
+                key: "loss".into(),
+                value: 0.0,
+                timestamp: 0,
+                step,
+            })
+            .collect::<Vec<_>>();
+        let params = (0..150)
+            .map(|i| Param {
+                key: format!("p{}", i),
+                value: String::new(),
+            })
+            .collect::<Vec<_>>();
+        let sizes = batches(&metrics, &params, &[])
+            .map(|b| (b.metrics.len(), b.params.len(), b.tags.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, vec![(900, 100, 0), (950, 50, 0), (50, 0, 0)]);
+    }
+
+    #[test]
+    fn dedup_params_detects_conflicts() {
+        let param = |key: &str, value: &str| Param {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        let unique = dedup_params(&[param("a", "1"), param("b", "2"), param("a", "1")]).unwrap();
+        assert_eq!(unique, vec![param("a", "1"), param("b", "2")]);
+        let conflict = dedup_params(&[param("a", "1"), param("a", "2")]);
+        assert!(matches!(conflict, Err(BatchError::ConflictingParam { key, .. }) if key == "a"));
+    }
+}
@@ -0,0 +1,291 @@
+//! Tags describing the context a run was created in.
+//!
+//! Every run created by [`TrackingRun`][super::TrackingRun] or [`LiveRun`][super::LiveRun]
+//! receives the tags of all registered [`ContextProvider`]s.
+//! By default these are the providers for the [user][User], the [source][Source] and the [git checkout][Git],
+//! which set the same tags as the MLflow Python client.
+//! Additional providers can be added using [`register`].
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+use crate::api::{run::RunTag, tags};
+
+/// Collects tags from the environment of the process.
+pub trait ContextProvider: Send {
+    fn tags(&self) -> Vec<RunTag>;
+}
+
+impl<F> ContextProvider for F
+where
+    F: Fn() -> Vec<RunTag> + Send,
+{
+    fn tags(&self) -> Vec<RunTag> {
+        self()
+    }
+}
+
+fn providers() -> &'static Mutex<Vec<Box<dyn ContextProvider>>> {
+    static PROVIDERS: OnceLock<Mutex<Vec<Box<dyn ContextProvider>>>> = OnceLock::new();
+    PROVIDERS.get_or_init(|| Mutex::new(vec![Box::new(User), Box::new(Source), Box::new(Git)]))
+}
+
+/// Adds a provider whose tags are attached to all runs created from now on.
+///
+/// Providers registered later take precedence if they set the same tag.
+pub fn register(provider: impl ContextProvider + 'static) {
+    providers().lock().unwrap().push(Box::new(provider));
+}
+
+/// Removes all providers, including the default ones.
+pub fn clear() {
+    providers().lock().unwrap().clear();
+}
+
+/// The tags of all registered providers.
+pub fn tags() -> Vec<RunTag> {
+    let mut tags: Vec<RunTag> = Vec::new();
+    for provider in providers().lock().unwrap().iter() {
+        for tag in provider.tags() {
+            tags.retain(|existing| existing.key != tag.key);
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+fn tag(key: &str, value: impl Into<String>) -> RunTag {
+    RunTag {
+        key: key.to_string(),
+        value: value.into(),
+    }
+}
+
+/// Sets [`USER`][tags::USER] to the name of the user running the process.
+pub struct User;
+
+impl ContextProvider for User {
+    fn tags(&self) -> Vec<RunTag> {
+        std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .map(|user| tag(tags::USER, user))
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Sets [`SOURCE_NAME`][tags::SOURCE_NAME] to the running executable
+/// and [`SOURCE_TYPE`][tags::SOURCE_TYPE] to `LOCAL`.
+pub struct Source;
+
+impl ContextProvider for Source {
+    fn tags(&self) -> Vec<RunTag> {
+        let name = std::env::current_exe()
+            .ok()
+            .map(|path| path.display().to_string())
+            .or_else(|| std::env::args().next());
+        name.map(|name| tag(tags::SOURCE_NAME, name))
+            .into_iter()
+            .chain(Some(tag(tags::SOURCE_TYPE, "LOCAL")))
+            .collect()
+    }
+}
+
+/// Sets the commit, branch and remote url of the git checkout containing the working directory.
+///
+/// The repository is read directly, so no `git` executable is required.
+pub struct Git;
+
+impl ContextProvider for Git {
+    fn tags(&self) -> Vec<RunTag> {
+        let repository = std::env::current_dir()
+            .ok()
+            .and_then(|dir| Repository::discover(&dir));
+        let repository = match repository {
+            Some(repository) => repository,
+            None => return Vec::new(),
+        };
+        let head = repository.head();
+        let mut tags = Vec::new();
+        if let Some(Head::Branch(reference)) = &head {
+            let branch = reference.trim_start_matches("refs/heads/");
+            tags.push(tag(tags::GIT_BRANCH, branch));
+        }
+        if let Some(commit) = head.and_then(|head| repository.commit(head)) {
+            tags.push(tag(tags::GIT_COMMIT, commit));
+        }
+        if let Some(url) = repository.remote_url("origin") {
+            tags.push(tag(tags::GIT_REPO_URL, url));
+        }
+        tags
+    }
+}
+
+struct Repository {
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+}
+
+enum Head {
+    Branch(String),
+    Detached(String),
+}
+
+impl Repository {
+    fn discover(start: &Path) -> Option<Self> {
+        let git_dir = start.ancestors().find_map(|dir| {
+            let git = dir.join(".git");
+            if git.is_dir() {
+                Some(git)
+            } else if git.is_file() {
+                // Worktrees and submodules point to their actual git directory.
+                let content = fs::read_to_string(&git).ok()?;
+                let path = content.strip_prefix("gitdir:")?.trim();
+                Some(dir.join(path))
+            } else {
+                None
+            }
+        })?;
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(path) => git_dir.join(path.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        Some(Repository {
+            git_dir,
+            common_dir,
+        })
+    }
+
+    fn head(&self) -> Option<Head> {
+        let head = fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        Some(match head.strip_prefix("ref:") {
+            Some(reference) => Head::Branch(reference.trim().to_string()),
+            None => Head::Detached(head.to_string()),
+        })
+    }
+
+    fn commit(&self, head: Head) -> Option<String> {
+        let reference = match head {
+            Head::Branch(reference) => reference,
+            Head::Detached(commit) => return Some(commit),
+        };
+        for dir in &[&self.git_dir, &self.common_dir] {
+            if let Ok(commit) = fs::read_to_string(dir.join(&reference)) {
+                return Some(commit.trim().to_string());
+            }
+        }
+        let packed = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        packed.lines().find_map(|line| {
+            let (commit, name) = line.split_once(' ')?;
+            (name == reference).then(|| commit.to_string())
+        })
+    }
+
+    fn remote_url(&self, remote: &str) -> Option<String> {
+        let config = fs::read_to_string(self.common_dir.join("config")).ok()?;
+        parse_remote_url(&config, remote)
+    }
+}
+
+fn parse_remote_url(config: &str, remote: &str) -> Option<String> {
+    let section = format!("[remote \"{}\"]", remote);
+    let mut in_section = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_section = line == section;
+        } else if in_section {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == "url" {
+                    return Some(value.trim().to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_remote_url, ContextProvider, Git, User};
+    use crate::{
+        api::{run::RunTag, tags},
+        testing::MockServer,
+        tracking::{LiveRun, TrackingRun},
+    };
+
+    /// The tags sent with the first `runs/create` request.
+    fn created_tags(server: &MockServer) -> Vec<RunTag> {
+        let requests = server.requests();
+        let create = requests
+            .iter()
+            .find(|r| r.path.ends_with("runs/create"))
+            .expect("a run was created");
+        serde_json::from_value(create.json().unwrap()["tags"].clone()).unwrap()
+    }
+
+    fn value<'a>(tags: &'a [RunTag], key: &str) -> Option<&'a str> {
+        tags.iter()
+            .find(|tag| tag.key == key)
+            .map(|tag| tag.value.as_str())
+    }
+
+    #[test]
+    fn new_runs_are_created_with_the_context_tags() {
+        let server = MockServer::start();
+        let mut run = TrackingRun::new();
+        run.set_name("tracked");
+        run.submit(&mut server.client(), &"0".into()).unwrap();
+        let tracked = created_tags(&server);
+
+        server.clear_requests();
+        let live = LiveRun::start_named(server.client(), &"0".into(), "live").unwrap();
+        live.finish().unwrap();
+        let live = created_tags(&server);
+
+        for tags in [&tracked, &live] {
+            for context in super::tags() {
+                assert_eq!(value(tags, &context.key), Some(context.value.as_str()));
+            }
+            assert!(value(tags, tags::SOURCE_NAME).is_some());
+            assert_eq!(value(tags, tags::SOURCE_TYPE), Some("LOCAL"));
+            let user = User.tags().pop();
+            assert_eq!(
+                value(tags, tags::USER),
+                user.as_ref().map(|tag| tag.value.as_str())
+            );
+            // The tests run inside the checkout of this crate, if it is one.
+            let commit = Git
+                .tags()
+                .into_iter()
+                .find(|tag| tag.key == tags::GIT_COMMIT);
+            assert_eq!(
+                value(tags, tags::GIT_COMMIT),
+                commit.as_ref().map(|tag| tag.value.as_str())
+            );
+        }
+        assert_eq!(value(&tracked, tags::RUN_NAME), Some("tracked"));
+        assert_eq!(value(&live, tags::RUN_NAME), Some("live"));
+    }
+
+    #[test]
+    fn parse_origin_url() {
+        let config = r#"
+[core]
+    bare = false
+[remote "upstream"]
+    url = https://example.com/upstream.git
+[remote "origin"]
+    url = git@example.com:me/fork.git
+    fetch = +refs/heads/*:refs/remotes/origin/*
+"#;
+        assert_eq!(
+            parse_remote_url(config, "origin").as_deref(),
+            Some("git@example.com:me/fork.git")
+        );
+        assert_eq!(parse_remote_url(config, "missing"), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! A process-wide active run, like `mlflow.start_run` and `mlflow.log_metric` in Python.
+
+use std::{fmt::Display, sync::Mutex};
+
+use crate::{
+    api::{
+        error::{GetError, StorageError},
+        run::Run,
+    },
+    backend::rest::Server,
+    tracking::TrackingRun,
+    Client, ExperimentId,
+};
+
+/// The stack of active runs, the outermost one first.
+static ACTIVE_RUNS: Mutex<Vec<TrackingRun<'static>>> = Mutex::new(Vec::new());
+
+fn with_active_run(f: impl FnOnce(&mut TrackingRun<'static>)) {
+    let mut runs = ACTIVE_RUNS
+        .lock()
+        .unwrap_or_else(|error| error.into_inner());
+    if runs.is_empty() {
+        runs.push(TrackingRun::new());
+    }
+    f(runs.last_mut().unwrap());
+}
+
+/// Starts a new run, which is nested in the active run if there is one.
+///
+/// This allows logging from deep inside library code without passing a run around:
+///
+/// ```no_run
+/// use mlflow::tracking;
+///
+/// tracking::start_run();
+/// tracking::log_param("lr", 0.01);
+/// for step in 0..10 {
+///     tracking::log_metric("loss", 1.0 / (step + 1) as f64, step);
+/// }
+/// tracking::end_run().expect("could not submit the run");
+/// ```
+///
+/// The runs are buffered in [`TrackingRun`]s. Runs started while another run is active become
+/// its children and are submitted together with the outermost run when it [ends][end_run].
+/// Data of runs which are still active when the process exits is lost.
+pub fn start_run() {
+    let mut runs = ACTIVE_RUNS
+        .lock()
+        .unwrap_or_else(|error| error.into_inner());
+    runs.push(TrackingRun::new());
+}
+
+/// Logs a metric to the active run, starting a run if none is active.
+pub fn log_metric(key: impl Into<String>, value: f64, step: i64) {
+    with_active_run(|run| run.log_metric(key.into(), value, step));
+}
+
+/// Logs a param to the active run, starting a run if none is active.
+pub fn log_param(key: impl Into<String>, value: impl Display) {
+    with_active_run(|run| run.log_param(key, value));
+}
+
+/// Sets a tag of the active run, starting a run if none is active.
+pub fn set_tag(key: impl Into<String>, value: impl Display) {
+    with_active_run(|run| run.log_tag(key, value));
+}
+
+/// Ends the active run.
+///
+/// A nested run is handed to its parent and `None` is returned.
+/// The outermost run is submitted together with all its children, using the tracking server
+/// given by `MLFLOW_TRACKING_URI` and the experiment given by `MLFLOW_EXPERIMENT_ID`
+/// or `MLFLOW_EXPERIMENT_NAME`, which is created if it does not exist.
+/// Without either, the default experiment `0` is used.
+///
+/// Ending a run while none is active does nothing. If the tracking server or the experiment
+/// cannot be found, the run stays active and the error is returned.
+pub fn end_run() -> Result<Option<Run>, StorageError> {
+    end_run_with(connect)
+}
+
+/// Ends the active run like [`end_run`], submitting the outermost run to the client and
+/// experiment returned by `connect`, which is only called for the outermost run.
+pub(crate) fn end_run_with<C: Client>(
+    connect: impl FnOnce() -> Result<(C, ExperimentId), StorageError>,
+) -> Result<Option<Run>, StorageError> {
+    let run = {
+        let mut runs = ACTIVE_RUNS
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let run = match runs.pop() {
+            Some(run) => run,
+            None => return Ok(None),
+        };
+        match runs.last_mut() {
+            Some(parent) => {
+                parent.add_child(run);
+                return Ok(None);
+            }
+            None => run,
+        }
+    };
+    let (mut client, experiment) = match connect() {
+        Ok(connected) => connected,
+        Err(error) => {
+            let mut runs = ACTIVE_RUNS
+                .lock()
+                .unwrap_or_else(|error| error.into_inner());
+            // The run was the outermost one, runs started since are nested in it again.
+            runs.insert(0, run);
+            return Err(error);
+        }
+    };
+    run.submit(&mut client, &experiment).map(Some)
+}
+
+fn connect() -> Result<(Server, ExperimentId), StorageError> {
+    let mut client = Server::from_env()?;
+    let experiment = experiment_from_env(&mut client)?;
+    Ok((client, experiment))
+}
+
+fn experiment_from_env(client: &mut dyn Client) -> Result<ExperimentId, StorageError> {
+    if let Ok(id) = std::env::var("MLFLOW_EXPERIMENT_ID") {
+        return Ok(ExperimentId::from(id));
+    }
+    match std::env::var("MLFLOW_EXPERIMENT_NAME") {
+        Ok(name) => experiment_by_name(client, &name),
+        Err(_) => Ok(ExperimentId::from("0".to_string())),
+    }
+}
+
+/// The id of the experiment, which is created if it does not exist.
+fn experiment_by_name(client: &mut dyn Client, name: &str) -> Result<ExperimentId, StorageError> {
+    match client.get_experiment_by_name(name) {
+        Ok(experiment) => Ok(experiment.experiment_id),
+        Err(GetError::DoesNotExist(_)) => Ok(client.create_experiment(name)?),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{end_run_with, experiment_by_name, log_metric, log_param, start_run};
+    use crate::{
+        api::search::SearchRunsRequest,
+        testing::{Fault, MockServer},
+        Client,
+    };
+
+    #[test]
+    fn runs_survive_a_failed_end() {
+        let server = MockServer::start();
+        let connect = || {
+            let mut client = server.client();
+            let experiment = experiment_by_name(&mut client, "fluent")?;
+            Ok((client, experiment))
+        };
+
+        start_run();
+        log_param("lr", 0.1);
+        start_run();
+        log_metric("loss", 0.5, 0);
+        assert!(end_run_with(connect).unwrap().is_none());
+
+        server.inject(Fault::on("experiments/get-by-name").times(1));
+        assert!(end_run_with(connect).is_err());
+        assert!(server
+            .requests()
+            .iter()
+            .all(|r| !r.path.ends_with("runs/create")));
+
+        let run = end_run_with(connect)
+            .unwrap()
+            .expect("the outermost run is submitted");
+        let stored = server.store().get_run(&run.info.run_id).unwrap();
+        assert_eq!(stored.data.params.unwrap()[0].key, "lr");
+        let search = SearchRunsRequest::new(&[run.info.experiment_id]);
+        assert_eq!(server.store().search_runs(&search).unwrap().runs.len(), 2);
+        assert!(end_run_with(connect).unwrap().is_none());
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{mpsc::Sender, Mutex, Once, OnceLock},
+};
+
+use crate::{tracking::live::Message, RunId};
+
+thread_local! {
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Remembers the message of the last panic of each thread,
+/// so a [`LiveRun`][super::LiveRun] dropped while unwinding can record it.
+///
+/// The previously installed hook is still called.
+pub(super) fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            PANIC_MESSAGE.with(|message| *message.borrow_mut() = Some(info.to_string()));
+            previous(info);
+        }));
+    });
+}
+
+pub(super) fn take_panic_message() -> Option<String> {
+    PANIC_MESSAGE.with(|message| message.borrow_mut().take())
+}
+
+/// The runs which have not been finished yet.
+fn active_runs() -> &'static Mutex<HashMap<RunId, Sender<Message>>> {
+    static ACTIVE: OnceLock<Mutex<HashMap<RunId, Sender<Message>>>> = OnceLock::new();
+    ACTIVE.get_or_init(Default::default)
+}
+
+pub(super) fn register(run_id: RunId, sender: Sender<Message>) {
+    active_runs().lock().unwrap().insert(run_id, sender);
+}
+
+pub(super) fn unregister(run_id: &RunId) {
+    active_runs().lock().unwrap().remove(run_id);
+}
+
+/// Marks all active [`LiveRun`][super::LiveRun]s as [`Killed`][crate::api::run::RunStatus::Killed]
+/// when the process receives `SIGINT` or `SIGTERM`, and exits afterwards.
+///
+/// Without this, the signals terminate the process right away and the runs stay running forever.
+#[cfg(unix)]
+pub fn kill_runs_on_signal() -> std::io::Result<()> {
+    use signal_hook::{
+        consts::{SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+
+    static INSTALLED: OnceLock<()> = OnceLock::new();
+    if INSTALLED.set(()).is_err() {
+        return Ok(());
+    }
+
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    std::thread::Builder::new()
+        .name("mlflow-signals".to_string())
+        .spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                let name = if signal == SIGINT {
+                    "SIGINT"
+                } else {
+                    "SIGTERM"
+                };
+                kill_all(&format!("The process received {}.", name));
+                std::process::exit(128 + signal);
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn kill_all(note: &str) {
+    use crate::api::{run::RunStatus, tags};
+    use std::{sync::mpsc, time::Duration};
+
+    let runs = active_runs()
+        .lock()
+        .unwrap()
+        .drain()
+        .map(|(_, sender)| sender)
+        .collect::<Vec<_>>();
+    for sender in &runs {
+        let _ = sender.send(Message::tag(tags::NOTE, note));
+        let _ = sender.send(Message::Finish(RunStatus::Killed));
+    }
+    for sender in runs {
+        // The worker stops after handling `Finish`, dropping this request unanswered,
+        // so the receiver disconnects as soon as the run has been updated.
+        let (reply, done) = mpsc::channel();
+        if sender.send(Message::Flush(reply)).is_ok() {
+            let _ = done.recv_timeout(Duration::from_secs(10));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::{
+        api::{run::RunStatus, tags},
+        testing::MockServer,
+        tracking::LiveRun,
+        Client,
+    };
+
+    #[test]
+    fn panics_fail_the_run_with_their_message() {
+        let server = MockServer::start();
+        let client = server.client();
+        let (sender, receiver) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            let run = LiveRun::start(client, &"0".into()).unwrap();
+            sender.send(run.run_id().clone()).unwrap();
+            run.log_metric("loss", 1e9, 0);
+            panic!("the loss diverged");
+        });
+        assert!(thread.join().is_err());
+        let id = receiver.recv().unwrap();
+        let run = server.store().get_run(&id).unwrap();
+        assert_eq!(run.info.status, RunStatus::Failed);
+        let tags = run.data.tags.unwrap();
+        let note = tags.iter().find(|tag| tag.key == tags::NOTE).unwrap();
+        assert!(note.value.contains("the loss diverged"), "{}", note.value);
+        assert_eq!(run.data.metrics.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{borrow::Cow, fmt::Display, sync::mpsc::Sender};
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        error::StorageError,
+        params,
+        run::{Metric, Param},
+    },
+    timestamp,
+    tracking::live::Message,
+    RunId,
+};
+
+/// A cloneable handle to log into a [`LiveRun`][super::LiveRun] from many threads.
+///
+/// All handles send their data to the single background thread of the run,
+/// which uploads it in the order it was received.
+/// The metrics logged by one thread therefore keep their order.
+///
+/// Data logged after the run was finished is discarded.
+#[derive(Clone)]
+pub struct RunHandle {
+    run_id: RunId,
+    sender: Sender<Message>,
+}
+
+impl RunHandle {
+    pub(super) fn new(run_id: RunId, sender: Sender<Message>) -> Self {
+        RunHandle { run_id, sender }
+    }
+
+    pub fn run_id(&self) -> &RunId {
+        &self.run_id
+    }
+
+    pub fn log_param(&self, key: impl Into<String>, value: impl Display) {
+        self.send(Message::Param(Param {
+            key: key.into(),
+            value: format!("{}", value),
+        }));
+    }
+
+    /// Logs all fields of the config as params, see [`params`][crate::api::params] for the format.
+    pub fn log_params_from(&self, config: &impl Serialize) -> Result<(), serde_json::Error> {
+        for param in params::flatten(config)? {
+            self.send(Message::Param(param));
+        }
+        Ok(())
+    }
+
+    pub fn log_tag(&self, key: impl Into<String>, value: impl Display) {
+        self.send(Message::tag(key, value));
+    }
+
+    pub fn log_metric(&self, key: impl Into<String>, value: f64, step: i64) {
+        self.send(Message::Metric(Metric {
+            key: Cow::Owned(key.into()),
+            value,
+            timestamp: timestamp(),
+            step,
+        }));
+    }
+
+    /// Sends all data buffered by the run to the server and waits for it to be stored.
+    pub fn flush(&self) -> Result<(), StorageError> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.send(Message::Flush(sender));
+        receiver
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("the run has already ended")))
+    }
+
+    fn send(&self, message: Message) {
+        // The worker only stops once the run has ended.
+        let _ = self.sender.send(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::RunHandle;
+    use crate::{
+        testing::MockServer,
+        tracking::{FlushPolicy, LiveRun},
+        Client,
+    };
+
+    #[test]
+    fn run_handle_is_shareable() {
+        fn assert_send_sync<T: Send + Sync + Clone>() {}
+        assert_send_sync::<RunHandle>();
+    }
+
+    #[test]
+    fn metrics_from_many_threads_keep_their_order() {
+        const THREADS: i64 = 4;
+        const STEPS: i64 = 250;
+        let server = MockServer::start();
+        let policy = FlushPolicy {
+            max_pending: 64,
+            interval: Duration::from_secs(3600),
+        };
+        let run = LiveRun::with_policy(server.client(), &"0".into(), policy).unwrap();
+        let id = run.run_id().clone();
+        let threads = (0..THREADS)
+            .map(|thread| {
+                let handle = run.handle();
+                std::thread::spawn(move || {
+                    for step in 0..STEPS {
+                        handle.log_metric(format!("thread{}", thread), step as f64, step);
+                        handle.log_metric("shared", thread as f64, step);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        run.finish().unwrap();
+
+        let mut store = server.store();
+        for thread in 0..THREADS {
+            let history = store
+                .get_metric_history(&id, &format!("thread{}", thread))
+                .unwrap();
+            let steps = history.iter().map(|m| m.step).collect::<Vec<_>>();
+            assert_eq!(steps, (0..STEPS).collect::<Vec<_>>());
+        }
+        let shared = store.get_metric_history(&id, "shared").unwrap();
+        assert_eq!(shared.len() as i64, THREADS * STEPS);
+        for thread in 0..THREADS {
+            let steps = shared
+                .iter()
+                .filter(|m| m.value == thread as f64)
+                .map(|m| m.step)
+                .collect::<Vec<_>>();
+            assert_eq!(steps, (0..STEPS).collect::<Vec<_>>());
+        }
+    }
+}
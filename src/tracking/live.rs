@@ -0,0 +1,633 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Display,
+    path::Path,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+use crate::{
+    api::{
+        error::{BatchError, StorageError},
+        limits, params,
+        run::{Metric, Param, Run, RunInfo, RunStatus, RunTag},
+        tags,
+    },
+    timestamp,
+    tracking::{
+        artifact::Artifact, batch, context, guard, handle::RunHandle, system::SystemMonitor,
+    },
+    Client, ExperimentId, RunId,
+};
+
+/// Controls when a [`LiveRun`] sends its buffered data to the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    /// Flush as soon as this many items are buffered.
+    pub max_pending: usize,
+    /// Flush at least this often while there is buffered data.
+    pub interval: Duration,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        FlushPolicy {
+            max_pending: limits::BATCH_TOTAL,
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A MLflow Run which is visible on the server while it is being tracked.
+///
+/// In contrast to [`TrackingRun`][super::TrackingRun], the run is created right away
+/// and logged data is sent to the server by a background thread according to a [`FlushPolicy`].
+/// The run stays [`RunStatus::Running`] until it is [finished][LiveRun::finish].
+///
+/// If the run is dropped without being finished, for example because the thread panicked,
+/// the buffered data is still flushed and the run is marked as [`RunStatus::Failed`].
+/// The panic message is recorded as the note of the run.
+/// Use [`kill_runs_on_signal`][super::kill_runs_on_signal] to mark runs as
+/// [`RunStatus::Killed`] when the process is interrupted.
+///
+/// Other threads can log into the run using a [`handle`][LiveRun::handle].
+pub struct LiveRun {
+    run_id: RunId,
+    experiment_id: ExperimentId,
+    policy: FlushPolicy,
+    sender: Option<Sender<Message>>,
+    monitor: Option<SystemMonitor>,
+    worker: Option<JoinHandle<Result<Option<RunInfo>, StorageError>>>,
+}
+
+pub(super) enum Message {
+    Metric(Metric<'static>),
+    Param(Param),
+    Tag(RunTag),
+    Artifact(Artifact),
+    Flush(Sender<Result<(), StorageError>>),
+    Finish(RunStatus),
+}
+
+impl Message {
+    pub(super) fn tag(key: impl Into<String>, value: impl Display) -> Self {
+        Message::Tag(RunTag {
+            key: key.into(),
+            value: format!("{}", value),
+        })
+    }
+}
+
+impl LiveRun {
+    /// Creates a new run in the given experiment, using the default [`FlushPolicy`].
+    pub fn start(
+        client: impl Client + Send + 'static,
+        experiment: &ExperimentId,
+    ) -> Result<Self, StorageError> {
+        Self::with_policy(client, experiment, FlushPolicy::default())
+    }
+
+    /// Starts a run with the name shown in the MLflow UI, which is set when the run is created.
+    pub fn start_named(
+        client: impl Client + Send + 'static,
+        experiment: &ExperimentId,
+        name: impl Into<String>,
+    ) -> Result<Self, StorageError> {
+        let name = RunTag {
+            key: tags::RUN_NAME.to_string(),
+            value: name.into(),
+        };
+        Self::create(
+            Box::new(client),
+            experiment,
+            &[name],
+            FlushPolicy::default(),
+        )
+    }
+
+    pub fn with_policy(
+        client: impl Client + Send + 'static,
+        experiment: &ExperimentId,
+        policy: FlushPolicy,
+    ) -> Result<Self, StorageError> {
+        Self::create(Box::new(client), experiment, &[], policy)
+    }
+
+    /// Creates a run nested in this one, using the same experiment and [`FlushPolicy`].
+    ///
+    /// The child is linked to this run by the [`PARENT_RUN_ID`][tags::PARENT_RUN_ID] tag,
+    /// so it appears below it in the MLflow UI.
+    pub fn start_child(
+        &self,
+        client: impl Client + Send + 'static,
+    ) -> Result<LiveRun, StorageError> {
+        let parent = RunTag {
+            key: tags::PARENT_RUN_ID.to_string(),
+            value: self.run_id.as_ref().to_string(),
+        };
+        Self::create(
+            Box::new(client),
+            &self.experiment_id,
+            &[parent],
+            self.policy,
+        )
+    }
+
+    fn create(
+        mut client: Box<dyn Client + Send>,
+        experiment: &ExperimentId,
+        tags: &[RunTag],
+        policy: FlushPolicy,
+    ) -> Result<Self, StorageError> {
+        let mut run_tags = context::tags();
+        run_tags.extend_from_slice(tags);
+        let run = client.create_run(experiment, timestamp(), &run_tags)?;
+        Ok(Self::spawn(client, run, policy))
+    }
+
+    fn spawn(client: Box<dyn Client + Send>, run: Run, policy: FlushPolicy) -> Self {
+        guard::install_panic_hook();
+        let experiment_id = run.info.experiment_id.clone();
+        let run_id = run.info.run_id.clone();
+        let (sender, receiver) = mpsc::channel();
+        guard::register(run_id.clone(), sender.clone());
+        let worker = std::thread::Builder::new()
+            .name(format!("mlflow-run-{}", run_id.as_ref()))
+            .spawn(move || Worker::new(client, run.info, policy).run(receiver))
+            .expect("failed to spawn the flushing thread");
+        LiveRun {
+            run_id,
+            experiment_id,
+            policy,
+            sender: Some(sender),
+            monitor: None,
+            worker: Some(worker),
+        }
+    }
+
+    pub fn run_id(&self) -> &RunId {
+        &self.run_id
+    }
+
+    pub fn experiment_id(&self) -> &ExperimentId {
+        &self.experiment_id
+    }
+
+    /// A handle to log into this run from other threads.
+    pub fn handle(&self) -> RunHandle {
+        let sender = self.sender.clone().expect("the run has already ended");
+        RunHandle::new(self.run_id.clone(), sender)
+    }
+
+    pub fn log_param(&self, key: impl Into<String>, value: impl Display) {
+        self.send(Message::Param(Param {
+            key: key.into(),
+            value: format!("{}", value),
+        }));
+    }
+
+    /// Logs all fields of the config as params, see [`params`][crate::api::params] for the format.
+    pub fn log_params_from(&self, config: &impl Serialize) -> Result<(), serde_json::Error> {
+        for param in params::flatten(config)? {
+            self.send(Message::Param(param));
+        }
+        Ok(())
+    }
+
+    pub fn log_tag(&self, key: impl Into<String>, value: impl Display) {
+        self.send(Message::tag(key, value));
+    }
+
+    pub fn log_metric(&self, key: impl Into<String>, value: f64, step: i64) {
+        self.send(Message::Metric(Metric {
+            key: Cow::Owned(key.into()),
+            value,
+            timestamp: timestamp(),
+            step,
+        }));
+    }
+
+    /// Stores the file as `artifact_path/<file name>`, or directly below the artifact root.
+    ///
+    /// The file is uploaded in the background, errors are reported by the next [flush][LiveRun::flush].
+    pub fn log_artifact(
+        &self,
+        local_path: impl AsRef<Path>,
+        artifact_path: Option<&str>,
+    ) -> Result<(), StorageError> {
+        let artifact = Artifact::file(local_path.as_ref(), artifact_path)?;
+        self.send(Message::Artifact(artifact));
+        Ok(())
+    }
+
+    /// Stores all files below the directory, keeping their relative paths below `artifact_path`.
+    pub fn log_artifacts(
+        &self,
+        local_dir: impl AsRef<Path>,
+        artifact_path: Option<&str>,
+    ) -> Result<(), StorageError> {
+        for artifact in Artifact::dir(local_dir.as_ref(), artifact_path)? {
+            self.send(Message::Artifact(artifact));
+        }
+        Ok(())
+    }
+
+    pub fn log_text(&self, text: &str, artifact_file: &str) -> Result<(), StorageError> {
+        self.log_bytes(text.as_bytes().to_vec(), artifact_file)
+    }
+
+    /// Stores the value as JSON or YAML, depending on the extension of `artifact_file`.
+    pub fn log_dict(
+        &self,
+        value: &impl Serialize,
+        artifact_file: &str,
+    ) -> Result<(), StorageError> {
+        self.send(Message::Artifact(Artifact::dict(artifact_file, value)?));
+        Ok(())
+    }
+
+    pub fn log_bytes(&self, bytes: Vec<u8>, artifact_file: &str) -> Result<(), StorageError> {
+        self.send(Message::Artifact(Artifact::bytes(artifact_file, bytes)?));
+        Ok(())
+    }
+
+    /// Starts logging the utilization of the system every `interval` until the run ends.
+    ///
+    /// See [`system`][super::system] for the logged metrics.
+    pub fn monitor_system(&mut self, interval: Duration) {
+        if let Some(sender) = &self.sender {
+            let sender = sender.clone();
+            let monitor = SystemMonitor::spawn(interval, move |metric| {
+                let _ = sender.send(Message::Metric(metric));
+            });
+            self.monitor = Some(monitor);
+        }
+    }
+
+    /// Sends all buffered data to the server and waits for it to be stored.
+    pub fn flush(&self) -> Result<(), StorageError> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(Message::Flush(sender));
+        receiver
+            .recv()
+            .unwrap_or_else(|_| Err(anyhow!("the flushing thread has stopped")))
+    }
+
+    /// Flushes all buffered data and marks the run as [`RunStatus::Finished`].
+    ///
+    /// If the data could not be stored, the run is marked as failed instead and the error is returned.
+    pub fn finish(self) -> Result<RunInfo, StorageError> {
+        self.end(RunStatus::Finished)
+    }
+
+    /// Flushes all buffered data and marks the run as [`RunStatus::Failed`].
+    ///
+    /// The error and its causes are recorded as the note of the run.
+    pub fn fail(self, error: impl Into<anyhow::Error>) -> Result<RunInfo, StorageError> {
+        self.log_tag(tags::NOTE, format!("{:#}", error.into()));
+        self.end(RunStatus::Failed)
+    }
+
+    /// Flushes all buffered data and sets the final status of the run.
+    pub fn end(mut self, status: RunStatus) -> Result<RunInfo, StorageError> {
+        self.monitor.take();
+        self.send(Message::Finish(status));
+        self.join()?
+            .ok_or_else(|| anyhow!("the run was not updated by the flushing thread"))
+    }
+
+    fn send(&self, message: Message) {
+        if let Some(sender) = &self.sender {
+            // The worker only stops early if it panicked, which is reported by `join`.
+            let _ = sender.send(message);
+        }
+    }
+
+    fn join(&mut self) -> Result<Option<RunInfo>, StorageError> {
+        self.monitor.take();
+        guard::unregister(&self.run_id);
+        self.sender.take();
+        match self.worker.take() {
+            Some(worker) => worker
+                .join()
+                .unwrap_or_else(|_| Err(anyhow!("the flushing thread panicked"))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Drop for LiveRun {
+    fn drop(&mut self) {
+        if self.worker.is_some() {
+            let note = if std::thread::panicking() {
+                guard::take_panic_message().unwrap_or_else(|| "The thread panicked.".to_string())
+            } else {
+                "The run was dropped before it was finished.".to_string()
+            };
+            self.monitor.take();
+            self.log_tag(tags::NOTE, note);
+            self.send(Message::Finish(RunStatus::Failed));
+        }
+        let _ = self.join();
+    }
+}
+
+struct Worker {
+    client: Box<dyn Client + Send>,
+    info: RunInfo,
+    policy: FlushPolicy,
+    metrics: Vec<Metric<'static>>,
+    params: Vec<Param>,
+    tags: Vec<RunTag>,
+    /// All params received so far, to detect conflicting values before uploading them.
+    known_params: HashMap<String, String>,
+    /// The first error which did not occur while flushing, reported by the next flush.
+    deferred: Option<StorageError>,
+}
+
+impl Worker {
+    fn new(client: Box<dyn Client + Send>, info: RunInfo, policy: FlushPolicy) -> Self {
+        Worker {
+            client,
+            info,
+            policy,
+            metrics: Vec::new(),
+            params: Vec::new(),
+            tags: Vec::new(),
+            known_params: HashMap::new(),
+            deferred: None,
+        }
+    }
+
+    fn pending(&self) -> usize {
+        self.metrics.len() + self.params.len() + self.tags.len()
+    }
+
+    fn run(mut self, receiver: Receiver<Message>) -> Result<Option<RunInfo>, StorageError> {
+        let mut deadline = Instant::now() + self.policy.interval;
+        // After a failed upload, the next one waits for the interval even if the buffer is full.
+        let mut failed = false;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match receiver.recv_timeout(timeout) {
+                Ok(Message::Metric(metric)) => self.metrics.push(metric),
+                Ok(Message::Param(param)) => self.receive_param(param),
+                Ok(Message::Tag(tag)) => self.tags.push(tag),
+                Ok(Message::Artifact(artifact)) => {
+                    if let Err(error) = artifact.upload(self.client.as_mut(), &self.info) {
+                        self.deferred.get_or_insert(error);
+                    }
+                }
+                Ok(Message::Flush(reply)) => {
+                    let _ = reply.send(self.flush());
+                    deadline = Instant::now() + self.policy.interval;
+                    continue;
+                }
+                Ok(Message::Finish(status)) => {
+                    // The run is ended even if the last flush failed, so it is not left running,
+                    // but as failed and with a note explaining why.
+                    let flushed = self.flush();
+                    let status = match &flushed {
+                        Ok(()) => status,
+                        Err(error) => {
+                            let note = RunTag {
+                                key: tags::NOTE.to_string(),
+                                value: format!("The final flush failed: {:#}", error),
+                            };
+                            let _ = self.client.log_batch(&self.info.run_id, &[], &[], &[note]);
+                            RunStatus::Failed
+                        }
+                    };
+                    let info =
+                        self.client
+                            .update_run(&self.info.run_id, status, Some(timestamp()))?;
+                    flushed?;
+                    return Ok(Some(info));
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.flush()?;
+                    return Ok(None);
+                }
+            }
+            let full = !failed && self.pending() >= self.policy.max_pending;
+            if full || Instant::now() >= deadline {
+                // Failed items stay buffered and are retried with the next upload,
+                // deferred errors are kept for the next flush.
+                failed = self.upload().is_err();
+                deadline = Instant::now() + self.policy.interval;
+            }
+        }
+    }
+
+    fn receive_param(&mut self, param: Param) {
+        match self.known_params.get(&param.key) {
+            None => {
+                self.known_params
+                    .insert(param.key.clone(), param.value.clone());
+                self.params.push(param);
+            }
+            Some(first) if *first == param.value => {}
+            Some(first) => {
+                let conflict = BatchError::ConflictingParam {
+                    key: param.key,
+                    first: first.clone(),
+                    second: param.value,
+                };
+                self.deferred.get_or_insert(conflict.into());
+            }
+        }
+    }
+
+    /// Uploads the buffered data and reports errors which occurred since the last flush.
+    fn flush(&mut self) -> Result<(), StorageError> {
+        self.upload()?;
+        match self.deferred.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Uploads the buffered data, keeping what failed for the next attempt.
+    ///
+    /// Batches which can never succeed are dropped instead, so they are not retried forever,
+    /// and their error is reported by the next flush.
+    fn upload(&mut self) -> Result<(), StorageError> {
+        loop {
+            let (logged, result) = batch::log_batches(
+                self.client.as_mut(),
+                &self.info.run_id,
+                &self.metrics,
+                &self.params,
+                &self.tags,
+            );
+            self.metrics.drain(..logged.metrics);
+            self.params.drain(..logged.params);
+            self.tags.drain(..logged.tags);
+            match result {
+                Ok(()) => return Ok(()),
+                Err(error) if batch::is_permanent(&error) => {
+                    let rejected = batch::batches(&self.metrics, &self.params, &self.tags)
+                        .next()
+                        .map(|b| (b.metrics.len(), b.params.len(), b.tags.len()))
+                        .unwrap_or_default();
+                    self.metrics.drain(..rejected.0);
+                    self.params.drain(..rejected.1);
+                    self.tags.drain(..rejected.2);
+                    self.deferred.get_or_insert(error.into());
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FlushPolicy, LiveRun};
+    use crate::{
+        api::{
+            error::BatchError,
+            run::{Param, RunStatus},
+            tags,
+        },
+        testing::{Fault, MockServer},
+        Client,
+    };
+
+    fn policy(max_pending: usize) -> FlushPolicy {
+        FlushPolicy {
+            max_pending,
+            interval: Duration::from_secs(3600),
+        }
+    }
+
+    /// Waits for the background thread to store what the condition checks.
+    fn wait_until(condition: impl Fn() -> bool) -> bool {
+        for _ in 0..500 {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn flushes_when_full_or_after_the_interval() {
+        let server = MockServer::start();
+        let run = LiveRun::with_policy(server.client(), &"0".into(), policy(3)).unwrap();
+        let id = run.run_id().clone();
+        let history = || server.store().get_metric_history(&id, "loss").unwrap();
+        run.log_metric("loss", 0.5, 0);
+        run.log_metric("loss", 0.4, 1);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(history().is_empty());
+        run.log_metric("loss", 0.3, 2);
+        assert!(wait_until(|| history().len() == 3));
+        run.finish().unwrap();
+
+        let interval = FlushPolicy {
+            max_pending: 1000,
+            interval: Duration::from_millis(20),
+        };
+        let run = LiveRun::with_policy(server.client(), &"0".into(), interval).unwrap();
+        let id = run.run_id().clone();
+        run.log_param("lr", 0.1);
+        let params = || server.store().get_run(&id).unwrap().data.params;
+        assert!(wait_until(
+            || params().is_some_and(|params| !params.is_empty())
+        ));
+        run.finish().unwrap();
+    }
+
+    #[test]
+    fn conflicting_params_survive_automatic_flushes() {
+        let server = MockServer::start();
+        let run = LiveRun::with_policy(server.client(), &"0".into(), policy(1)).unwrap();
+        let id = run.run_id().clone();
+        run.log_param("lr", 0.1);
+        run.log_param("lr", 0.2);
+        run.log_metric("loss", 0.5, 0);
+        let error = run.finish().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(BatchError::ConflictingParam { .. })
+        ));
+        let run = server.store().get_run(&id).unwrap();
+        assert_eq!(run.info.status, RunStatus::Failed);
+        assert_eq!(run.data.metrics.unwrap().len(), 1);
+        let tags = run.data.tags.unwrap();
+        let note = tags.iter().find(|tag| tag.key == tags::NOTE).unwrap();
+        assert!(note.value.contains("lr"));
+    }
+
+    #[test]
+    fn rejected_batches_are_dropped() {
+        let server = MockServer::start();
+        let run = LiveRun::with_policy(server.client(), &"0".into(), policy(2)).unwrap();
+        let id = run.run_id().clone();
+        let param = Param {
+            key: "lr".to_string(),
+            value: "0.1".to_string(),
+        };
+        server.store().log_batch(&id, &[], &[param], &[]).unwrap();
+        // The server rejects this batch, which must not stay buffered and be retried.
+        run.log_param("lr", 0.2);
+        run.log_metric("loss", 0.5, 0);
+        assert!(wait_until(|| server
+            .requests()
+            .iter()
+            .any(|r| r.path.ends_with("runs/log-batch"))));
+        run.log_metric("loss", 0.4, 1);
+        run.log_metric("loss", 0.3, 2);
+        let error = run.flush().unwrap_err();
+        assert!(error.to_string().contains("lr"), "{:#}", error);
+        let history = server.store().get_metric_history(&id, "loss").unwrap();
+        assert_eq!(history.len(), 2);
+        run.finish().unwrap();
+        let uploads = server.requests();
+        let uploads = uploads
+            .iter()
+            .filter(|r| r.path.ends_with("runs/log-batch"))
+            .count();
+        // The rejected batch and one batch with the later metrics.
+        assert_eq!(uploads, 2);
+    }
+
+    #[test]
+    fn failed_uploads_wait_for_the_interval() {
+        let server = MockServer::start();
+        let run = LiveRun::with_policy(server.client(), &"0".into(), policy(1)).unwrap();
+        server.inject(Fault::on("runs/log-batch"));
+        for step in 0..10 {
+            run.log_metric("loss", 0.5, step);
+        }
+        assert!(run.flush().is_err());
+        let uploads = server.requests();
+        let uploads = uploads
+            .iter()
+            .filter(|r| r.path.ends_with("runs/log-batch"));
+        // One automatic upload when the buffer was full, and the explicit flush.
+        assert_eq!(uploads.count(), 2);
+
+        server.clear_faults();
+        let id = run.run_id().clone();
+        run.finish().unwrap();
+        assert_eq!(
+            server
+                .store()
+                .get_metric_history(&id, "loss")
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Accumulators which aggregate metrics before they are logged.
+//!
+//! Pass them to [`TrackingRun::update_metric`][super::TrackingRun::update_metric]
+//! to log the aggregated value every `n` updates, and to
+//! [`TrackingRun::log_aggregate`][super::TrackingRun::log_aggregate] at epoch boundaries:
+//!
+//! ```
+//! use mlflow::tracking::{metrics::MeanMetric, TrackingRun};
+//!
+//! let mut run = TrackingRun::new();
+//! let mut loss = MeanMetric::new("train_loss").every(100);
+//! for epoch in 0..3 {
+//!     for batch in 0..1000 {
+//!         run.update_metric(&mut loss, 1.0 / (epoch * 1000 + batch + 1) as f64);
+//!     }
+//!     run.log_aggregate(&mut loss);
+//! }
+//! ```
+
+use crate::api::run::Metric;
+
+/// Aggregates the values of a metric over a window of updates.
+pub trait Aggregate {
+    /// The key the aggregated value is logged with.
+    fn key(&self) -> &str;
+    /// Adds a value and returns the aggregate if it is due to be logged.
+    fn update(&mut self, value: f64) -> Option<f64>;
+    /// Returns the aggregate of the current window and starts a new one.
+    ///
+    /// Returns `None` if there were no updates since the last aggregate was returned.
+    fn take(&mut self) -> Option<f64>;
+}
+
+/// Counts the updates of a window, to report an aggregate every `n` updates.
+#[derive(Debug, Clone, Default)]
+struct Window {
+    every: Option<usize>,
+    count: usize,
+}
+
+impl Window {
+    /// Counts an update and returns whether the window is full.
+    fn update(&mut self) -> bool {
+        self.count += 1;
+        self.every.is_some_and(|every| self.count >= every)
+    }
+
+    /// Starts a new window and returns whether the previous one had any updates.
+    fn reset(&mut self) -> bool {
+        std::mem::take(&mut self.count) > 0
+    }
+}
+
+macro_rules! impl_every {
+    ($name:ident) => {
+        impl $name {
+            /// Reports the aggregate every `n` updates, instead of only when it is taken.
+            pub fn every(mut self, n: usize) -> Self {
+                self.window.every = Some(n.max(1));
+                self
+            }
+        }
+    };
+}
+
+/// The mean of the values since the last aggregate.
+#[derive(Debug, Clone)]
+pub struct MeanMetric {
+    key: String,
+    window: Window,
+    sum: f64,
+}
+
+impl MeanMetric {
+    pub fn new(key: impl Into<String>) -> Self {
+        MeanMetric {
+            key: key.into(),
+            window: Window::default(),
+            sum: 0.0,
+        }
+    }
+}
+
+impl_every!(MeanMetric);
+
+impl Aggregate for MeanMetric {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn update(&mut self, value: f64) -> Option<f64> {
+        self.sum += value;
+        if self.window.update() {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    fn take(&mut self) -> Option<f64> {
+        let count = self.window.count as f64;
+        let sum = std::mem::take(&mut self.sum);
+        self.window.reset().then(|| sum / count)
+    }
+}
+
+/// An exponential moving average, which smoothes the values across windows.
+///
+/// Each update moves the average by `alpha` towards the new value.
+/// Taking the aggregate does not reset the average.
+#[derive(Debug, Clone)]
+pub struct EmaMetric {
+    key: String,
+    window: Window,
+    alpha: f64,
+    average: Option<f64>,
+}
+
+impl EmaMetric {
+    /// `alpha` is clamped to `(0, 1]`, where `1` disables the smoothing.
+    pub fn new(key: impl Into<String>, alpha: f64) -> Self {
+        EmaMetric {
+            key: key.into(),
+            window: Window::default(),
+            alpha: alpha.clamp(f64::MIN_POSITIVE, 1.0),
+            average: None,
+        }
+    }
+}
+
+impl_every!(EmaMetric);
+
+impl Aggregate for EmaMetric {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn update(&mut self, value: f64) -> Option<f64> {
+        let average = match self.average {
+            Some(average) => average + self.alpha * (value - average),
+            None => value,
+        };
+        self.average = Some(average);
+        if self.window.update() {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    fn take(&mut self) -> Option<f64> {
+        if self.window.reset() {
+            self.average
+        } else {
+            None
+        }
+    }
+}
+
+/// The smallest value since the last aggregate.
+#[derive(Debug, Clone)]
+pub struct MinMetric {
+    key: String,
+    window: Window,
+    min: f64,
+}
+
+impl MinMetric {
+    pub fn new(key: impl Into<String>) -> Self {
+        MinMetric {
+            key: key.into(),
+            window: Window::default(),
+            min: f64::INFINITY,
+        }
+    }
+}
+
+impl_every!(MinMetric);
+
+impl Aggregate for MinMetric {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn update(&mut self, value: f64) -> Option<f64> {
+        self.min = self.min.min(value);
+        if self.window.update() {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    fn take(&mut self) -> Option<f64> {
+        let min = std::mem::replace(&mut self.min, f64::INFINITY);
+        self.window.reset().then_some(min)
+    }
+}
+
+/// The largest value since the last aggregate.
+#[derive(Debug, Clone)]
+pub struct MaxMetric {
+    key: String,
+    window: Window,
+    max: f64,
+}
+
+impl MaxMetric {
+    pub fn new(key: impl Into<String>) -> Self {
+        MaxMetric {
+            key: key.into(),
+            window: Window::default(),
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl_every!(MaxMetric);
+
+impl Aggregate for MaxMetric {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn update(&mut self, value: f64) -> Option<f64> {
+        self.max = self.max.max(value);
+        if self.window.update() {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    fn take(&mut self) -> Option<f64> {
+        let max = std::mem::replace(&mut self.max, f64::NEG_INFINITY);
+        self.window.reset().then_some(max)
+    }
+}
+
+/// Whether smaller or larger values of a metric are better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    Minimize,
+    Maximize,
+}
+
+impl Goal {
+    pub(crate) fn is_better(self, value: f64, than: f64) -> bool {
+        match self {
+            Goal::Minimize => value < than,
+            Goal::Maximize => value > than,
+        }
+    }
+
+    /// The best of the metrics with the key, the earliest one if several are equally good.
+    pub(crate) fn best<'a, 'm>(
+        self,
+        metrics: impl IntoIterator<Item = &'a Metric<'m>>,
+        key: &str,
+    ) -> Option<&'a Metric<'m>>
+    where
+        'm: 'a,
+    {
+        metrics
+            .into_iter()
+            .filter(|metric| metric.key == key && !metric.value.is_nan())
+            .fold(None, |best: Option<&Metric>, metric| match best {
+                Some(best) if !self.is_better(metric.value, best.value) => Some(best),
+                _ => Some(metric),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aggregate, EmaMetric, Goal, MaxMetric, MeanMetric, MinMetric};
+    use crate::api::run::Metric;
+
+    #[test]
+    fn aggregates_are_reported_every_n_updates() {
+        let mut mean = MeanMetric::new("loss").every(2);
+        let reported = [1.0, 3.0, 5.0, 7.0, 9.0]
+            .iter()
+            .map(|value| mean.update(*value))
+            .collect::<Vec<_>>();
+        assert_eq!(reported, vec![None, Some(2.0), None, Some(6.0), None]);
+        assert_eq!(mean.take(), Some(9.0));
+        assert_eq!(mean.take(), None);
+
+        let mut ema = EmaMetric::new("loss", 0.5);
+        ema.update(4.0);
+        ema.update(2.0);
+        assert_eq!(ema.take(), Some(3.0));
+
+        let mut min = MinMetric::new("loss");
+        let mut max = MaxMetric::new("loss");
+        for value in &[2.0, -1.0, 5.0] {
+            min.update(*value);
+            max.update(*value);
+        }
+        assert_eq!((min.take(), max.take()), (Some(-1.0), Some(5.0)));
+    }
+
+    #[test]
+    fn best_metric_depends_on_goal() {
+        let metrics = [(0.5, 0), (0.2, 1), (0.9, 2), (0.2, 3)]
+            .iter()
+            .map(|&(value, step)| Metric {
+                key: "loss".into(),
+                value,
+                timestamp: 0,
+                step,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(Goal::Minimize.best(&metrics, "loss").unwrap().step, 1);
+        assert_eq!(Goal::Maximize.best(&metrics, "loss").unwrap().step, 2);
+        assert!(Goal::Minimize.best(&metrics, "acc").is_none());
+    }
+}
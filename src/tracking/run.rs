@@ -1,12 +1,31 @@
-use std::{borrow::Cow, fmt::Display};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use serde::Serialize;
 
 use crate::{
     api::{
-        error::StorageError,
-        limits,
-        run::{Metric, Param, Run, RunTag},
+        artifact::validate_path,
+        error::{GetError, StorageError},
+        params,
+        run::{Metric, Param, Run, RunInfo, RunStatus, RunTag},
+        tags,
+    },
+    timestamp,
+    tracking::{
+        artifact::{self, Artifact, Table},
+        batch, context,
+        metrics::{Aggregate, Goal},
+        system::SystemMonitor,
     },
-    timestamp, Client, ExperimentId,
+    Client, ExperimentId, RunId,
 };
 
 /// A MLflow Run.
@@ -14,11 +33,24 @@ use crate::{
 /// This can be created using [`Experiment::create_run`].
 ///
 /// It allows logging [parameters][self::Run::log_param()] and [metrics][self::Run::log_metric()].
+///
+/// Everything is kept in memory until the run is [submitted][TrackingRun::submit],
+/// so a panic or signal before that loses the run, and a [resumed][TrackingRun::resume] run
+/// stays running on the server. Unlike [`LiveRun`][super::LiveRun], it is not marked as failed
+/// or killed in these cases, so use a `LiveRun` for runs which should be.
 pub struct TrackingRun<'b> {
     start_time: i64,
     param_buffer: Vec<Param>,
     tag_buffer: Vec<RunTag>,
-    metric_buffer: Vec<Vec<Metric<'b>>>,
+    metric_buffer: Vec<Metric<'b>>,
+    artifacts: Vec<Artifact>,
+    tables: BTreeMap<String, Table>,
+    name: Option<String>,
+    children: Vec<TrackingRun<'b>>,
+    system_metrics: Option<(SystemMonitor, Arc<Mutex<Vec<Metric<'static>>>>)>,
+    last_steps: HashMap<String, i64>,
+    best: Vec<(String, Goal)>,
+    resumed: Option<Run>,
 }
 
 impl<'b> TrackingRun<'b> {
@@ -27,15 +59,72 @@ impl<'b> TrackingRun<'b> {
             start_time: timestamp(),
             param_buffer: Vec::new(),
             tag_buffer: Vec::new(),
-            metric_buffer: vec![Vec::with_capacity(limits::BATCH_METRICS)],
+            metric_buffer: Vec::new(),
+            artifacts: Vec::new(),
+            tables: BTreeMap::new(),
+            name: None,
+            children: Vec::new(),
+            system_metrics: None,
+            last_steps: HashMap::new(),
+            best: Vec::new(),
+            resumed: None,
+        }
+    }
+
+    /// Continues an existing run, for example after a job was preempted.
+    ///
+    /// The run is marked as [`RunStatus::Running`] again. When it is [submitted][TrackingRun::submit],
+    /// the logged data is added to the existing run and params which are already present are skipped.
+    /// Use [`last_step`][TrackingRun::last_step] to continue the step numbering of the metrics.
+    pub fn resume(client: &mut dyn Client, id: &RunId) -> Result<Self, GetError> {
+        let run = client.get_run(id)?;
+        let info = client.update_run(id, RunStatus::Running, None)?;
+        let mut resumed = TrackingRun::new();
+        resumed.start_time = info.start_time;
+        for metric in run.data.metrics.iter().flatten() {
+            resumed
+                .last_steps
+                .insert(metric.key.to_string(), metric.step);
+        }
+        resumed.resumed = Some(Run {
+            info,
+            data: run.data,
+        });
+        Ok(resumed)
+    }
+
+    /// Resumes the run given by the `MLFLOW_RUN_ID` environment variable, if it is set.
+    pub fn from_env(client: &mut dyn Client) -> Result<Self, GetError> {
+        match std::env::var("MLFLOW_RUN_ID") {
+            Ok(id) if !id.is_empty() => Self::resume(client, &RunId::from(id)),
+            _ => Ok(TrackingRun::new()),
         }
     }
 
+    /// The highest step logged for the metric, including steps logged before the run was resumed.
+    pub fn last_step(&self, key: &str) -> Option<i64> {
+        self.last_steps.get(key).copied()
+    }
+
+    /// Sets the name shown for this run in the MLflow UI.
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = Some(name.into());
+    }
+
+    /// Adds a nested run, which is submitted together with this one.
+    ///
+    /// The child is created in the same experiment and linked to this run by the
+    /// [`PARENT_RUN_ID`][tags::PARENT_RUN_ID] tag, so it appears below it in the MLflow UI.
+    pub fn child(&mut self) -> &mut TrackingRun<'b> {
+        self.children.push(TrackingRun::new());
+        self.children.last_mut().unwrap()
+    }
+
+    pub(crate) fn add_child(&mut self, child: TrackingRun<'b>) {
+        self.children.push(child);
+    }
+
     pub fn log_param(&mut self, key: impl Into<String>, value: impl Display) {
-        assert!(
-            self.param_buffer.len() < limits::BATCH_PARAMS,
-            "TrackingRun supports only up to 100 params for now"
-        );
         let param = Param {
             key: key.into(),
             value: format!("{}", value),
@@ -43,11 +132,13 @@ impl<'b> TrackingRun<'b> {
         self.param_buffer.push(param);
     }
 
+    /// Logs all fields of the config as params, see [`params`][crate::api::params] for the format.
+    pub fn log_params_from(&mut self, config: &impl Serialize) -> Result<(), serde_json::Error> {
+        self.param_buffer.extend(params::flatten(config)?);
+        Ok(())
+    }
+
     pub fn log_tag(&mut self, key: impl Into<String>, value: impl Display) {
-        assert!(
-            self.tag_buffer.len() < limits::BATCH_TAGS,
-            "TrackingRun supports only up to 100 tags for now"
-        );
         let tag = RunTag {
             key: key.into(),
             value: format!("{}", value),
@@ -56,31 +147,415 @@ impl<'b> TrackingRun<'b> {
     }
 
     pub fn log_metric(&mut self, key: impl Into<Cow<'b, str>>, value: f64, step: i64) {
-        if self.metric_buffer.last().unwrap().len() == limits::BATCH_METRICS {
-            self.metric_buffer
-                .push(Vec::with_capacity(limits::BATCH_METRICS));
-        }
         let metric = Metric {
             key: key.into(),
             value,
             timestamp: timestamp(),
             step,
         };
-        self.metric_buffer.last_mut().unwrap().push(metric);
+        let last_step = self
+            .last_steps
+            .entry(metric.key.to_string())
+            .or_insert(step);
+        *last_step = step.max(*last_step);
+        self.metric_buffer.push(metric);
+    }
+
+    /// Logs the metric with the step following the [last step][TrackingRun::last_step] of the key,
+    /// or step `0` if it was not logged before. Returns the step.
+    pub fn log_metric_auto(&mut self, key: impl Into<Cow<'b, str>>, value: f64) -> i64 {
+        let key = key.into();
+        let step = self.last_step(&key).map_or(0, |step| step + 1);
+        self.log_metric(key, value, step);
+        step
+    }
+
+    /// Adds the value to the accumulator and logs the aggregate with the next step when it is due.
+    ///
+    /// See [`metrics`][super::metrics] for the available accumulators.
+    pub fn update_metric(&mut self, metric: &mut impl Aggregate, value: f64) {
+        if let Some(aggregate) = metric.update(value) {
+            self.log_metric_auto(metric.key().to_string(), aggregate);
+        }
+    }
+
+    /// Logs the aggregate of the current window with the next step, e.g. at the end of an epoch.
+    pub fn log_aggregate(&mut self, metric: &mut impl Aggregate) {
+        if let Some(aggregate) = metric.take() {
+            self.log_metric_auto(metric.key().to_string(), aggregate);
+        }
+    }
+
+    /// Logs the best value of the metric as `best_<key>` when the run is submitted.
+    ///
+    /// The summary has the step of the best value. For resumed runs, the previous best is kept
+    /// if it is better than the values logged since.
+    pub fn track_best(&mut self, key: impl Into<String>, goal: Goal) {
+        let key = key.into();
+        self.best.retain(|(existing, _)| *existing != key);
+        self.best.push((key, goal));
+    }
+
+    /// Stores the file as `artifact_path/<file name>`, or directly below the artifact root.
+    ///
+    /// The file is read when the run is submitted.
+    pub fn log_artifact(
+        &mut self,
+        local_path: impl AsRef<Path>,
+        artifact_path: Option<&str>,
+    ) -> Result<(), StorageError> {
+        let artifact = Artifact::file(local_path.as_ref(), artifact_path)?;
+        self.artifacts.push(artifact);
+        Ok(())
+    }
+
+    /// Stores all files below the directory, keeping their relative paths below `artifact_path`.
+    pub fn log_artifacts(
+        &mut self,
+        local_dir: impl AsRef<Path>,
+        artifact_path: Option<&str>,
+    ) -> Result<(), StorageError> {
+        let artifacts = Artifact::dir(local_dir.as_ref(), artifact_path)?;
+        self.artifacts.extend(artifacts);
+        Ok(())
+    }
+
+    pub fn log_text(&mut self, text: &str, artifact_file: &str) -> Result<(), StorageError> {
+        self.log_bytes(text.as_bytes().to_vec(), artifact_file)
+    }
+
+    /// Stores the value as JSON or YAML, depending on the extension of `artifact_file`.
+    pub fn log_dict(
+        &mut self,
+        value: &impl Serialize,
+        artifact_file: &str,
+    ) -> Result<(), StorageError> {
+        self.artifacts.push(Artifact::dict(artifact_file, value)?);
+        Ok(())
+    }
+
+    pub fn log_bytes(&mut self, bytes: Vec<u8>, artifact_file: &str) -> Result<(), StorageError> {
+        self.artifacts.push(Artifact::bytes(artifact_file, bytes)?);
+        Ok(())
+    }
+
+    /// Appends rows to the table stored as `artifact_file`, which the UI shows in its evaluation view.
+    ///
+    /// Each row has to serialize to a sequence with one value per column, e.g. a tuple or a `Vec`.
+    /// Calling this again with the same file, e.g. once per step, adds the rows to the same table,
+    /// so the columns have to stay the same.
+    /// Tables are written when the run is submitted. The rows of a [resumed][TrackingRun::resume]
+    /// run are appended to the table it already has, which then has to have the same columns.
+    pub fn log_table<R: Serialize>(
+        &mut self,
+        artifact_file: &str,
+        columns: &[&str],
+        rows: impl IntoIterator<Item = R>,
+    ) -> Result<(), StorageError> {
+        if !artifact_file.ends_with(".json") {
+            bail!(
+                "the table {:?} must be stored as a .json file",
+                artifact_file
+            );
+        }
+        validate_path(artifact_file)?;
+        let table = self
+            .tables
+            .entry(artifact_file.to_string())
+            .or_insert_with(|| Table::new(columns));
+        if !table.has_columns(columns) {
+            bail!(
+                "the columns {:?} differ from the columns of the table {}",
+                columns,
+                artifact_file
+            );
+        }
+        table.append(rows)
+    }
+
+    /// Starts sampling the utilization of the system every `interval` until the run is submitted.
+    ///
+    /// See [`system`][super::system] for the logged metrics.
+    pub fn monitor_system(&mut self, interval: Duration) {
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let monitor = {
+            let samples = samples.clone();
+            SystemMonitor::spawn(interval, move |metric| samples.lock().unwrap().push(metric))
+        };
+        self.system_metrics = Some((monitor, samples));
     }
 
+    /// Creates the run and its children on the server and uploads all logged data.
+    ///
+    /// The data is packed into as few requests as the [`limits`][crate::api::limits] allow.
+    /// Fails before creating the run if a param was logged with conflicting values,
+    /// and marks a resumed run as failed if a param conflicts with its logged params.
+    /// [Resumed][TrackingRun::resume] runs are not created again and keep their experiment.
+    ///
+    /// If the upload fails, the run is marked as [`RunStatus::Failed`] with the error as its note.
     pub fn submit(
         self,
         client: &mut dyn Client,
         experiment: &ExperimentId,
     ) -> Result<Run, StorageError> {
-        let mut run = client.create_run(experiment, self.start_time, &[])?;
+        self.submit_nested(client, experiment, None)
+    }
+
+    fn submit_nested(
+        mut self,
+        client: &mut dyn Client,
+        experiment: &ExperimentId,
+        parent: Option<&RunId>,
+    ) -> Result<Run, StorageError> {
+        if let Some((mut monitor, samples)) = self.system_metrics.take() {
+            monitor.stop();
+            self.metric_buffer.extend(samples.lock().unwrap().drain(..));
+        }
+        self.log_best();
+        let params = batch::dedup_params(&self.param_buffer);
+        let name = self.name.take().map(|name| RunTag {
+            key: tags::RUN_NAME.to_string(),
+            value: name,
+        });
+        let (mut run, params) = match self.resumed.take() {
+            Some(mut run) => {
+                // The run is already running again, so conflicts have to fail it below.
+                let logged = run.data.params.take().unwrap_or_default();
+                let params = params.and_then(|params| batch::skip_logged(params, &logged));
+                self.tag_buffer.extend(name);
+                (run, params)
+            }
+            None => {
+                let params = params?;
+                let mut run_tags = context::tags();
+                run_tags.extend(name);
+                if let Some(parent) = parent {
+                    run_tags.push(RunTag {
+                        key: tags::PARENT_RUN_ID.to_string(),
+                        value: parent.as_ref().to_string(),
+                    });
+                }
+                let run = client.create_run(experiment, self.start_time, &run_tags)?;
+                (run, Ok(params))
+            }
+        };
+        let experiment = &run.info.experiment_id.clone();
         let id = &run.info.run_id.clone();
-        client.log_batch(id, &[], &self.param_buffer, &self.tag_buffer)?;
-        for buffer in &self.metric_buffer {
-            client.log_batch(id, buffer, &[], &[])?;
+        let children = std::mem::take(&mut self.children);
+        let result = params
+            .map(|params| self.param_buffer = params)
+            .map_err(StorageError::from)
+            .and_then(|()| self.write_tables(client, &run))
+            .and_then(|()| self.upload(client, &run.info))
+            .and_then(|()| {
+                children.into_iter().try_for_each(|child| {
+                    child.submit_nested(client, experiment, Some(id))?;
+                    Ok(())
+                })
+            });
+        if let Err(error) = result {
+            let note = RunTag {
+                key: tags::NOTE.to_string(),
+                value: format!("{:#}", error),
+            };
+            let _ = client.log_batch(id, &[], &[], &[note]);
+            let _ = client.update_run(id, RunStatus::Failed, Some(timestamp()));
+            return Err(error);
         }
-        run.info = client.update_run(id, crate::api::run::RunStatus::Finished, timestamp())?;
+        run.info = client.update_run(id, RunStatus::Finished, Some(timestamp()))?;
         Ok(run)
     }
+
+    fn log_best(&mut self) {
+        let previous = self
+            .resumed
+            .iter()
+            .flat_map(|run| run.data.metrics.iter().flatten());
+        let mut summaries = Vec::new();
+        for (key, goal) in &self.best {
+            let summary_key = format!("best_{}", key);
+            let previous = previous.clone().find(|metric| metric.key == summary_key);
+            if let Some(best) = goal.best(&self.metric_buffer, key) {
+                if previous.is_none_or(|previous| goal.is_better(best.value, previous.value)) {
+                    summaries.push(Metric {
+                        key: Cow::Owned(summary_key),
+                        value: best.value,
+                        timestamp: timestamp(),
+                        step: best.step,
+                    });
+                }
+            }
+        }
+        self.metric_buffer.extend(summaries);
+    }
+
+    /// Turns the tables into artifacts and registers them in the tags of the run.
+    ///
+    /// Tables which the run already has are downloaded and the new rows are appended to them.
+    fn write_tables(&mut self, client: &mut dyn Client, run: &Run) -> Result<(), StorageError> {
+        if self.tables.is_empty() {
+            return Ok(());
+        }
+        let mut existing = run.data.tags.iter().flatten();
+        let existing = existing
+            .find(|tag| tag.key == tags::LOGGED_ARTIFACTS)
+            .map(|tag| tag.value.as_str());
+        let logged = artifact::logged_tables(existing)?;
+        let tag = artifact::logged_tables_tag(existing, self.tables.keys().map(String::as_str))?;
+        self.tag_buffer.push(tag);
+        for (path, table) in std::mem::take(&mut self.tables) {
+            let table = if logged.contains(&path) {
+                let stored = client.download_artifact(&run.info, &path)?;
+                let mut stored: Table = serde_json::from_slice(&stored)
+                    .with_context(|| format!("the table {} is not valid", path))?;
+                stored.extend(table)?;
+                stored
+            } else {
+                table
+            };
+            self.artifacts.push(Artifact::dict(&path, &table)?);
+        }
+        Ok(())
+    }
+
+    fn upload(&self, client: &mut dyn Client, run: &RunInfo) -> Result<(), StorageError> {
+        let (_, result) = batch::log_batches(
+            client,
+            &run.run_id,
+            &self.metric_buffer,
+            &self.param_buffer,
+            &self.tag_buffer,
+        );
+        result?;
+        self.artifacts
+            .iter()
+            .try_for_each(|artifact| artifact.upload(client, run))
+    }
+}
+
+impl Default for TrackingRun<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrackingRun;
+    use crate::{
+        api::{run::RunStatus, tags},
+        testing::MockServer,
+        tracking::LiveRun,
+        Client, Paginate,
+    };
+
+    #[test]
+    fn children_are_tagged_with_their_parent() {
+        let server = MockServer::start();
+        let mut client = server.client();
+        let mut run = TrackingRun::new();
+        for fold in 0..2 {
+            run.child().log_param("fold", fold);
+        }
+        let parent = run.submit(&mut client, &"0".into()).unwrap().info;
+        let live = LiveRun::start(server.client(), &"0".into()).unwrap();
+        let child = live.start_child(server.client()).unwrap();
+        let live_child = child.run_id().clone();
+        child.finish().unwrap();
+        let live_parent = live.finish().unwrap();
+
+        let children = client.child_runs(&parent).iter();
+        let children = children.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(children.len(), 2);
+        for child in &children {
+            let tags = child.data.tags.as_deref().unwrap_or_default();
+            let parent_tag = tags.iter().find(|tag| tag.key == tags::PARENT_RUN_ID);
+            assert_eq!(parent_tag.unwrap().value, parent.run_id.as_ref());
+        }
+        let children = client.child_runs(&live_parent).iter();
+        let children = children.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].info.run_id, live_child);
+    }
+
+    #[test]
+    fn resumed_runs_continue_where_they_stopped() {
+        let server = MockServer::start();
+        let mut client = server.client();
+        let mut run = TrackingRun::new();
+        run.log_param("lr", 0.1);
+        for step in 0..3 {
+            run.log_metric("loss", 1.0 / (step + 1) as f64, step);
+        }
+        let info = run.submit(&mut client, &"0".into()).unwrap().info;
+        client
+            .update_run(&info.run_id, RunStatus::Killed, Some(info.start_time + 1))
+            .unwrap();
+
+        let mut resumed = TrackingRun::resume(&mut client, &info.run_id).unwrap();
+        assert_eq!(
+            server.store().get_run(&info.run_id).unwrap().info.status,
+            RunStatus::Running
+        );
+        let step = resumed.last_step("loss").unwrap() + 1;
+        assert_eq!(step, 3);
+        resumed.log_param("lr", 0.1);
+        resumed.log_param("seed", 7);
+        resumed.log_metric("loss", 0.2, step);
+        let run = resumed.submit(&mut client, &"0".into()).unwrap();
+        assert_eq!(run.info.run_id, info.run_id);
+
+        let mut store = server.store();
+        let stored = store.get_run(&info.run_id).unwrap();
+        assert_eq!(stored.info.status, RunStatus::Finished);
+        assert_eq!(stored.info.start_time, info.start_time);
+        assert_eq!(stored.data.params.unwrap().len(), 2);
+        let history = store.get_metric_history(&info.run_id, "loss").unwrap();
+        let steps = history.iter().map(|metric| metric.step).collect::<Vec<_>>();
+        assert_eq!(steps, [0, 1, 2, 3]);
+        let runs = store.runs(&[&"0".into()]).iter().count();
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn conflicting_params_fail_resumed_runs() {
+        let server = MockServer::start();
+        let mut client = server.client();
+        let mut run = TrackingRun::new();
+        run.log_param("lr", 0.1);
+        let id = run.submit(&mut client, &"0".into()).unwrap().info.run_id;
+
+        let mut resumed = TrackingRun::resume(&mut client, &id).unwrap();
+        resumed.log_param("lr", 0.2);
+        assert!(resumed.submit(&mut client, &"0".into()).is_err());
+        let run = server.store().get_run(&id).unwrap();
+        assert_eq!(run.info.status, RunStatus::Failed);
+        let tags = run.data.tags.unwrap();
+        assert!(tags.iter().any(|tag| tag.key == tags::NOTE));
+    }
+
+    #[test]
+    fn resumed_runs_append_to_their_tables() {
+        let server = MockServer::start();
+        let mut client = server.client();
+        let mut run = TrackingRun::new();
+        run.log_table("eval.json", &["x"], vec![(1,)]).unwrap();
+        let id = run.submit(&mut client, &"0".into()).unwrap().info.run_id;
+
+        let mut resumed = TrackingRun::resume(&mut client, &id).unwrap();
+        resumed.log_table("eval.json", &["x"], vec![(2,)]).unwrap();
+        resumed.submit(&mut client, &"0".into()).unwrap();
+        let table = server.store().artifact(&id, "eval.json").unwrap().to_vec();
+        let table: serde_json::Value = serde_json::from_slice(&table).unwrap();
+        assert_eq!(table["data"], serde_json::json!([[1], [2]]));
+
+        let mut resumed = TrackingRun::resume(&mut client, &id).unwrap();
+        resumed.log_table("eval.json", &["y"], vec![(3,)]).unwrap();
+        assert!(resumed.submit(&mut client, &"0".into()).is_err());
+        let table = server.store().artifact(&id, "eval.json").unwrap().to_vec();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&table).unwrap()["data"],
+            serde_json::json!([[1], [2]])
+        );
+    }
 }
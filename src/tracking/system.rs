@@ -0,0 +1,259 @@
+//! Sampling of the system utilization during a run.
+//!
+//! Enabled with [`LiveRun::monitor_system`][super::LiveRun::monitor_system]
+//! or [`TrackingRun::monitor_system`][super::TrackingRun::monitor_system].
+//! The metrics are logged using the keys below, which match the system metrics of MLflow 2.8.
+
+use std::{
+    borrow::Cow,
+    fs,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{api::run::Metric, timestamp};
+
+pub const CPU_UTILIZATION_PERCENTAGE: &str = "system/cpu_utilization_percentage";
+pub const SYSTEM_MEMORY_USAGE_MEGABYTES: &str = "system/system_memory_usage_megabytes";
+pub const SYSTEM_MEMORY_USAGE_PERCENTAGE: &str = "system/system_memory_usage_percentage";
+pub const DISK_USAGE_PERCENTAGE: &str = "system/disk_usage_percentage";
+pub const DISK_USAGE_MEGABYTES: &str = "system/disk_usage_megabytes";
+pub const DISK_AVAILABLE_MEGABYTES: &str = "system/disk_available_megabytes";
+pub const NETWORK_RECEIVE_MEGABYTES: &str = "system/network_receive_megabytes";
+pub const NETWORK_TRANSMIT_MEGABYTES: &str = "system/network_transmit_megabytes";
+
+const MEGABYTE: f64 = 1_000_000.0;
+
+/// Periodically samples the utilization of the system in a background thread.
+///
+/// The step of the metrics is the index of the sample.
+/// Currently, only Linux is supported, on other systems no metrics are sampled.
+///
+/// The sampling stops when the monitor is [stopped][SystemMonitor::stop] or dropped.
+pub(crate) struct SystemMonitor {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SystemMonitor {
+    pub fn spawn(
+        interval: Duration,
+        mut sink: impl FnMut(Metric<'static>) + Send + 'static,
+    ) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name("mlflow-system-metrics".to_string())
+            .spawn(move || {
+                let mut sampler = Sampler::new();
+                let mut step = 0;
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let timestamp = timestamp();
+                    for (key, value) in sampler.sample() {
+                        sink(Metric {
+                            key: Cow::Borrowed(key),
+                            value,
+                            timestamp,
+                            step,
+                        });
+                    }
+                    step += 1;
+                }
+            })
+            .expect("failed to spawn the system metrics thread");
+        SystemMonitor {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops sampling and waits for the last sample to be taken.
+    pub fn stop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SystemMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct Sampler {
+    cpu: Option<CpuTimes>,
+    network: Option<NetworkBytes>,
+}
+
+impl Sampler {
+    fn new() -> Self {
+        Sampler {
+            cpu: CpuTimes::read(),
+            network: NetworkBytes::read(),
+        }
+    }
+
+    fn sample(&mut self) -> Vec<(&'static str, f64)> {
+        let mut metrics = Vec::new();
+
+        if let Some(cpu) = CpuTimes::read() {
+            if let Some(previous) = self.cpu.replace(cpu) {
+                let total = cpu.total.saturating_sub(previous.total);
+                let idle = cpu.idle.saturating_sub(previous.idle);
+                if total > 0 {
+                    let busy = (total - idle) as f64 / total as f64;
+                    metrics.push((CPU_UTILIZATION_PERCENTAGE, busy * 100.0));
+                }
+            }
+        }
+
+        if let Some((total, available)) = read_memory() {
+            let used = total.saturating_sub(available);
+            metrics.push((SYSTEM_MEMORY_USAGE_MEGABYTES, used as f64 / MEGABYTE));
+            metrics.push((
+                SYSTEM_MEMORY_USAGE_PERCENTAGE,
+                used as f64 / total as f64 * 100.0,
+            ));
+        }
+
+        if let Some((total, available)) = read_disk() {
+            let used = total.saturating_sub(available);
+            metrics.push((DISK_USAGE_MEGABYTES, used as f64 / MEGABYTE));
+            metrics.push((DISK_AVAILABLE_MEGABYTES, available as f64 / MEGABYTE));
+            metrics.push((DISK_USAGE_PERCENTAGE, used as f64 / total as f64 * 100.0));
+        }
+
+        // Like MLflow, the network traffic is reported relative to the start of the monitor.
+        if let (Some(start), Some(now)) = (self.network, NetworkBytes::read()) {
+            let received = now.received.saturating_sub(start.received);
+            let transmitted = now.transmitted.saturating_sub(start.transmitted);
+            metrics.push((NETWORK_RECEIVE_MEGABYTES, received as f64 / MEGABYTE));
+            metrics.push((NETWORK_TRANSMIT_MEGABYTES, transmitted as f64 / MEGABYTE));
+        }
+
+        metrics
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CpuTimes {
+    total: u64,
+    idle: u64,
+}
+
+impl CpuTimes {
+    /// Reads the aggregated line of `/proc/stat`.
+    fn read() -> Option<Self> {
+        let stat = fs::read_to_string("/proc/stat").ok()?;
+        let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+        let times = line
+            .split_whitespace()
+            .skip(1)
+            .map(str::parse::<u64>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        // user nice system idle iowait irq softirq steal guest guest_nice,
+        // where guest time is already included in user and nice.
+        let total = times.iter().take(8).sum();
+        let idle = times.get(3)? + times.get(4).unwrap_or(&0);
+        Some(CpuTimes { total, idle })
+    }
+}
+
+/// Reads the total and available memory in bytes from `/proc/meminfo`.
+fn read_memory() -> Option<(u64, u64)> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let line = meminfo.lines().find(|line| line.starts_with(name))?;
+        let kilobytes = line[name.len()..].trim().trim_end_matches("kB").trim();
+        kilobytes.parse::<u64>().ok().map(|kb| kb * 1024)
+    };
+    Some((field("MemTotal:")?, field("MemAvailable:")?))
+}
+
+/// Reads the total and available space of the root file system in bytes.
+#[cfg(unix)]
+fn read_disk() -> Option<(u64, u64)> {
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: The path is a valid C string and `stat` is only read if the call succeeded.
+    let stat = unsafe {
+        if libc::statvfs(b"/\0".as_ptr().cast(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    let block_size = stat.f_frsize as u64;
+    Some((
+        stat.f_blocks as u64 * block_size,
+        stat.f_bavail as u64 * block_size,
+    ))
+}
+
+#[cfg(not(unix))]
+fn read_disk() -> Option<(u64, u64)> {
+    None
+}
+
+#[derive(Clone, Copy)]
+struct NetworkBytes {
+    received: u64,
+    transmitted: u64,
+}
+
+impl NetworkBytes {
+    /// Sums up the traffic of all interfaces listed in `/proc/net/dev`.
+    fn read() -> Option<Self> {
+        let dev = fs::read_to_string("/proc/net/dev").ok()?;
+        let mut bytes = NetworkBytes {
+            received: 0,
+            transmitted: 0,
+        };
+        for line in dev.lines().skip(2) {
+            let (_, counters) = line.split_once(':')?;
+            let counters = counters.split_whitespace().collect::<Vec<_>>();
+            bytes.received += counters.first()?.parse::<u64>().ok()?;
+            bytes.transmitted += counters.get(8)?.parse::<u64>().ok()?;
+        }
+        Some(bytes)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CPU_UTILIZATION_PERCENTAGE, SYSTEM_MEMORY_USAGE_MEGABYTES};
+    use crate::{
+        testing::MockServer,
+        tracking::{LiveRun, TrackingRun},
+        Client,
+    };
+
+    #[test]
+    fn system_metrics_are_logged() {
+        let server = MockServer::start();
+        let mut run = LiveRun::start(server.client(), &"0".into()).unwrap();
+        run.monitor_system(Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(100));
+        let live = run.finish().unwrap().run_id;
+
+        let mut run = TrackingRun::new();
+        run.monitor_system(Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(100));
+        let mut client = server.client();
+        let tracked = run.submit(&mut client, &"0".into()).unwrap().info.run_id;
+
+        let mut store = server.store();
+        for id in [live, tracked] {
+            let memory = store.get_metric_history(&id, SYSTEM_MEMORY_USAGE_MEGABYTES);
+            let memory = memory.unwrap();
+            assert!(memory.len() > 1, "{:?}", memory);
+            assert!(memory.iter().all(|metric| metric.value > 0.0));
+            // The utilization is measured between two samples.
+            let cpu = store.get_metric_history(&id, CPU_UTILIZATION_PERCENTAGE);
+            assert!(cpu.unwrap().iter().all(|metric| metric.value <= 100.0));
+        }
+    }
+}